@@ -0,0 +1,7 @@
+-- Corbeille : date de suppression d'un personnage (NULL tant qu'il est actif)
+-- Les appareils poussent leurs suppressions comme des "pierres tombales" :
+-- une ligne avec deleted_at renseigné, que les autres appareils mettent à la corbeille.
+ALTER TABLE personnages ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP WITH TIME ZONE DEFAULT NULL;
+
+-- Index pour retrouver rapidement les suppressions à propager
+CREATE INDEX IF NOT EXISTS idx_personnages_deleted_at ON personnages(deleted_at) WHERE deleted_at IS NOT NULL;
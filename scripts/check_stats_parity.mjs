@@ -0,0 +1,83 @@
+// Runs the stats engine of the character sheet (src/utils/statsEngine.ts) on the
+// fixture the Rust engine is tested with (logic.rs, test_stats_parity_fixture)
+// and checks both find the values the fixture expects.
+// Usage: npm run test:stats
+import { mkdtempSync, mkdirSync, readFileSync, rmSync, writeFileSync } from 'node:fs';
+import { tmpdir } from 'node:os';
+import { dirname, join } from 'node:path';
+import { fileURLToPath, pathToFileURL } from 'node:url';
+import ts from 'typescript';
+
+const root = join(dirname(fileURLToPath(import.meta.url)), '..');
+const readJson = (path) => JSON.parse(readFileSync(join(root, path), 'utf-8'));
+
+// The engine and the modules it imports at runtime (type-only imports are dropped)
+const MODULES = ['utils/statsEngine', 'utils/alcohol', 'utils/sacUtils'];
+
+// Keys renamed by the serde aliases of the Rust rules structs (commands.rs)
+const ALIASES = {
+    ID: 'id',
+    Name_M: 'name_m',
+    Name_F: 'name_f',
+    Min: 'min',
+    Max: 'max',
+    Vitesse: 'vitesse',
+    Metiers_impossibles: 'metiers_impossibles',
+    Nyctalopie: 'nyctalopie',
+    Specialisations: 'specialisations',
+    SousSpecialisations: 'sous_specialisations',
+    Attributs_automatisables: 'attributs_automatisables',
+    Attributs_specifiques: 'attributs_specifiques',
+};
+
+const withAliases = (value) => {
+    if (Array.isArray(value)) return value.map(withAliases);
+    if (value === null || typeof value !== 'object') return value;
+    return Object.fromEntries(Object.entries(value).map(([k, v]) => [ALIASES[k] ?? k, withAliases(v)]));
+};
+
+const loadEngine = async () => {
+    const out = mkdtempSync(join(tmpdir(), 'stats-parity-'));
+    for (const module of MODULES) {
+        const source = readFileSync(join(root, 'src', `${module}.ts`), 'utf-8');
+        const { outputText } = ts.transpileModule(source, {
+            compilerOptions: { module: ts.ModuleKind.ESNext, target: ts.ScriptTarget.ES2020 },
+        });
+        const file = join(out, `${module}.js`);
+        mkdirSync(dirname(file), { recursive: true });
+        writeFileSync(file, outputText.replace(/from (['"])(\.{1,2}\/[^'"]+)\1/g, 'from $1$2.js$1'));
+    }
+    try {
+        return await import(pathToFileURL(join(out, 'utils/statsEngine.js')).href);
+    } finally {
+        rmSync(out, { recursive: true, force: true });
+    }
+};
+
+const { computeEquippedValues, computeDerivedStats } = await loadEngine();
+const fixture = readJson('src-tauri/tests/fixtures/stats_parity.json');
+const gameRules = {
+    origines: withAliases(readJson('src-tauri/data/config/origines.json')),
+    metiers: withAliases(readJson('src-tauri/data/config/metiers.json')),
+    corruption_palier: readJson('src-tauri/data/config/corruption_palier.json'),
+};
+
+const { sheet, refs, expected } = fixture;
+const equipped = computeEquippedValues(sheet, refs, gameRules);
+const derived = computeDerivedStats(sheet, refs, gameRules, equipped);
+
+// What the panels show: derived stats get the `temp` of the sheet on top
+const shown = (key) => key in equipped
+    ? equipped[key]
+    : { value: derived[key].value + (sheet.magic?.[key]?.temp ?? sheet.movement?.[key]?.temp ?? 0), components: derived[key].details.components };
+
+let failures = 0;
+for (const [key, value] of Object.entries(expected)) {
+    const { value: actual, components } = shown(key);
+    if (actual !== value) {
+        failures += 1;
+        console.error(`${key}: ${actual} instead of ${value}`, components);
+    }
+}
+if (failures > 0) process.exit(1);
+console.log(`Stats parity: ${Object.keys(expected).length} values match`);
@@ -0,0 +1,164 @@
+use crate::sheet::{read_i64, read_str};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Port of `src/utils/alcohol.ts`. One row per dose (0 to 10), columns:
+// COU, INT, CHA, AD, FO, PER, ES, AT, PRD, PI
+const ALCOOL_LEGER: [[i32; 10]; 11] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 0
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 1
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 2
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 3
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 4
+    [1, -1, 0, 0, 0, -1, 0, 0, 0, 0],     // Dose 5
+    [1, -1, 0, -1, 0, -1, 0, 1, 0, 0],    // Dose 6
+    [2, -2, -1, -2, 0, -2, 0, 1, 0, 0],   // Dose 7
+    [2, -2, -2, -2, 1, -2, 0, 1, 0, 0],   // Dose 8
+    [3, -3, -3, -3, 2, -3, 0, 2, -2, -2], // Dose 9
+    [3, -3, -4, -3, 2, -3, 0, 2, -2, -2], // Dose 10
+];
+
+const ALCOOL_FORT: [[i32; 10]; 11] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 0
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 1
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],       // Dose 2
+    [0, -1, 0, -1, 0, -1, 0, 0, 0, 0],    // Dose 3
+    [1, -1, -1, -2, 0, -1, 0, 0, 0, 0],   // Dose 4
+    [1, -2, -1, -2, 1, -2, 0, -1, -1, 0], // Dose 5
+    [2, -2, -2, -3, 2, -2, 0, 1, -2, -2], // Dose 6
+    [2, -3, -2, -3, 2, -3, 0, 1, -2, -2], // Dose 7
+    [3, -3, -3, -3, 3, -3, 0, 2, -3, -3], // Dose 8
+    [3, -3, -4, -3, 3, -3, 0, 2, -3, 1],  // Dose 9
+    [3, -3, -5, -3, 3, -3, 0, 2, -3, 2],  // Dose 10
+];
+
+const GUEULE_DE_BOIS: [[i32; 10]; 11] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],        // Dose 0
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],        // Dose 1
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],        // Dose 2
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],        // Dose 3
+    [0, -1, 0, 0, 0, -1, 0, 0, 0, 0],      // Dose 4
+    [0, -1, 0, 0, 0, -1, 0, 0, 0, 0],      // Dose 5
+    [0, -1, -1, 0, 0, -1, 0, 0, 0, 0],     // Dose 6
+    [0, -1, -1, 0, 0, -1, 0, 0, 0, 0],     // Dose 7
+    [0, -2, -1, -1, 0, -2, 0, -1, -1, 0],  // Dose 8
+    [0, -2, -2, -1, 0, -2, 0, -1, -1, 0],  // Dose 9
+    [0, -2, -3, -2, 0, -2, -1, -1, -1, 0], // Dose 10
+];
+
+pub const DOSE_MAX: i64 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlcoholModifiers {
+    pub courage: i32,
+    pub intelligence: i32,
+    pub charisme: i32,
+    pub adresse: i32,
+    pub force: i32,
+    pub perception: i32,
+    pub esquive: i32,
+    pub attaque: i32,
+    pub parade: i32,
+    pub pi: i32, // Applied to Weapon Damage
+}
+
+impl AlcoholModifiers {
+    fn from_row(row: &[i32; 10]) -> Self {
+        AlcoholModifiers {
+            courage: row[0],
+            intelligence: row[1],
+            charisme: row[2],
+            adresse: row[3],
+            force: row[4],
+            perception: row[5],
+            esquive: row[6],
+            attaque: row[7],
+            parade: row[8],
+            pi: row[9],
+        }
+    }
+
+    /// Value for a characteristic key of the sheet (`courage`, `perception`...).
+    pub fn get(&self, key: &str) -> i32 {
+        match key {
+            "courage" => self.courage,
+            "intelligence" => self.intelligence,
+            "charisme" => self.charisme,
+            "adresse" => self.adresse,
+            "force" => self.force,
+            "perception" => self.perception,
+            "esquive" => self.esquive,
+            "attaque" => self.attaque,
+            "parade" => self.parade,
+            "pi" => self.pi,
+            _ => 0,
+        }
+    }
+
+    fn without_malus(self) -> Self {
+        let keep = |v: i32| v.max(0);
+        AlcoholModifiers {
+            courage: keep(self.courage),
+            intelligence: keep(self.intelligence),
+            charisme: keep(self.charisme),
+            adresse: keep(self.adresse),
+            force: keep(self.force),
+            perception: keep(self.perception),
+            esquive: keep(self.esquive),
+            attaque: keep(self.attaque),
+            parade: keep(self.parade),
+            pi: keep(self.pi),
+        }
+    }
+
+    fn plus(self, other: Self) -> Self {
+        AlcoholModifiers {
+            courage: self.courage + other.courage,
+            intelligence: self.intelligence + other.intelligence,
+            charisme: self.charisme + other.charisme,
+            adresse: self.adresse + other.adresse,
+            force: self.force + other.force,
+            perception: self.perception + other.perception,
+            esquive: self.esquive + other.esquive,
+            attaque: self.attaque + other.attaque,
+            parade: self.parade + other.parade,
+            pi: self.pi + other.pi,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlcoholState {
+    pub leger: AlcoholModifiers,
+    pub fort: AlcoholModifiers,
+    pub gueule_de_bois: AlcoholModifiers,
+}
+
+impl AlcoholState {
+    pub fn total(&self) -> AlcoholModifiers {
+        self.leger.plus(self.fort).plus(self.gueule_de_bois)
+    }
+}
+
+fn dose(data: &Value, pointer: &str) -> usize {
+    read_i64(data, pointer).clamp(0, DOSE_MAX) as usize
+}
+
+/// Same as `getAlcoholModifiers`, with the Flibustier rule applied
+/// (strong alcohol never gives them a malus).
+pub fn alcohol_modifiers(data: &Value) -> AlcoholState {
+    let flibustier = read_str(data, "/identity/specialisation").to_lowercase() == "flibustier";
+    let fort = AlcoholModifiers::from_row(&ALCOOL_FORT[dose(data, "/status/alcohol/fort")]);
+
+    AlcoholState {
+        leger: AlcoholModifiers::from_row(&ALCOOL_LEGER[dose(data, "/status/alcohol/leger")]),
+        fort: if flibustier {
+            fort.without_malus()
+        } else {
+            fort
+        },
+        gueule_de_bois: AlcoholModifiers::from_row(
+            &GUEULE_DE_BOIS[dose(data, "/status/alcohol/gueule_de_bois")],
+        ),
+    }
+}
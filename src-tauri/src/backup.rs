@@ -0,0 +1,395 @@
+use crate::db::AppState;
+use crate::files::write_atomic;
+use crate::migrations::{run_migrations, schema_version};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OptionalExtension, MAIN_DB};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+// A backup is a gzipped tar holding `manifest.json` and a copy of the database
+// taken with the SQLite online backup API, so it is consistent even while the
+// app is writing. Importing goes the other way through the same API, which
+// replaces the live database in one step.
+
+pub const BACKUP_FORMAT: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.json";
+const DB_NAME: &str = "codex_debilium.db";
+const AUTO_BACKUP_KEY: &str = "auto_backup";
+const AUTO_BACKUP_PREFIX: &str = "auto-";
+/// Folder of the automatic backups, in the app data dir
+pub const BACKUPS_DIR: &str = "backups";
+pub const BACKUP_EXTENSION: &str = "tar.gz";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub app_version: String,
+    pub schema_version: i64,
+    pub created_at: String,
+    pub personnages: i64,
+    pub ref_items: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoBackup {
+    pub enabled: bool,
+    /// Automatic backups (at startup and before an import) kept in the backups folder
+    pub keep: usize,
+}
+
+impl Default for AutoBackup {
+    fn default() -> Self {
+        AutoBackup {
+            enabled: false,
+            keep: 5,
+        }
+    }
+}
+
+fn temp_dir() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join(format!("codex-backup-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Rows in `table`, 0 when a database from an older build does not have it yet.
+fn count(conn: &Connection, table: &str) -> Result<i64, String> {
+    if !table_exists(conn, table)? {
+        return Ok(0);
+    }
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Writes the archive next to `path` first and renames it, so a failed export
+/// never leaves a truncated file behind.
+pub fn write_backup(conn: &Connection, path: &Path, app_version: &str) -> Result<Manifest, String> {
+    let work = temp_dir()?;
+    let result = (|| {
+        let db_copy = work.join(DB_NAME);
+        conn.backup(MAIN_DB, &db_copy, None)
+            .map_err(|e| e.to_string())?;
+
+        let copy = Connection::open(&db_copy).map_err(|e| e.to_string())?;
+        let manifest = Manifest {
+            format: BACKUP_FORMAT,
+            app_version: app_version.to_string(),
+            schema_version: schema_version(&copy)?,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            personnages: count(&copy, "personnages")?,
+            ref_items: count(&copy, "ref_items")?,
+        };
+        drop(copy);
+        let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+
+        write_atomic(path, |part| {
+            let file = File::create(part).map_err(|e| e.to_string())?;
+            let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(manifest_json.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+            archive
+                .append_data(&mut header, MANIFEST_NAME, manifest_json.as_slice())
+                .map_err(|e| e.to_string())?;
+            archive
+                .append_path_with_name(&db_copy, DB_NAME)
+                .map_err(|e| e.to_string())?;
+            archive
+                .into_inner()
+                .and_then(|gz| gz.finish())
+                .and_then(|file| file.sync_all())
+                .map_err(|e| e.to_string())
+        })?;
+        Ok(manifest)
+    })();
+    let _ = std::fs::remove_dir_all(&work);
+    result
+}
+
+/// Unpacks and checks an archive. Returns its manifest and the path of the
+/// database, upgraded to the current schema, inside `work`.
+fn open_backup(path: &Path, work: &Path) -> Result<(Manifest, PathBuf), String> {
+    let file = File::open(path).map_err(|e| format!("Sauvegarde illisible: {}", e))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut manifest = None;
+    let db_path = work.join(DB_NAME);
+
+    for entry in archive
+        .entries()
+        .map_err(|e| format!("Sauvegarde illisible: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Sauvegarde illisible: {}", e))?;
+        let name = entry.path().map_err(|e| e.to_string())?.into_owned();
+        if name == Path::new(MANIFEST_NAME) {
+            let parsed: Manifest = serde_json::from_reader(&mut entry)
+                .map_err(|e| format!("Manifeste invalide: {}", e))?;
+            manifest = Some(parsed);
+        } else if name == Path::new(DB_NAME) {
+            entry.unpack(&db_path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let manifest = manifest.ok_or("Manifeste absent de la sauvegarde")?;
+    if manifest.format > BACKUP_FORMAT {
+        return Err(format!(
+            "Format de sauvegarde {} inconnu: mettez l'application à jour",
+            manifest.format
+        ));
+    }
+    if !db_path.is_file() {
+        return Err("Base de données absente de la sauvegarde".to_string());
+    }
+
+    let mut conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if integrity != "ok" {
+        return Err(format!("Sauvegarde corrompue: {}", integrity));
+    }
+    let version = schema_version(&conn)?;
+    if version != manifest.schema_version {
+        return Err(format!(
+            "Schéma {} annoncé mais {} trouvé dans la sauvegarde",
+            manifest.schema_version, version
+        ));
+    }
+    // Refuses a newer schema, upgrades an older one
+    run_migrations(&mut conn)?;
+    Ok((manifest, db_path))
+}
+
+/// Replaces the whole live database with the archive content, in one step.
+pub fn restore_backup(conn: &mut Connection, path: &Path) -> Result<Manifest, String> {
+    let work = temp_dir()?;
+    let result = open_backup(path, &work).and_then(|(manifest, db_path)| {
+        conn.restore(MAIN_DB, &db_path, None::<fn(rusqlite::backup::Progress)>)
+            .map_err(|e| e.to_string())?;
+        Ok(manifest)
+    });
+    let _ = std::fs::remove_dir_all(&work);
+    result
+}
+
+/// Also read at startup before the migrations, hence the check on `db_meta`.
+pub fn load_auto_backup(conn: &Connection) -> Result<AutoBackup, String> {
+    if !table_exists(conn, "db_meta")? {
+        return Ok(AutoBackup::default());
+    }
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM db_meta WHERE key = ?1",
+            [AUTO_BACKUP_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(value
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Startup backup into `dir` when enabled, keeping the `keep` latest ones.
+/// Returns the new archive.
+pub fn auto_backup(
+    conn: &Connection,
+    dir: &Path,
+    app_version: &str,
+) -> Result<Option<PathBuf>, String> {
+    let settings = load_auto_backup(conn)?;
+    if !settings.enabled {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let name = format!(
+        "{}{}.{}",
+        AUTO_BACKUP_PREFIX,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        BACKUP_EXTENSION
+    );
+    let path = dir.join(name);
+    write_backup(conn, &path, app_version)?;
+
+    // Timestamped names sort by date
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(AUTO_BACKUP_PREFIX) && n.ends_with(BACKUP_EXTENSION))
+        })
+        .collect();
+    existing.sort();
+    let surplus = existing.len().saturating_sub(settings.keep.max(1));
+    for old in &existing[..surplus] {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(Some(path))
+}
+
+#[tauri::command]
+pub fn export_backup(
+    path: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Manifest, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    write_backup(
+        &db,
+        Path::new(&path),
+        &app.package_info().version.to_string(),
+    )
+}
+
+/// Replaces all local data with the archive. Reload everything afterwards.
+/// When automatic backups are on, the live database is backed up first and the
+/// import is refused if that fails.
+#[tauri::command]
+pub fn import_backup(
+    path: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Manifest, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(BACKUPS_DIR);
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    auto_backup(&db, &dir, &app.package_info().version.to_string())
+        .map_err(|e| format!("Sauvegarde avant import impossible: {}", e))?;
+    restore_backup(&mut db, Path::new(&path))
+}
+
+#[tauri::command]
+pub fn get_auto_backup(state: State<AppState>) -> Result<AutoBackup, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    load_auto_backup(&db)
+}
+
+#[tauri::command]
+pub fn set_auto_backup(settings: AutoBackup, state: State<AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    db.execute(
+        "INSERT OR REPLACE INTO db_meta (key, value) VALUES (?1, ?2)",
+        params![AUTO_BACKUP_KEY, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::MIGRATIONS;
+
+    fn db_with(name: &str) -> Connection {
+        let conn = crate::migrations::test_db_with(&serde_json::json!({}));
+        conn.execute("UPDATE personnages SET name = ?1 WHERE id = 'p1'", [name])
+            .unwrap();
+        conn
+    }
+
+    fn name(conn: &Connection) -> String {
+        conn.query_row("SELECT name FROM personnages", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let dir = temp_dir().unwrap();
+        let path = dir.join("sauvegarde.tar.gz");
+
+        let manifest = write_backup(&db_with("Gurdil"), &path, "1.0.0").unwrap();
+        assert_eq!(manifest.personnages, 1);
+        assert_eq!(manifest.schema_version, MIGRATIONS.last().unwrap().version);
+
+        let mut other = db_with("Lanfeust");
+        restore_backup(&mut other, &path).unwrap();
+        assert_eq!(name(&other), "Gurdil");
+
+        // Anything else is refused and the live data stays as it was
+        std::fs::write(dir.join("bad.tar.gz"), b"pas une archive").unwrap();
+        assert!(restore_backup(&mut other, &dir.join("bad.tar.gz")).is_err());
+        assert_eq!(name(&other), "Gurdil");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_auto_backups_rotate() {
+        let dir = temp_dir().unwrap();
+        let conn = db_with("Gurdil");
+        assert_eq!(auto_backup(&conn, &dir, "1.0.0").unwrap(), None);
+
+        conn.execute(
+            "INSERT INTO db_meta (key, value) VALUES (?1, '{\"enabled\":true,\"keep\":2}')",
+            [AUTO_BACKUP_KEY],
+        )
+        .unwrap();
+        for day in 1..=3 {
+            // Older backups, named as the rotation expects
+            std::fs::write(dir.join(format!("auto-2020010{}-000000.tar.gz", day)), b"").unwrap();
+        }
+        let latest = auto_backup(&conn, &dir, "1.0.0").unwrap().unwrap();
+
+        let mut remaining: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![dir.join("auto-20200103-000000.tar.gz"), latest]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_auto_backup_before_migrations() {
+        let dir = temp_dir().unwrap();
+        // Fresh install: nothing to back up yet
+        let empty = Connection::open_in_memory().unwrap();
+        assert_eq!(auto_backup(&empty, &dir, "1.0.0").unwrap(), None);
+
+        // As left by a build from before migrations and ref_items
+        let mut old = Connection::open_in_memory().unwrap();
+        old.execute_batch(
+            "CREATE TABLE personnages (id TEXT PRIMARY KEY, name TEXT NOT NULL, data TEXT NOT NULL, updated_at TEXT NOT NULL);
+             CREATE TABLE db_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO personnages VALUES ('p1', 'Gurdil', '{}', 'now');
+             INSERT INTO db_meta VALUES ('auto_backup', '{\"enabled\":true}');",
+        )
+        .unwrap();
+        let path = auto_backup(&old, &dir, "1.0.0").unwrap().unwrap();
+        run_migrations(&mut old).unwrap();
+
+        // The archive holds the old schema and is upgraded on restore
+        let mut other = db_with("Lanfeust");
+        let manifest = restore_backup(&mut other, &path).unwrap();
+        assert_eq!((manifest.schema_version, manifest.ref_items), (0, 0));
+        assert_eq!(name(&other), "Gurdil");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use crate::consumables::{vieillir, Peremption};
+use crate::db::{
+    load_personnage_data, load_ref_items, log_personnage_event, store_personnage_data, AppState,
+    RefEquipement,
+};
+use crate::fatigue::{appliquer_marche, appliquer_repos, FatigueReport, QualiteRepos};
+use crate::modifiers::{ecouler, EffetTemporaire};
+use crate::sheet::{read_i64, read_str, set_value};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Game time is counted in minutes from the start of the campaign, day 1 at
+// midnight. Daily effects (drug withdrawal, corruption, food) apply once per
+// midnight crossed, so two advances of 12 hours count as one day. Temporary
+// effects count down by the minute.
+
+pub const MINUTES_PAR_HEURE: i64 = 60;
+pub const MINUTES_PAR_JOUR: i64 = 24 * MINUTES_PAR_HEURE;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Duree {
+    pub jours: i64,
+    pub heures: i64,
+    pub minutes: i64,
+}
+
+impl Duree {
+    pub fn en_minutes(&self) -> i64 {
+        self.jours * MINUTES_PAR_JOUR + self.heures * MINUTES_PAR_HEURE + self.minutes
+    }
+}
+
+/// What the characters do while time passes; fatigue depends on it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "activite", rename_all = "snake_case")]
+pub enum Activite {
+    /// Nothing that changes fatigue
+    #[default]
+    Autre,
+    Repos {
+        qualite: Option<QualiteRepos>,
+    },
+    Marche,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeureDeJeu {
+    pub clock_id: String,
+    pub minutes: i64,
+    /// Starts at 1
+    pub jour: i64,
+    pub heure: i64,
+    pub minute: i64,
+}
+
+impl HeureDeJeu {
+    pub fn new(clock_id: &str, minutes: i64) -> Self {
+        let minutes = minutes.max(0);
+        HeureDeJeu {
+            clock_id: clock_id.to_string(),
+            minutes,
+            jour: minutes / MINUTES_PAR_JOUR + 1,
+            heure: minutes % MINUTES_PAR_JOUR / MINUTES_PAR_HEURE,
+            minute: minutes % MINUTES_PAR_HEURE,
+        }
+    }
+}
+
+/// Number of midnights between two clock readings.
+pub fn jours_passes(avant: i64, apres: i64) -> i64 {
+    (apres.div_euclid(MINUTES_PAR_JOUR) - avant.div_euclid(MINUTES_PAR_JOUR)).max(0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Changement {
+    pub domaine: String,
+    pub avant: Value,
+    pub apres: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RapportPersonnage {
+    pub id: String,
+    pub changements: Vec<Changement>,
+    pub fatigue: Option<FatigueReport>,
+    pub peremptions: Vec<Peremption>,
+    pub effets_expires: Vec<EffetTemporaire>,
+    /// What could not be applied to this character; the rest still was
+    pub erreurs: Vec<String>,
+}
+
+impl RapportPersonnage {
+    fn noter(&mut self, domaine: &str, avant: Value, apres: Value) {
+        if avant != apres {
+            self.changements.push(Changement {
+                domaine: domaine.to_string(),
+                avant,
+                apres,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvancementTemps {
+    pub avant: HeureDeJeu,
+    pub apres: HeureDeJeu,
+    pub jours_passes: i64,
+    pub personnages: Vec<RapportPersonnage>,
+}
+
+/// Full hours of `activite` once `minutes` are added to the minutes left over by
+/// the previous advances of the same activity. The new leftover is kept in
+/// `status.fatigue.minutes_reportees`, so that advancing 30 minutes twice counts
+/// as one hour.
+fn heures_entieres(data: &mut Value, activite: &str, minutes: i64) -> i64 {
+    let reportees = if read_str(data, "/status/fatigue/minutes_reportees/activite") == activite {
+        read_i64(data, "/status/fatigue/minutes_reportees/minutes")
+    } else {
+        0
+    };
+    let total = reportees + minutes.max(0);
+    set_value(
+        data,
+        "/status/fatigue/minutes_reportees",
+        serde_json::json!({ "activite": activite, "minutes": total % MINUTES_PAR_HEURE }),
+    );
+    total / MINUTES_PAR_HEURE
+}
+
+/// Applies `minutes` of game time, `jours` of which cross midnight, to one sheet.
+pub fn faire_passer_le_temps(
+    id: &str,
+    data: &mut Value,
+    refs: &[RefEquipement],
+    minutes: i64,
+    jours: i64,
+    activite: Activite,
+) -> Result<RapportPersonnage, String> {
+    let mut rapport = RapportPersonnage {
+        id: id.to_string(),
+        ..Default::default()
+    };
+
+    rapport.fatigue = match activite {
+        Activite::Autre => None,
+        Activite::Repos { qualite } => {
+            let heures = heures_entieres(data, "repos", minutes);
+            Some(appliquer_repos(data, heures, qualite)?)
+        }
+        Activite::Marche => {
+            let heures = heures_entieres(data, "marche", minutes);
+            Some(appliquer_marche(data, heures))
+        }
+    };
+
+    // Unreadable effects are left as they are rather than holding back the
+    // whole party
+    match ecouler(data, 0, minutes) {
+        Ok(expires) => rapport.effets_expires = expires,
+        Err(e) => rapport.erreurs.push(e),
+    }
+
+    if jours > 0 {
+        // Days since the last dose keep piling up for an addict
+        let drogue = read_str(data, "/status/drug/type");
+        if !drogue.is_empty() && drogue != "Aucune" {
+            let avant = read_i64(data, "/status/drug/jours_retard");
+            set_value(
+                data,
+                "/status/drug/jours_retard",
+                Value::from(avant + jours),
+            );
+            rapport.noter("drogue", Value::from(avant), Value::from(avant + jours));
+        }
+
+        let avant = read_i64(data, "/vitals/corruption/current");
+        let max = data
+            .pointer("/vitals/corruption/max")
+            .map(|_| read_i64(data, "/vitals/corruption/max"))
+            .unwrap_or(100);
+        let daily = read_i64(data, "/vitals/corruption/daily");
+        let apres = (avant + daily * jours).clamp(0, max.max(0));
+        set_value(data, "/vitals/corruption/current", Value::from(apres));
+        rapport.noter("corruption", Value::from(avant), Value::from(apres));
+
+        rapport.peremptions = vieillir(data, refs, jours);
+    }
+
+    Ok(rapport)
+}
+
+fn horloge_du_personnage(conn: &Connection, id: &str) -> Result<String, String> {
+    let clock_id: Option<String> = conn
+        .query_row(
+            "SELECT clock_id FROM game_clock_members WHERE personnage_id = ?1",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(clock_id.unwrap_or_else(|| id.to_string()))
+}
+
+fn lire_horloge(conn: &Connection, clock_id: &str) -> Result<HeureDeJeu, String> {
+    let minutes: Option<i64> = conn
+        .query_row(
+            "SELECT minutes FROM game_clocks WHERE clock_id = ?1",
+            [clock_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(HeureDeJeu::new(clock_id, minutes.unwrap_or(0)))
+}
+
+/// Characters following a clock: its members, plus the character it is named
+/// after when that one has not joined another clock. Trashed characters are
+/// left out.
+fn membres(conn: &Connection, clock_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.personnage_id FROM game_clock_members m
+             JOIN personnages p ON p.id = m.personnage_id
+             WHERE m.clock_id = ?1 AND p.deleted_at IS NULL
+             UNION
+             SELECT p.id FROM personnages p
+             WHERE p.id = ?1
+               AND p.deleted_at IS NULL
+               AND NOT EXISTS (SELECT 1 FROM game_clock_members m WHERE m.personnage_id = p.id)
+             ORDER BY 1",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([clock_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_game_time(id: String, state: State<AppState>) -> Result<HeureDeJeu, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let clock_id = horloge_du_personnage(&db, &id)?;
+    lire_horloge(&db, &clock_id)
+}
+
+/// Makes the character follow a shared party clock, or its own with `None`.
+#[tauri::command]
+pub fn join_game_clock(
+    id: String,
+    clock_id: Option<String>,
+    state: State<AppState>,
+) -> Result<HeureDeJeu, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    match clock_id.as_deref() {
+        Some(clock_id) if clock_id != id => {
+            db.execute(
+                "INSERT INTO game_clock_members (personnage_id, clock_id) VALUES (?1, ?2)
+                 ON CONFLICT(personnage_id) DO UPDATE SET clock_id = excluded.clock_id",
+                params![id, clock_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        _ => {
+            db.execute(
+                "DELETE FROM game_clock_members WHERE personnage_id = ?1",
+                [&id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    let clock_id = horloge_du_personnage(&db, &id)?;
+    lire_horloge(&db, &clock_id)
+}
+
+/// Moves the character's clock forward and applies the elapsed time to every
+/// character following that clock.
+#[tauri::command]
+pub fn advance_time(
+    id: String,
+    duree: Duree,
+    activite: Option<Activite>,
+    state: State<AppState>,
+) -> Result<AvancementTemps, String> {
+    let minutes = duree.en_minutes();
+    if minutes <= 0 {
+        return Err("La durée doit être positive".to_string());
+    }
+
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let refs = load_ref_items(&db)?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let clock_id = horloge_du_personnage(&tx, &id)?;
+    let avant = lire_horloge(&tx, &clock_id)?;
+    let apres = HeureDeJeu::new(&clock_id, avant.minutes + minutes);
+    let jours = jours_passes(avant.minutes, apres.minutes);
+
+    let mut personnages = Vec::new();
+    for membre in membres(&tx, &clock_id)? {
+        let mut data = load_personnage_data(&tx, &membre)?;
+        let rapport = faire_passer_le_temps(
+            &membre,
+            &mut data,
+            &refs,
+            minutes,
+            jours,
+            activite.unwrap_or_default(),
+        )?;
+        let now = store_personnage_data(&tx, &membre, &data)?;
+        log_personnage_event(
+            &tx,
+            &membre,
+            "advance_time",
+            &serde_json::json!({
+                "clock_id": clock_id,
+                "avant": avant.minutes,
+                "apres": apres.minutes,
+                "activite": activite,
+            }),
+            &now,
+        )?;
+        personnages.push(rapport);
+    }
+
+    tx.execute(
+        "INSERT INTO game_clocks (clock_id, minutes, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(clock_id) DO UPDATE SET minutes = excluded.minutes, updated_at = excluded.updated_at",
+        params![clock_id, apres.minutes, chrono::Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(AvancementTemps {
+        avant,
+        apres,
+        jours_passes: jours,
+        personnages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_are_counted_at_midnight() {
+        let soir = HeureDeJeu::new("groupe", 20 * MINUTES_PAR_HEURE);
+        assert_eq!((soir.jour, soir.heure, soir.minute), (1, 20, 0));
+        let duree = Duree {
+            heures: 6,
+            ..Default::default()
+        };
+        assert_eq!(
+            jours_passes(soir.minutes, soir.minutes + duree.en_minutes()),
+            1
+        );
+        assert_eq!(jours_passes(0, 12 * MINUTES_PAR_HEURE), 0);
+    }
+
+    #[test]
+    fn test_daily_states_advance_together() {
+        let mut data = serde_json::json!({
+            "vitals": { "corruption": { "current": 3, "max": 100, "daily": -2 } },
+            "status": {
+                "drug": { "type": "ADD", "jours_retard": 1 },
+                "fatigue": { "etat": "Normal", "heures_marche": 0 }
+            },
+            "inventory": [{ "uid": "pain", "nom": "Pain", "peremption": "2 jours" }]
+        });
+
+        let rapport = faire_passer_le_temps(
+            "p1",
+            &mut data,
+            &[],
+            2 * MINUTES_PAR_JOUR,
+            2,
+            Activite::Marche,
+        )
+        .unwrap();
+
+        assert_eq!(read_i64(&data, "/status/drug/jours_retard"), 3);
+        // Corruption wears off but never below zero
+        assert_eq!(read_i64(&data, "/vitals/corruption/current"), 0);
+        assert!(rapport.peremptions[0].perime);
+        assert_eq!(read_str(&data, "/status/fatigue/etat"), "Epuisé 10");
+        assert_eq!(rapport.changements.len(), 2);
+    }
+
+    #[test]
+    fn test_leftover_minutes_carry_over() {
+        let mut data = serde_json::json!({
+            "status": { "fatigue": { "etat": "Normal", "heures_marche": 0 } }
+        });
+        let marche = |data: &mut Value, minutes| {
+            faire_passer_le_temps("p1", data, &[], minutes, 0, Activite::Marche).unwrap();
+        };
+
+        marche(&mut data, 90);
+        marche(&mut data, 100);
+        assert_eq!(read_i64(&data, "/status/fatigue/heures_marche"), 3);
+        marche(&mut data, 50);
+        assert_eq!(read_str(&data, "/status/fatigue/etat"), "Fatigué");
+
+        // Minutes left by a march do not count as rest
+        data["status"]["fatigue"]["minutes_reportees"]["minutes"] = Value::from(30);
+        assert_eq!(heures_entieres(&mut data, "repos", 30), 0);
+    }
+
+    #[test]
+    fn test_malformed_effects_are_reported() {
+        let mut data = serde_json::json!({
+            "vitals": { "corruption": { "current": 3, "daily": 1 } },
+            "temp_effects": [{ "uid": "a", "cible": "force", "valeur": "beaucoup" }]
+        });
+
+        let rapport =
+            faire_passer_le_temps("p1", &mut data, &[], MINUTES_PAR_JOUR, 1, Activite::Autre)
+                .unwrap();
+
+        assert_eq!(rapport.erreurs.len(), 1);
+        assert_eq!(data["temp_effects"][0]["valeur"], "beaucoup");
+        assert_eq!(read_i64(&data, "/vitals/corruption/current"), 4);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::db::{load_ref_items, log_personnage_event, AppState, RefEquipement};
+use crate::files::write_atomic;
+use crate::journal;
+use crate::versions::snapshot_version;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use tauri::{AppHandle, State};
+
+// `.codex` character file: one UTF-8 JSON document.
+//
+// {
+//   "format": "codex-personnage",
+//   "schema_version": 1,
+//   "app_version": "0.1.11",
+//   "exported_at": "<RFC 3339>",
+//   "personnage": { "id", "name", "updated_at", "data" },
+//   "ref_items": [ <ref_items rows used by the sheet, see RefEquipement> ],
+//   "avatar": "data:image/png;base64,..." | null
+// }
+//
+// The avatar is taken out of `data.identity.avatar_url` so the sheet stays
+// readable, and put back on import. `refId`s in the sheet point at the
+// exporting database; on import they are matched to local items by category,
+// ref_id and name, and items missing locally are added. A `refId` with no
+// embedded item is dropped and listed in the import report. See FORMAT_CODEX.md
+// for the format and when `schema_version` changes.
+
+pub const CODEX_FORMAT: &str = "codex-personnage";
+pub const CODEX_SCHEMA_VERSION: u32 = 1;
+pub const CODEX_EXTENSION: &str = "codex";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonnageExport {
+    pub id: String,
+    pub name: String,
+    pub updated_at: String,
+    pub data: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CodexFile {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub exported_at: String,
+    pub personnage: PersonnageExport,
+    pub ref_items: Vec<RefEquipement>,
+    #[serde(default)]
+    pub avatar: Option<String>,
+}
+
+/// What to do when the file's character already exists locally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiExistant {
+    /// Report the collision and import nothing
+    #[default]
+    Demander,
+    /// Overwrite, after saving the local sheet as a version
+    Remplacer,
+    /// Import under a new id
+    Copie,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFichier {
+    pub importe: bool,
+    pub id: String,
+    pub name: String,
+    /// Name of the local character with the same id, when nothing was imported
+    pub collision: Option<String>,
+    /// Reference items added to the local table
+    pub ref_items_ajoutes: usize,
+    /// `refId`s of the file with no embedded item, removed from the sheet
+    pub ref_ids_retires: Vec<i64>,
+}
+
+/// Every `refId` used in the sheet, companions and containers included.
+fn ref_ids(value: &Value, ids: &mut BTreeSet<i64>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(id) = map.get("refId").and_then(|v| v.as_i64()) {
+                ids.insert(id);
+            }
+            map.values().for_each(|v| ref_ids(v, ids));
+        }
+        Value::Array(items) => items.iter().for_each(|v| ref_ids(v, ids)),
+        _ => {}
+    }
+}
+
+/// Points each `refId` at its local item. The ones `map` does not know would
+/// point at whatever has that id here, so they are removed and collected in
+/// `retires`.
+fn remap_ref_ids(value: &mut Value, map: &HashMap<i64, i64>, retires: &mut BTreeSet<i64>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(id) = object.get("refId").and_then(|v| v.as_i64()) {
+                match map.get(&id) {
+                    Some(local) => {
+                        object.insert("refId".to_string(), Value::from(*local));
+                    }
+                    None => {
+                        object.remove("refId");
+                        retires.insert(id);
+                    }
+                }
+            }
+            object
+                .values_mut()
+                .for_each(|v| remap_ref_ids(v, map, retires));
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|v| remap_ref_ids(v, map, retires)),
+        _ => {}
+    }
+}
+
+pub fn build_codex_file(
+    conn: &Connection,
+    id: &str,
+    app_version: &str,
+) -> Result<CodexFile, String> {
+    let (name, data, updated_at): (String, String, String) = conn
+        .query_row(
+            "SELECT name, data, updated_at FROM personnages WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Personnage introuvable: {}", e))?;
+    let mut data: Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+
+    let avatar = data
+        .pointer_mut("/identity/avatar_url")
+        .map(Value::take)
+        .and_then(|v| v.as_str().map(str::to_string))
+        .filter(|a| !a.is_empty());
+    if let Some(identity) = data.get_mut("identity").and_then(|v| v.as_object_mut()) {
+        identity.insert("avatar_url".to_string(), Value::from(""));
+    }
+
+    let mut used = BTreeSet::new();
+    ref_ids(&data, &mut used);
+    let ref_items = load_ref_items(conn)?
+        .into_iter()
+        .filter(|r| used.contains(&r.id))
+        .collect();
+
+    Ok(CodexFile {
+        format: CODEX_FORMAT.to_string(),
+        schema_version: CODEX_SCHEMA_VERSION,
+        app_version: app_version.to_string(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        personnage: PersonnageExport {
+            id: id.to_string(),
+            name,
+            updated_at,
+            data,
+        },
+        ref_items,
+        avatar,
+    })
+}
+
+pub fn parse_codex_file(contenu: &str) -> Result<CodexFile, String> {
+    let file: CodexFile =
+        serde_json::from_str(contenu).map_err(|e| format!("Fichier .codex invalide: {}", e))?;
+    if file.format != CODEX_FORMAT {
+        return Err(format!(
+            "Ce n'est pas un fichier de personnage: {}",
+            file.format
+        ));
+    }
+    if file.schema_version > CODEX_SCHEMA_VERSION {
+        return Err(format!(
+            "Fichier créé par une version plus récente (format {}, app {})",
+            file.schema_version, file.app_version
+        ));
+    }
+    if !file.personnage.data.is_object() {
+        return Err("La fiche du personnage est vide".to_string());
+    }
+    Ok(file)
+}
+
+/// Local id of each embedded item, adding the ones this database lacks.
+fn resolve_ref_items(
+    conn: &Connection,
+    items: &[RefEquipement],
+) -> Result<(HashMap<i64, i64>, usize), String> {
+    let mut map = HashMap::new();
+    let mut added = 0;
+    for item in items {
+        let local: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM ref_items WHERE category = ?1 AND ref_id = ?2 AND nom = ?3",
+                params![item.category, item.ref_id, item.nom],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let local = match local {
+            Some(local) => local,
+            None => {
+                conn.execute(
+                    "INSERT INTO ref_items (category, ref_id, nom, degats, caracteristiques, protections, prix_info, craft, details)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        item.category,
+                        item.ref_id,
+                        item.nom,
+                        item.degats.to_string(),
+                        item.caracteristiques.to_string(),
+                        item.protections.to_string(),
+                        item.prix_info.to_string(),
+                        item.craft.to_string(),
+                        item.details.to_string(),
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+                added += 1;
+                conn.last_insert_rowid()
+            }
+        };
+        map.insert(item.id, local);
+    }
+    Ok((map, added))
+}
+
+pub fn import_codex_file(
+    conn: &Connection,
+    file: CodexFile,
+    si_existant: SiExistant,
+) -> Result<ImportFichier, String> {
+    let mut personnage = file.personnage;
+    let existant: Option<String> = conn
+        .query_row(
+            "SELECT name FROM personnages WHERE id = ?1",
+            [&personnage.id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(local_name) = existant {
+        match si_existant {
+            SiExistant::Demander => {
+                return Ok(ImportFichier {
+                    importe: false,
+                    id: personnage.id,
+                    name: personnage.name,
+                    collision: Some(local_name),
+                    ref_items_ajoutes: 0,
+                    ref_ids_retires: Vec::new(),
+                })
+            }
+            SiExistant::Remplacer => {
+                snapshot_version(conn, &personnage.id)?;
+                journal::clear(conn, &personnage.id)?;
+            }
+            SiExistant::Copie => {
+                personnage.id = uuid::Uuid::new_v4().to_string();
+                personnage.name = format!("{} (copie)", personnage.name);
+                if let Some(identity) = personnage
+                    .data
+                    .get_mut("identity")
+                    .and_then(|v| v.as_object_mut())
+                {
+                    identity.insert("nom".to_string(), Value::from(personnage.name.clone()));
+                }
+            }
+        }
+    }
+
+    let (map, ref_items_ajoutes) = resolve_ref_items(conn, &file.ref_items)?;
+    let mut ref_ids_retires = BTreeSet::new();
+    remap_ref_ids(&mut personnage.data, &map, &mut ref_ids_retires);
+    if let (Some(avatar), Some(identity)) = (
+        file.avatar,
+        personnage
+            .data
+            .get_mut("identity")
+            .and_then(|v| v.as_object_mut()),
+    ) {
+        identity.insert("avatar_url".to_string(), Value::from(avatar));
+    }
+
+    // An explicit import brings back a character deleted here
+    conn.execute(
+        "DELETE FROM personnages_tombstones WHERE id = ?1",
+        [&personnage.id],
+    )
+    .map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT OR REPLACE INTO personnages
+         (id, name, data, updated_at, deleted_at, version_label, version_client)
+         VALUES (?1, ?2, ?3, ?4, NULL, 'Import .codex', 'import')",
+        params![
+            personnage.id,
+            personnage.name,
+            personnage.data.to_string(),
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    log_personnage_event(
+        conn,
+        &personnage.id,
+        "import_file",
+        &serde_json::json!({
+            "app_version": file.app_version,
+            "exported_at": file.exported_at,
+            "ref_ids_retires": ref_ids_retires,
+        }),
+        &now,
+    )?;
+
+    Ok(ImportFichier {
+        importe: true,
+        id: personnage.id,
+        name: personnage.name,
+        collision: None,
+        ref_items_ajoutes,
+        ref_ids_retires: ref_ids_retires.into_iter().collect(),
+    })
+}
+
+#[tauri::command]
+pub fn export_personnage(
+    id: String,
+    path: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let file = build_codex_file(&db, &id, &app.package_info().version.to_string())?;
+    let contenu = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+
+    write_atomic(Path::new(&path), |part| {
+        std::fs::write(part, contenu).map_err(|e| e.to_string())
+    })
+}
+
+/// Imports a `.codex` file. With the default `si_existant`, a character whose
+/// id already exists is not imported and `collision` is set, so the UI can
+/// offer to replace it or import a copy.
+#[tauri::command]
+pub fn import_personnage_file(
+    path: String,
+    si_existant: Option<SiExistant>,
+    state: State<AppState>,
+) -> Result<ImportFichier, String> {
+    let contenu = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let file = parse_codex_file(&contenu)?;
+
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let report = import_codex_file(&tx, file, si_existant.unwrap_or_default())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::test_db as db;
+
+    fn add_ref(conn: &Connection, nom: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO ref_items (category, ref_id, nom) VALUES ('Armes', 1, ?1)",
+            [nom],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = db();
+        add_ref(&source, "Gourdin");
+        let epee = add_ref(&source, "Epée");
+        let data = serde_json::json!({
+            "identity": { "nom": "Gurdil", "avatar_url": "data:image/png;base64,AAAA" },
+            "inventory": [{ "uid": "a", "refId": epee }, { "uid": "b", "refId": 999 }]
+        });
+        source
+            .execute(
+                "INSERT INTO personnages (id, name, data, updated_at) VALUES ('p1', 'Gurdil', ?1, 'now')",
+                [data.to_string()],
+            )
+            .unwrap();
+
+        let file = build_codex_file(&source, "p1", "1.0.0").unwrap();
+        assert_eq!(file.ref_items.len(), 1);
+        assert_eq!(file.avatar.as_deref(), Some("data:image/png;base64,AAAA"));
+        let contenu = serde_json::to_string(&file).unwrap();
+
+        // The target only knows the sword, under another id
+        let target = db();
+        add_ref(&target, "Hache");
+        let local_epee = add_ref(&target, "Epée");
+        let report = import_codex_file(
+            &target,
+            parse_codex_file(&contenu).unwrap(),
+            SiExistant::Demander,
+        )
+        .unwrap();
+        assert!(report.importe);
+        assert_eq!(report.ref_items_ajoutes, 0);
+        // No item in the file for 999: the link is dropped rather than left
+        // pointing at an unrelated local item
+        assert_eq!(report.ref_ids_retires, vec![999]);
+
+        let data = crate::db::load_personnage_data(&target, "p1").unwrap();
+        assert_eq!(data["inventory"][0]["refId"], local_epee);
+        assert!(data["inventory"][1].get("refId").is_none());
+        assert_eq!(data["identity"]["avatar_url"], "data:image/png;base64,AAAA");
+    }
+
+    #[test]
+    fn test_id_collision() {
+        let conn = db();
+        conn.execute(
+            "INSERT INTO personnages (id, name, data, updated_at) VALUES ('p1', 'Gurdil', '{\"identity\":{}}', 'now')",
+            [],
+        )
+        .unwrap();
+        let contenu =
+            serde_json::to_string(&build_codex_file(&conn, "p1", "1.0.0").unwrap()).unwrap();
+
+        let report = import_codex_file(
+            &conn,
+            parse_codex_file(&contenu).unwrap(),
+            SiExistant::Demander,
+        )
+        .unwrap();
+        assert!(!report.importe);
+        assert_eq!(report.collision.as_deref(), Some("Gurdil"));
+
+        let copie = import_codex_file(
+            &conn,
+            parse_codex_file(&contenu).unwrap(),
+            SiExistant::Copie,
+        )
+        .unwrap();
+        assert_ne!(copie.id, "p1");
+        assert_eq!(copie.name, "Gurdil (copie)");
+
+        assert!(parse_codex_file("{\"format\":\"autre\"}").is_err());
+    }
+}
@@ -0,0 +1,808 @@
+use crate::commands::get_game_rules;
+use crate::db::{
+    load_personnage_data, load_ref_items, log_personnage_event, store_personnage_data, AppState,
+    RefEquipement,
+};
+use crate::dice::{lancer, Des, Jet, Lanceur};
+use crate::equipement::{armes_portees, pieces_portees, pr_solide_totale};
+use crate::logic::caracteristiques_equipees;
+use crate::modifiers::ecouler;
+use crate::sheet::{read_i64, read_str, set_value};
+use crate::vitals::{etat_vital, read_jauge, EtatVital, Jauge, Pool, SEUIL_MORT};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// An encounter lives in `combat_encounters` as one JSON document. Each assault
+// (round) every fighter gets its action points back and may parry or dodge once;
+// a natural 1 on the attack is a critical hit that cannot be defended and
+// ignores armour, a natural 20 is a fumble.
+
+pub const PA_PAR_ASSAUT: i64 = 2;
+pub const COUT_ATTAQUE: i64 = 1;
+const DE_ATTAQUE: &str = "1D20";
+const DE_INITIATIVE: &str = "1D6";
+// Force above this adds one point of damage per point
+pub const SEUIL_BONUS_FORCE: i64 = 12;
+// Weapon types the strength bonus does not apply to
+const TYPES_SANS_BONUS_FORCE: [&str; 6] = [
+    "Arbalète",
+    "Pistolet",
+    "Fusil",
+    "Arme de siège",
+    "Engins explosifs",
+    "Engin incendiaire",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Origine {
+    /// PV are read from and written to the character sheet
+    Personnage {
+        id: String,
+    },
+    Pnj,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Combattant {
+    pub uid: String,
+    pub nom: String,
+    pub origine: Origine,
+    pub attaque: i64,
+    pub parade: i64,
+    pub esquive: i64,
+    pub courage: i64,
+    pub pr: i64,
+    pub degats: String,
+    pub bonus_degats: i64,
+    pub pv: Jauge,
+    pub initiative: i64,
+    pub pa: i64,
+    pub defense_utilisee: bool,
+    pub hors_combat: bool,
+}
+
+/// Stat block of a non-player fighter.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NouveauPnj {
+    pub nom: String,
+    pub attaque: i64,
+    pub parade: i64,
+    #[serde(default)]
+    pub esquive: i64,
+    #[serde(default)]
+    pub courage: i64,
+    #[serde(default)]
+    pub pr: i64,
+    pub pv: i64,
+    pub degats: String,
+    #[serde(default)]
+    pub bonus_degats: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Defense {
+    #[default]
+    Parade,
+    Esquive,
+    Aucune,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rencontre {
+    pub id: String,
+    pub nom: String,
+    /// Starts at 1
+    pub round: i64,
+    /// Index of the fighter whose turn it is, in initiative order
+    pub tour: usize,
+    pub combattants: Vec<Combattant>,
+    pub termine: bool,
+    pub journal: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JetDefense {
+    pub defense: Defense,
+    pub jet: i64,
+    pub reussie: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Echange {
+    pub attaquant: String,
+    pub cible: String,
+    pub jet_attaque: i64,
+    pub critique: bool,
+    pub maladresse: bool,
+    pub touche: bool,
+    pub defense: Option<JetDefense>,
+    pub degats: Option<Jet>,
+    /// Damage left once armour has absorbed its share
+    pub degats_subis: i64,
+    pub pv_cible: Jauge,
+    pub etat_cible: EtatVital,
+}
+
+/// An attack and the encounter once it is resolved: the attacker's action
+/// points, the target's defense and PV.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultatAttaque {
+    pub echange: Echange,
+    pub rencontre: Rencontre,
+}
+
+impl Combattant {
+    pub fn pnj(pnj: NouveauPnj) -> Self {
+        Combattant {
+            uid: uuid::Uuid::new_v4().to_string(),
+            nom: pnj.nom,
+            origine: Origine::Pnj,
+            attaque: pnj.attaque,
+            parade: pnj.parade,
+            esquive: pnj.esquive,
+            courage: pnj.courage,
+            pr: pnj.pr,
+            degats: pnj.degats,
+            bonus_degats: pnj.bonus_degats,
+            pv: Jauge {
+                current: pnj.pv,
+                max: pnj.pv,
+                temp: 0,
+            },
+            initiative: 0,
+            pa: PA_PAR_ASSAUT,
+            defense_utilisee: false,
+            hors_combat: pnj.pv <= 0,
+        }
+    }
+
+    /// Stat block of a character: equipped characteristics, worn PR and the
+    /// weapon `arme` (an inventory uid) among those at hand, the first one
+    /// when not given, bare hands when there is none. The weapon's AT and PRD
+    /// add to the characteristics.
+    pub fn personnage(
+        id: &str,
+        data: &Value,
+        refs: &[RefEquipement],
+        arme: Option<&str>,
+    ) -> Result<Self, String> {
+        let pieces = pieces_portees(data, refs);
+        let caracs = caracteristiques_equipees(data, refs, &get_game_rules()?.metiers);
+        let carac = |key: &str| caracs.get(key) as i64;
+
+        let armes = armes_portees(data, refs);
+        let arme = match arme {
+            Some(uid) => Some(
+                armes
+                    .iter()
+                    .find(|a| read_str(a.item, "/uid") == uid)
+                    .ok_or_else(|| format!("Arme introuvable ou inutilisable: {}", uid))?,
+            ),
+            None => armes.first(),
+        };
+        let reference = arme.and_then(|a| a.reference.map(|r| (a.item, r)));
+        let degats = DegatsArme::calculer(carac("force"), reference);
+        // AT and PRD of the weapon in hand (a club gives PRD -2)
+        let bonus_arme = |key: &str| {
+            reference
+                .map(|(_, r)| read_i64(&r.caracteristiques, &format!("/{}", key)))
+                .unwrap_or(0)
+        };
+        // `combat_attack` re-reads it strictly before writing damage back
+        let pv = read_jauge(data, Pool::Pv).unwrap_or_default();
+
+        Ok(Combattant {
+            uid: uuid::Uuid::new_v4().to_string(),
+            nom: read_str(data, "/identity/nom").to_string(),
+            origine: Origine::Personnage { id: id.to_string() },
+            attaque: carac("attaque") + bonus_arme("attaque"),
+            parade: carac("parade") + bonus_arme("parade"),
+            esquive: carac("esquive"),
+            courage: carac("courage"),
+            pr: pr_solide_totale(data, &pieces),
+            degats: formule_degats(&degats.de, degats.pi),
+            bonus_degats: degats.bonus_force.unwrap_or(0),
+            pv,
+            initiative: 0,
+            pa: PA_PAR_ASSAUT,
+            defense_utilisee: false,
+            hors_combat: pv.current <= 0,
+        })
+    }
+}
+
+/// Damage of a carried weapon, worked out like the "Total" column of the Armes
+/// table (ArmesTable.tsx), so that every printout and the combat tracker agree
+/// with the sheet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DegatsArme {
+    pub de: String,
+    /// Impact points of the reference plus the item's `modif_pi`
+    pub pi: i64,
+    /// Strength above the threshold, the weapon's own FO included. `None` for
+    /// the weapon types it does not apply to.
+    pub bonus_force: Option<i64>,
+}
+
+impl DegatsArme {
+    /// `force` is the equipped characteristic; `arme` is an inventory item and
+    /// its reference, bare hands (1D) without one.
+    pub fn calculer(force: i64, arme: Option<(&Value, &RefEquipement)>) -> Self {
+        let Some((item, reference)) = arme else {
+            return DegatsArme {
+                de: "1D".to_string(),
+                pi: 0,
+                bonus_force: Some((force - SEUIL_BONUS_FORCE).max(0)),
+            };
+        };
+
+        let de = read_str(&reference.degats, "/degats");
+        let type_arme = match read_str(&reference.details, "/type") {
+            "" => reference.category.as_str(),
+            t => t,
+        };
+        let bonus_force = (!TYPES_SANS_BONUS_FORCE.contains(&type_arme)).then(|| {
+            (force + read_i64(&reference.caracteristiques, "/force") - SEUIL_BONUS_FORCE).max(0)
+        });
+        DegatsArme {
+            de: if de.is_empty() { "1D" } else { de }.to_string(),
+            pi: read_i64(&reference.degats, "/pi") + read_i64(item, "/modif_pi"),
+            bonus_force,
+        }
+    }
+
+    /// Everything included, as on the sheet: "1D + 5".
+    pub fn formule(&self) -> String {
+        formule_degats(&self.de, self.pi + self.bonus_force.unwrap_or(0))
+    }
+}
+
+/// Dice plus a flat amount, written like the sheet does ("1D + 3", "2D - 1").
+pub fn formule_degats(de: &str, points: i64) -> String {
+    match points {
+        0 => de.to_string(),
+        p if p > 0 => format!("{} + {}", de, p),
+        p => format!("{} - {}", de, -p),
+    }
+}
+
+/// Natural 1 always succeeds, natural 20 always fails.
+fn reussite(jet: i64, seuil: i64) -> bool {
+    jet == 1 || (jet != 20 && jet <= seuil)
+}
+
+impl Rencontre {
+    pub fn new(nom: &str, combattants: Vec<Combattant>, lanceur: &mut impl Lanceur) -> Self {
+        let mut rencontre = Rencontre {
+            id: uuid::Uuid::new_v4().to_string(),
+            nom: nom.to_string(),
+            round: 1,
+            tour: 0,
+            combattants,
+            termine: false,
+            journal: Vec::new(),
+        };
+        rencontre.lancer_initiative(lanceur);
+        rencontre
+    }
+
+    /// Courage plus 1D6, highest first; ties go to the bravest.
+    pub fn lancer_initiative(&mut self, lanceur: &mut impl Lanceur) {
+        for combattant in &mut self.combattants {
+            let jet = lancer(DE_INITIATIVE, lanceur).map_or(0, |j| j.total);
+            combattant.initiative = combattant.courage + jet;
+        }
+        self.combattants
+            .sort_by_key(|c| std::cmp::Reverse((c.initiative, c.courage)));
+        self.tour = self.premier_actif(0).unwrap_or(0);
+    }
+
+    fn premier_actif(&self, depuis: usize) -> Option<usize> {
+        (depuis..self.combattants.len()).find(|&i| !self.combattants[i].hors_combat)
+    }
+
+    fn index(&self, uid: &str) -> Result<usize, String> {
+        self.combattants
+            .iter()
+            .position(|c| c.uid == uid)
+            .ok_or_else(|| format!("Combattant introuvable: {}", uid))
+    }
+
+    fn verifier_en_cours(&self) -> Result<(), String> {
+        if self.termine {
+            return Err("Le combat est terminé".to_string());
+        }
+        Ok(())
+    }
+
+    /// Only the fighter whose turn it is may act.
+    fn verifier_tour(&self, index: usize) -> Result<(), String> {
+        if index != self.tour {
+            let actif = self
+                .combattants
+                .get(self.tour)
+                .map_or("personne", |c| c.nom.as_str());
+            return Err(format!(
+                "Ce n'est pas le tour de {}: c'est à {} de jouer",
+                self.combattants[index].nom, actif
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn depenser_pa(&mut self, uid: &str, cout: i64, action: &str) -> Result<(), String> {
+        self.verifier_en_cours()?;
+        let index = self.index(uid)?;
+        self.verifier_tour(index)?;
+        let combattant = &mut self.combattants[index];
+        if combattant.hors_combat {
+            return Err(format!("{} est hors de combat", combattant.nom));
+        }
+        if cout > combattant.pa {
+            return Err(format!(
+                "Pas assez de PA: {} demandés, {} restants",
+                cout, combattant.pa
+            ));
+        }
+        combattant.pa -= cout.max(0);
+        let ligne = format!("Assaut {}: {} - {}", self.round, combattant.nom, action);
+        self.journal.push(ligne);
+        Ok(())
+    }
+
+    pub fn attaquer(
+        &mut self,
+        attaquant: &str,
+        cible: &str,
+        defense: Defense,
+        lanceur: &mut impl Lanceur,
+    ) -> Result<Echange, String> {
+        let i_cible = self.index(cible)?;
+        if self.combattants[i_cible].pv.current <= SEUIL_MORT {
+            return Err(format!("{} est déjà mort", self.combattants[i_cible].nom));
+        }
+        let cible_nom = self.combattants[i_cible].nom.clone();
+        self.depenser_pa(attaquant, COUT_ATTAQUE, &format!("attaque {}", cible_nom))?;
+        let a = self.combattants[self.index(attaquant)?].clone();
+
+        let jet_attaque = lancer(DE_ATTAQUE, lanceur)?.total;
+        let critique = jet_attaque == 1;
+        let maladresse = jet_attaque == 20;
+        let mut touche = reussite(jet_attaque, a.attaque);
+
+        let c = &mut self.combattants[i_cible];
+        let mut jet_defense = None;
+        if touche
+            && !critique
+            && defense != Defense::Aucune
+            && !c.defense_utilisee
+            && !c.hors_combat
+        {
+            let seuil = match defense {
+                Defense::Esquive => c.esquive,
+                _ => c.parade,
+            };
+            let jet = lancer(DE_ATTAQUE, lanceur)?.total;
+            let reussie = reussite(jet, seuil);
+            c.defense_utilisee = true;
+            touche = !reussie;
+            jet_defense = Some(JetDefense {
+                defense,
+                jet,
+                reussie,
+            });
+        }
+
+        let mut degats = None;
+        let mut degats_subis = 0;
+        if touche {
+            let mut jet = lancer(&a.degats, lanceur)?;
+            jet.total += a.bonus_degats;
+            let armure = if critique { 0 } else { c.pr };
+            degats_subis = (jet.total - armure).max(0);
+            c.pv.subir(degats_subis, SEUIL_MORT);
+            c.hors_combat = c.pv.current <= 0;
+            degats = Some(jet);
+        }
+
+        let echange = Echange {
+            attaquant: a.uid,
+            cible: c.uid.clone(),
+            jet_attaque,
+            critique,
+            maladresse,
+            touche,
+            defense: jet_defense,
+            degats,
+            degats_subis,
+            pv_cible: c.pv,
+            etat_cible: etat_vital(&c.pv),
+        };
+        self.journal.push(format!(
+            "Assaut {}: {} -> {} (AT {}{}): {}",
+            self.round,
+            a.nom,
+            cible_nom,
+            jet_attaque,
+            echange
+                .defense
+                .as_ref()
+                .map(|d| format!(", défense {}", d.jet))
+                .unwrap_or_default(),
+            if touche {
+                format!("{} PV", degats_subis)
+            } else {
+                "raté".to_string()
+            }
+        ));
+        Ok(echange)
+    }
+
+    /// Hands over to the next fighter still standing. Returns true when a new
+    /// assault starts: action points and defenses come back.
+    pub fn tour_suivant(&mut self) -> Result<bool, String> {
+        self.verifier_en_cours()?;
+        if let Some(suivant) = self.premier_actif(self.tour + 1) {
+            self.tour = suivant;
+            return Ok(false);
+        }
+        self.round += 1;
+        for combattant in &mut self.combattants {
+            combattant.pa = PA_PAR_ASSAUT;
+            combattant.defense_utilisee = false;
+        }
+        self.tour = self.premier_actif(0).unwrap_or(0);
+        Ok(true)
+    }
+}
+
+fn charger(conn: &Connection, encounter_id: &str) -> Result<Rencontre, String> {
+    let data: String = conn
+        .query_row(
+            "SELECT data FROM combat_encounters WHERE encounter_id = ?1",
+            [encounter_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Combat introuvable: {}", e))?;
+    serde_json::from_str(&data).map_err(|e| e.to_string())
+}
+
+fn enregistrer(conn: &Connection, rencontre: &Rencontre) -> Result<(), String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let data = serde_json::to_string(rencontre).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO combat_encounters (encounter_id, nom, data, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(encounter_id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+        params![rencontre.id, rencontre.nom, data, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// `armes` maps a character id to the uid of the weapon it fights with;
+/// characters left out take their first weapon at hand.
+#[tauri::command]
+pub fn create_encounter(
+    nom: String,
+    personnages: Vec<String>,
+    pnjs: Vec<NouveauPnj>,
+    armes: Option<std::collections::HashMap<String, String>>,
+    state: State<AppState>,
+) -> Result<Rencontre, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let refs = load_ref_items(&db)?;
+    let armes = armes.unwrap_or_default();
+
+    let mut combattants = Vec::new();
+    for id in &personnages {
+        let data = load_personnage_data(&db, id)?;
+        combattants.push(Combattant::personnage(
+            id,
+            &data,
+            &refs,
+            armes.get(id).map(String::as_str),
+        )?);
+    }
+    combattants.extend(pnjs.into_iter().map(Combattant::pnj));
+    if combattants.is_empty() {
+        return Err("Un combat sans combattant, c'est une sieste".to_string());
+    }
+
+    let rencontre = Rencontre::new(&nom, combattants, &mut Des::new());
+    enregistrer(&db, &rencontre)?;
+    Ok(rencontre)
+}
+
+#[tauri::command]
+pub fn get_encounter(encounter_id: String, state: State<AppState>) -> Result<Rencontre, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    charger(&db, &encounter_id)
+}
+
+/// Encounters still running, most recent first.
+#[tauri::command]
+pub fn list_encounters(state: State<AppState>) -> Result<Vec<Rencontre>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT data FROM combat_encounters ORDER BY updated_at DESC")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut rencontres = Vec::new();
+    for row in rows {
+        let rencontre: Rencontre =
+            serde_json::from_str(&row.map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        if !rencontre.termine {
+            rencontres.push(rencontre);
+        }
+    }
+    Ok(rencontres)
+}
+
+/// Resolves an attack; damage to a character is written to its sheet.
+#[tauri::command]
+pub fn combat_attack(
+    encounter_id: String,
+    attaquant: String,
+    cible: String,
+    defense: Option<Defense>,
+    state: State<AppState>,
+) -> Result<ResultatAttaque, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut rencontre = charger(&tx, &encounter_id)?;
+    // The sheet may have changed since the fight started (potion, healing…)
+    for combattant in &mut rencontre.combattants {
+        if let Origine::Personnage { id } = &combattant.origine {
+            if combattant.uid == cible {
+                combattant.pv = read_jauge(&load_personnage_data(&tx, id)?, Pool::Pv)?;
+            }
+        }
+    }
+
+    let echange = rencontre.attaquer(
+        &attaquant,
+        &cible,
+        defense.unwrap_or_default(),
+        &mut Des::new(),
+    )?;
+
+    let touche = rencontre.combattants.iter().find(|c| c.uid == cible);
+    if let (Some(Origine::Personnage { id }), true) =
+        (touche.map(|c| &c.origine), echange.degats_subis > 0)
+    {
+        let mut data = load_personnage_data(&tx, id)?;
+        set_value(
+            &mut data,
+            "/vitals/pv",
+            serde_json::to_value(echange.pv_cible).map_err(|e| e.to_string())?,
+        );
+        let now = store_personnage_data(&tx, id, &data)?;
+        log_personnage_event(
+            &tx,
+            id,
+            "damage",
+            &serde_json::json!({
+                "pool": "pv",
+                "amount": echange.degats_subis,
+                "etat": echange.etat_cible,
+                "note": format!("Combat: {}", rencontre.nom),
+            }),
+            &now,
+        )?;
+    }
+
+    enregistrer(&tx, &rencontre)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(ResultatAttaque { echange, rencontre })
+}
+
+/// Spends action points on anything else than an attack (a skill, drinking a potion…).
+#[tauri::command]
+pub fn combat_spend_action(
+    encounter_id: String,
+    uid: String,
+    cout: i64,
+    action: String,
+    state: State<AppState>,
+) -> Result<Rencontre, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut rencontre = charger(&db, &encounter_id)?;
+    rencontre.depenser_pa(&uid, cout, &action)?;
+    enregistrer(&db, &rencontre)?;
+    Ok(rencontre)
+}
+
+/// Next fighter. A new assault also counts down the round-based temporary
+/// effects of the characters involved.
+#[tauri::command]
+pub fn combat_next_turn(encounter_id: String, state: State<AppState>) -> Result<Rencontre, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut rencontre = charger(&tx, &encounter_id)?;
+    if rencontre.tour_suivant()? {
+        for combattant in &rencontre.combattants {
+            if let Origine::Personnage { id } = &combattant.origine {
+                let mut data = load_personnage_data(&tx, id)?;
+                let expires = ecouler(&mut data, 1, 0)?;
+                if !expires.is_empty() {
+                    let now = store_personnage_data(&tx, id, &data)?;
+                    log_personnage_event(
+                        &tx,
+                        id,
+                        "temp_effects_expired",
+                        &serde_json::json!({ "effets": expires }),
+                        &now,
+                    )?;
+                }
+            }
+        }
+    }
+
+    enregistrer(&tx, &rencontre)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(rencontre)
+}
+
+#[tauri::command]
+pub fn end_encounter(encounter_id: String, state: State<AppState>) -> Result<Rencontre, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut rencontre = charger(&db, &encounter_id)?;
+    rencontre.termine = true;
+    enregistrer(&db, &rencontre)?;
+    Ok(rencontre)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::DesPipes;
+
+    fn pnj(nom: &str, courage: i64, pv: i64) -> Combattant {
+        Combattant::pnj(NouveauPnj {
+            nom: nom.to_string(),
+            attaque: 10,
+            parade: 8,
+            esquive: 9,
+            courage,
+            pr: 2,
+            pv,
+            degats: "1D+2".to_string(),
+            bonus_degats: 1,
+        })
+    }
+
+    fn rencontre(des: &[i64]) -> (Rencontre, DesPipes) {
+        let mut pipes = DesPipes(des.iter().copied().collect());
+        let combat = Rencontre::new(
+            "Embuscade",
+            vec![pnj("Gobelin", 8, 10), pnj("Orque", 11, 20)],
+            &mut pipes,
+        );
+        (combat, pipes)
+    }
+
+    #[test]
+    fn test_initiative_and_rounds() {
+        let (mut combat, _) = rencontre(&[6, 1]);
+        // Gobelin 8 + 6 beats Orque 11 + 1
+        assert_eq!(combat.combattants[0].nom, "Gobelin");
+        assert_eq!(combat.combattants[0].initiative, 14);
+
+        assert!(!combat.tour_suivant().unwrap());
+        assert!(combat.tour_suivant().unwrap());
+        assert_eq!((combat.round, combat.tour), (2, 0));
+    }
+
+    #[test]
+    fn test_attack_parry_and_damage() {
+        // Initiative, then: attack 7 parried by 5, attack 9 hits (no second parry), damage 4
+        let (mut combat, mut des) = rencontre(&[6, 1, 7, 5, 9, 4, 3]);
+        let gobelin = combat.combattants[0].uid.clone();
+        let orque = combat.combattants[1].uid.clone();
+
+        let pare = combat
+            .attaquer(&gobelin, &orque, Defense::Parade, &mut des)
+            .unwrap();
+        assert!(!pare.touche);
+        assert!(pare.defense.unwrap().reussie);
+
+        let touche = combat
+            .attaquer(&gobelin, &orque, Defense::Parade, &mut des)
+            .unwrap();
+        assert!(touche.defense.is_none());
+        // 4 + 2 + 1 (force) - 2 PR
+        assert_eq!(touche.degats_subis, 5);
+        assert_eq!(touche.pv_cible.current, 15);
+
+        // Both action points are spent
+        assert!(combat
+            .attaquer(&gobelin, &orque, Defense::Parade, &mut des)
+            .is_err());
+    }
+
+    #[test]
+    fn test_only_the_active_fighter_acts() {
+        let (mut combat, mut des) = rencontre(&[6, 1, 9, 4]);
+        let gobelin = combat.combattants[0].uid.clone();
+        let orque = combat.combattants[1].uid.clone();
+
+        assert!(combat
+            .attaquer(&orque, &gobelin, Defense::Aucune, &mut des)
+            .is_err());
+        assert!(combat.depenser_pa(&orque, 1, "boire").is_err());
+        combat.tour_suivant().unwrap();
+        assert!(combat.depenser_pa(&orque, 1, "boire").is_ok());
+    }
+
+    #[test]
+    fn test_character_fights_with_a_weapon_at_hand() {
+        let arme = |id: i64, pi: i64, caracteristiques: Value| -> RefEquipement {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "category": "Armes", "ref_id": id, "nom": format!("Arme {}", id),
+                "degats": { "degats": "1D", "pi": pi },
+                "caracteristiques": caracteristiques,
+                "protections": {}, "prix_info": {}, "craft": {}, "details": {}
+            }))
+            .unwrap()
+        };
+        let refs = vec![
+            arme(1, 1, serde_json::json!({})),
+            arme(2, 2, serde_json::json!({})),
+            arme(3, 3, serde_json::json!({ "attaque": "1", "parade": "-2" })),
+        ];
+        let data = serde_json::json!({
+            "identity": { "nom": "Gurdil" },
+            "inventory": [
+                { "uid": "sac", "equipement_type": "Sacs", "contenu": [{ "uid": "rangee", "refId": 1 }] },
+                { "uid": "cassee", "refId": 1, "equipement_type": "Armes", "etat": "Cassé" },
+                { "uid": "hache", "refId": 2, "equipement_type": "Armes" },
+                { "uid": "dague", "refId": 3, "equipement_type": "Armes" }
+            ]
+        });
+
+        let hache = Combattant::personnage("p1", &data, &refs, None).unwrap();
+        assert_eq!(hache.degats, "1D + 2");
+        let dague = Combattant::personnage("p1", &data, &refs, Some("dague")).unwrap();
+        assert_eq!(dague.degats, "1D + 3");
+        assert_eq!(dague.attaque, hache.attaque + 1);
+        assert_eq!(dague.parade, hache.parade - 2);
+        assert!(Combattant::personnage("p1", &data, &refs, Some("cassee")).is_err());
+        assert!(Combattant::personnage("p1", &data, &refs, Some("rangee")).is_err());
+    }
+
+    #[test]
+    fn test_weapon_damage_follows_the_sheet() {
+        let arme = |type_arme: &str| -> RefEquipement {
+            serde_json::from_value(serde_json::json!({
+                "id": 1, "category": "Armes", "ref_id": 1, "nom": "Arme",
+                "degats": { "degats": "1D", "pi": 2 },
+                "caracteristiques": { "force": 1 },
+                "protections": {}, "prix_info": {}, "craft": {},
+                "details": { "type": type_arme }
+            }))
+            .unwrap()
+        };
+        let item = serde_json::json!({ "refId": 1, "modif_pi": 1 });
+
+        // 2 PI + 1 modif + (13 + 1 FO of the weapon - 12)
+        let epee = arme("Epée");
+        let degats = DegatsArme::calculer(13, Some((&item, &epee)));
+        assert_eq!(degats.formule(), "1D + 5");
+        let arbalete = arme("Arbalète");
+        let degats = DegatsArme::calculer(13, Some((&item, &arbalete)));
+        assert_eq!(
+            (degats.bonus_force, degats.formule()),
+            (None, "1D + 3".to_string())
+        );
+    }
+}
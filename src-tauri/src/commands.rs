@@ -1,5 +1,13 @@
-use crate::db::{AppState, RefEquipement};
-use crate::logic::{calculer_stats_finales, BaseStats, Equipement, Etats, FinalStats};
+use crate::db::{log_personnage_event, AppState, RefEquipement};
+use crate::journal;
+use crate::logic::{
+    calculer_stats_finales, calculer_stats_magiques, BaseStats, Equipement, Etats, FinalStats,
+    MagicStats,
+};
+use crate::trash;
+use crate::versions::{
+    list_versions, prune_versions, snapshot_version, tag_current, VersionSummary,
+};
 use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use tauri::State;
@@ -7,34 +15,7 @@ use tauri::State;
 #[tauri::command]
 pub fn get_ref_items(state: State<AppState>) -> Result<Vec<RefEquipement>, String> {
     let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, category, ref_id, nom, degats, caracteristiques, protections, prix_info, craft, details FROM ref_items")
-        .map_err(|e| e.to_string())?;
-
-    let items_iter = stmt
-        .query_map([], |row| {
-            Ok(RefEquipement {
-                id: row.get(0)?,
-                category: row.get(1)?,
-                ref_id: row.get(2)?,
-                nom: row.get(3)?,
-                degats: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
-                caracteristiques: serde_json::from_str(&row.get::<_, String>(5)?)
-                    .unwrap_or_default(),
-                protections: serde_json::from_str(&row.get::<_, String>(6)?).unwrap_or_default(),
-                prix_info: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
-                craft: serde_json::from_str(&row.get::<_, String>(8)?).unwrap_or_default(),
-                details: serde_json::from_str(&row.get::<_, String>(9)?).unwrap_or_default(),
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut items = Vec::new();
-    for item in items_iter {
-        items.push(item.map_err(|e| e.to_string())?);
-    }
-
-    Ok(items)
+    crate::db::load_ref_items(&conn)
 }
 
 #[tauri::command]
@@ -124,6 +105,34 @@ pub fn compute_stats(base: BaseStats, equipements: Vec<Equipement>, etats: Etats
     calculer_stats_finales(base, equipements, etats)
 }
 
+#[tauri::command]
+pub fn get_personnage_etats(id: String, state: State<AppState>) -> Result<Etats, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = crate::db::load_personnage_data(&db, &id)?;
+    Ok(Etats::depuis_fiche(&data))
+}
+
+/// Highest corruption tier reached, if any.
+pub fn palier_corruption(
+    paliers: &[CorruptionPalierRef],
+    corruption: i64,
+) -> Option<&CorruptionPalierRef> {
+    paliers
+        .iter()
+        .filter(|p| p.paliers as i64 <= corruption)
+        .max_by_key(|p| p.paliers)
+}
+
+#[tauri::command]
+pub fn compute_magic_stats(id: String, state: State<AppState>) -> Result<MagicStats, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = crate::db::load_personnage_data(&db, &id)?;
+    let refs = crate::db::load_ref_items(&db)?;
+    drop(db);
+
+    Ok(calculer_stats_magiques(&data, &refs, &get_game_rules()?))
+}
+
 #[derive(Serialize)]
 pub struct CharacterSummary {
     pub id: String,
@@ -136,7 +145,10 @@ pub fn get_all_personnages(state: State<AppState>) -> Result<Vec<CharacterSummar
     let db = state.db.lock().map_err(|e| e.to_string())?;
 
     let mut stmt = db
-        .prepare("SELECT id, name, updated_at FROM personnages ORDER BY updated_at DESC")
+        .prepare(
+            "SELECT id, name, updated_at FROM personnages
+             WHERE deleted_at IS NULL ORDER BY updated_at DESC",
+        )
         .map_err(|e| e.to_string())?;
 
     let personnages = stmt
@@ -159,7 +171,7 @@ pub fn get_personnage(id: String, state: State<AppState>) -> Result<crate::db::P
     let db = state.db.lock().map_err(|e| e.to_string())?;
 
     let mut stmt = db
-        .prepare("SELECT id, name, data, updated_at FROM personnages WHERE id = ?1")
+        .prepare("SELECT id, name, data, updated_at, deleted_at FROM personnages WHERE id = ?1")
         .map_err(|e| e.to_string())?;
 
     let personnage = stmt
@@ -173,6 +185,7 @@ pub fn get_personnage(id: String, state: State<AppState>) -> Result<crate::db::P
                 name: row.get(1)?,
                 data,
                 updated_at: row.get(3)?,
+                deleted_at: row.get(4)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -243,6 +256,7 @@ pub fn create_personnage(name: String, state: State<AppState>) -> Result<String,
             "mod2": "",
             "mod3": ""
         },
+        "temp_effects": [],
         "inventory": []
     });
 
@@ -255,13 +269,16 @@ pub fn create_personnage(name: String, state: State<AppState>) -> Result<String,
     Ok(id)
 }
 
+/// Moves the character to the trash, see `trash::restore_deleted_personnage`.
 #[tauri::command]
 pub fn delete_personnage(id: String, state: State<AppState>) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
 
-    db.execute("DELETE FROM personnages WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
+    let now = trash::soft_delete(&tx, &id)?;
+    log_personnage_event(&tx, &id, "delete", &serde_json::json!({}), &now)?;
 
+    tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -271,15 +288,29 @@ pub fn import_personnage(
     name: String,
     data: String, // JSON string
     updated_at: String,
+    deleted_at: Option<String>, // Tombstone from the cloud
     state: State<AppState>,
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
 
+    // A tombstone never creates a row, it only trashes the local copy
+    if let Some(deleted_at) = deleted_at {
+        trash::apply_remote_deletion(&db, &id, &deleted_at)?;
+        return Ok(());
+    }
+    // Deleted here after this copy was saved: keep it deleted
+    if trash::deleted_since(&db, &id, &updated_at)? {
+        return Ok(());
+    }
+
     db.execute(
-        "INSERT OR REPLACE INTO personnages (id, name, data, updated_at) VALUES (?1, ?2, ?3, ?4)",
+        "INSERT OR REPLACE INTO personnages (id, name, data, updated_at, deleted_at, version_client)
+         VALUES (?1, ?2, ?3, ?4, NULL, 'sync')",
         params![id, name, data, updated_at],
     )
     .map_err(|e| e.to_string())?;
+    // The local undo history does not apply to the imported sheet
+    journal::clear(&db, &id)?;
 
     Ok(())
 }
@@ -313,6 +344,9 @@ pub struct Origine {
     pub vitesse: i32,
     #[serde(alias = "Metiers_impossibles", default)]
     pub metiers_impossibles: Option<Vec<String>>,
+    /// "Moyenne" or "Totale" for the origins that see in the dark
+    #[serde(alias = "Nyctalopie", default)]
+    pub nyctalopie: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -424,16 +458,19 @@ pub struct GameRules {
     pub domaines: Vec<Domaine>,
 }
 
+pub fn origines() -> Result<Vec<Origine>, String> {
+    serde_json::from_str(include_str!("../data/config/origines.json"))
+        .map_err(|e| format!("Failed to parse origines.json: {}", e))
+}
+
 #[tauri::command]
 pub fn get_game_rules() -> Result<GameRules, String> {
-    let origines_json = include_str!("../data/config/origines.json");
     let metiers_json = include_str!("../data/config/metiers.json");
     let corruption_origine_json = include_str!("../data/config/corruption_origine.json");
     let corruption_palier_json = include_str!("../data/config/corruption_palier.json");
     let domaines_json = include_str!("../data/config/domaines.json");
 
-    let origines: Vec<Origine> = serde_json::from_str(origines_json)
-        .map_err(|e| format!("Failed to parse origines.json: {}", e))?;
+    let origines = origines()?;
     let metiers: Vec<Metier> = serde_json::from_str(metiers_json)
         .map_err(|e| format!("Failed to parse metiers.json: {}", e))?;
     let corruption_origine: Vec<CorruptionOrigineRef> =
@@ -453,38 +490,16 @@ pub fn get_game_rules() -> Result<GameRules, String> {
     })
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VersionSummary {
-    version_id: i64,
-    saved_at: String,
-}
-
 #[tauri::command]
 pub fn get_personnage_versions(
     id: String,
     state: State<AppState>,
 ) -> Result<Vec<VersionSummary>, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    let mut stmt = db
-        .prepare("SELECT version_id, saved_at FROM personnages_versions WHERE personnage_id = ?1 ORDER BY saved_at DESC")
-        .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map(params![id], |row| {
-            Ok(VersionSummary {
-                version_id: row.get(0)?,
-                saved_at: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut versions = Vec::new();
-    for row in rows {
-        versions.push(row.map_err(|e| e.to_string())?);
-    }
-    Ok(versions)
+    list_versions(&db, &id)
 }
 
+/// Restores a version. The current sheet is saved first so the restore can be undone.
 #[tauri::command]
 pub fn restore_personnage_version(
     id: String,
@@ -501,62 +516,56 @@ pub fn restore_personnage_version(
             params![version_id, id],
             |row| Ok((row.get(0)?, row.get(1)?)),
         )
-        .map_err(|e| format!("Version introuvable: {}", e.to_string()))?;
+        .map_err(|e| format!("Version introuvable: {}", e))?;
 
-        // 2. Restore to main table
+        // 2. Snapshot the current sheet
+        snapshot_version(&tx, &id)?;
+
+        // 3. Restore to main table, undoable like any other change
+        if let Some(current) = journal::previous_data(&tx, &id)? {
+            let restored: serde_json::Value =
+                serde_json::from_str(&data).map_err(|e| e.to_string())?;
+            journal::record_change(&tx, &id, &current, &restored)?;
+        }
         tx.execute(
             "UPDATE personnages SET data = ?1, updated_at = ?2 WHERE id = ?3",
-            params![data, saved_at, id],
+            params![data, chrono::Utc::now().to_rfc3339(), id],
         )
         .map_err(|e| e.to_string())?;
+        let label = format!("Restauration de la version du {}", saved_at);
+        tag_current(&tx, &id, Some(&label), Some("restore"))?;
+
+        prune_versions(&tx, &id)?;
     }
 
     tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// `label` and `client` describe the sheet being saved; the history shows them
+/// once a later save replaces it.
 #[tauri::command]
 pub fn save_personnage_local(
     id: String,
     name: String,
     data: String,
     updated_at: String,
+    label: Option<String>,
+    client: Option<String>,
     state: State<AppState>,
 ) -> Result<(), String> {
     let mut db = state.db.lock().map_err(|e| e.to_string())?;
     let tx = db.transaction().map_err(|e| e.to_string())?;
 
-    // 1. Get current data to backup
-    let result: Result<Option<(String, String)>, rusqlite::Error> = tx
-        .query_row(
-            "SELECT data, updated_at FROM personnages WHERE id = ?1",
-            params![id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .optional();
-    // Note: If new char, result is None, nothing to backup.
-
-    if let Ok(Some((old_data, old_updated_at))) = result {
-        tx.execute(
-            "INSERT INTO personnages_versions (personnage_id, data, saved_at) VALUES (?1, ?2, ?3)",
-            params![id, old_data, old_updated_at],
-        )
-        .map_err(|e| e.to_string())?;
+    // 1. Backup current data (nothing to backup for a new character)
+    snapshot_version(&tx, &id)?;
+    if let Some(old) = journal::previous_data(&tx, &id)? {
+        let new: serde_json::Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+        journal::record_change(&tx, &id, &old, &new)?;
     }
 
-    // 2. Prune old versions (Keep 3)
-    tx.execute(
-        "DELETE FROM personnages_versions 
-         WHERE personnage_id = ?1 
-         AND version_id NOT IN (
-            SELECT version_id FROM personnages_versions 
-            WHERE personnage_id = ?1 
-            ORDER BY saved_at DESC 
-            LIMIT 3
-         )",
-        params![id],
-    )
-    .map_err(|e| e.to_string())?;
+    // 2. Prune old versions according to the retention policy
+    prune_versions(&tx, &id)?;
 
     // 3. Update Current
     tx.execute(
@@ -564,6 +573,7 @@ pub fn save_personnage_local(
         params![name, data, updated_at, id],
     )
     .map_err(|e| e.to_string())?;
+    tag_current(&tx, &id, label.as_deref(), client.as_deref())?;
 
     tx.commit().map_err(|e| e.to_string())?;
     Ok(())
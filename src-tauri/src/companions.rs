@@ -0,0 +1,603 @@
+use crate::db::{load_personnage_data, log_personnage_event, store_personnage_data, AppState};
+use crate::sheet::{lenient_i64, read_list, read_str, set_value};
+use crate::vitals::Jauge;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tauri::State;
+
+// Mounts, familiars and invocations share the `Mount` shape of the frontend and
+// stay stored in the sheet arrays, so the panels keep working unchanged.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeCompagnon {
+    Monture,
+    Familier,
+    Invocation,
+}
+
+impl TypeCompagnon {
+    pub(crate) fn key(&self) -> &'static str {
+        match self {
+            TypeCompagnon::Monture => "mounts",
+            TypeCompagnon::Familier => "familiers",
+            TypeCompagnon::Invocation => "invocations",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Compagnon {
+    #[serde(default)]
+    pub uid: String,
+    #[serde(default)]
+    pub nom: String,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub pv_current: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub pv_max: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub courage: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub intelligence: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub charisme: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub adresse: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub force: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub esquive: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub perception: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub attaque: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub parade: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub rm: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub mvt_marche: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub mvt_course: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub mvt_voyage: i64,
+    /// Free text such as "5 PO"
+    #[serde(default)]
+    pub prix: String,
+    /// Free text such as "1 PA / jour"
+    #[serde(default)]
+    pub entretien: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub competences: String,
+    /// Free text such as "+1 AT, -1 PRD"
+    #[serde(default)]
+    pub bonus_cavalier: String,
+    #[serde(default)]
+    pub lieux: String,
+    /// Free text such as "150 kg"
+    #[serde(default)]
+    pub charge_max: String,
+    #[serde(default)]
+    pub at_speciales: String,
+    /// Fields added by other features (saddlebags...) are kept as they are
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// An amount in one of the currencies of `richesse`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Montant {
+    pub montant: i64,
+    pub monnaie: String,
+}
+
+/// Upkeep parsed from `entretien`: `montant` every `jours` days.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entretien {
+    pub cout: Montant,
+    pub jours: i64,
+}
+
+impl Entretien {
+    /// Cost for `jours` days; a started period is due in full.
+    pub fn pour(&self, jours: i64) -> Montant {
+        let periodes = (jours.max(0) + self.jours - 1) / self.jours;
+        Montant {
+            montant: self.cout.montant * periodes,
+            monnaie: self.cout.monnaie.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BonusCavalier {
+    /// Characteristic key of the sheet (`attaque`, `parade`...)
+    pub caracteristique: String,
+    pub valeur: i32,
+}
+
+fn monnaie(mot: &str) -> Option<&'static str> {
+    match mot.to_lowercase().trim_end_matches('.') {
+        "po" | "or" => Some("or"),
+        "pa" | "argent" => Some("argent"),
+        "pc" | "cuivre" => Some("cuivre"),
+        "thritil" => Some("thritil"),
+        "beryllium" | "béryllium" => Some("beryllium"),
+        _ => None,
+    }
+}
+
+fn caracteristique(abreviation: &str) -> Option<&'static str> {
+    match abreviation.to_uppercase().as_str() {
+        "COU" => Some("courage"),
+        "INT" => Some("intelligence"),
+        "CHA" => Some("charisme"),
+        "AD" => Some("adresse"),
+        "FO" => Some("force"),
+        "PER" => Some("perception"),
+        "ES" => Some("esquive"),
+        "AT" => Some("attaque"),
+        "PRD" => Some("parade"),
+        _ => None,
+    }
+}
+
+fn premier_nombre(texte: &str) -> Option<i64> {
+    texte
+        .split(|c: char| !(c.is_ascii_digit() || c == '-' || c == '+'))
+        .find_map(|mot| mot.parse::<i64>().ok())
+}
+
+impl Compagnon {
+    pub fn entretien_par_periode(&self) -> Option<Entretien> {
+        let texte = self.entretien.to_lowercase();
+        let (cout, periode) = texte.split_once('/').unwrap_or((&texte, ""));
+        let montant = premier_nombre(cout)?;
+        let monnaie = cout
+            .split(|c: char| c.is_whitespace() || c.is_ascii_digit())
+            .find_map(monnaie)?;
+        let jours = match periode.trim() {
+            p if p.contains("semaine") => 7,
+            p if p.contains("mois") => 30,
+            _ => 1,
+        };
+        Some(Entretien {
+            cout: Montant {
+                montant,
+                monnaie: monnaie.to_string(),
+            },
+            jours,
+        })
+    }
+
+    /// `charge_max` in grams, like item weights. A bare number is in kilograms.
+    pub fn charge_max_grammes(&self) -> Option<i64> {
+        let texte = self.charge_max.to_lowercase();
+        let valeur = texte
+            .trim()
+            .trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace())
+            .replace(',', ".")
+            .parse::<f64>()
+            .ok()?;
+        let grammes = texte.trim_end().ends_with('g') && !texte.trim_end().ends_with("kg");
+        Some(if grammes { valeur } else { valeur * 1000.0 } as i64)
+    }
+
+    pub fn bonus_cavalier(&self) -> Vec<BonusCavalier> {
+        self.bonus_cavalier
+            .split([',', ';', '/'])
+            .filter_map(|partie| {
+                let mut mots = partie.split_whitespace();
+                let valeur = mots.next()?.parse::<i32>().ok()?;
+                let caracteristique = caracteristique(mots.next()?)?;
+                Some(BonusCavalier {
+                    caracteristique: caracteristique.to_string(),
+                    valeur,
+                })
+            })
+            .collect()
+    }
+
+    /// Applies the fields the client sent on top of this companion. Anything it
+    /// left out, such as the mount `inventaire`, stays as stored.
+    pub fn fusionner(&self, champs: serde_json::Map<String, Value>) -> Result<Compagnon, String> {
+        let mut valeur = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        if let Some(objet) = valeur.as_object_mut() {
+            objet.extend(champs);
+            objet.insert("uid".to_string(), Value::from(self.uid.clone()));
+        }
+        serde_json::from_value(valeur).map_err(|e| format!("Compagnon invalide: {}", e))
+    }
+
+    fn jauge(&self) -> Jauge {
+        Jauge {
+            current: self.pv_current,
+            max: self.pv_max,
+            temp: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompagnonDetail {
+    pub kind: TypeCompagnon,
+    pub compagnon: Compagnon,
+    pub entretien: Option<Entretien>,
+    pub charge_max_grammes: Option<i64>,
+    pub bonus_cavalier: Vec<BonusCavalier>,
+    /// The character is currently riding this mount
+    pub monte: bool,
+}
+
+fn detail(data: &Value, kind: TypeCompagnon, compagnon: Compagnon) -> CompagnonDetail {
+    CompagnonDetail {
+        kind,
+        entretien: compagnon.entretien_par_periode(),
+        charge_max_grammes: compagnon.charge_max_grammes(),
+        bonus_cavalier: compagnon.bonus_cavalier(),
+        monte: kind == TypeCompagnon::Monture
+            && !compagnon.uid.is_empty()
+            && read_str(data, "/status/monture_active") == compagnon.uid,
+        compagnon,
+    }
+}
+
+/// Companions of one kind, in the order of the sheet.
+pub fn lire_compagnons(data: &Value, kind: TypeCompagnon) -> Result<Vec<Compagnon>, String> {
+    read_list(data, &format!("/{}", kind.key()))
+        .map_err(|e| format!("Compagnons illisibles ({}): {}", kind.key(), e))
+}
+
+fn ecrire_compagnons(
+    data: &mut Value,
+    kind: TypeCompagnon,
+    compagnons: &[Compagnon],
+) -> Result<(), String> {
+    let list = serde_json::to_value(compagnons).map_err(|e| e.to_string())?;
+    set_value(data, &format!("/{}", kind.key()), list);
+    Ok(())
+}
+
+/// Mount the character is riding, if any.
+pub fn monture_active(data: &Value) -> Option<Compagnon> {
+    let uid = read_str(data, "/status/monture_active");
+    if uid.is_empty() {
+        return None;
+    }
+    lire_compagnons(data, TypeCompagnon::Monture)
+        .ok()?
+        .into_iter()
+        .find(|m| m.uid == uid)
+}
+
+/// Rider bonus on a characteristic while mounted.
+pub fn bonus_cavalier_actif(data: &Value, key: &str) -> i32 {
+    monture_active(data)
+        .map(|m| {
+            m.bonus_cavalier()
+                .iter()
+                .filter(|b| b.caracteristique == key)
+                .map(|b| b.valeur)
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Loads the sheet, lets `change` edit the companion list and stores the result.
+fn modifier_compagnons<T>(
+    state: &State<AppState>,
+    id: &str,
+    kind: TypeCompagnon,
+    event: &str,
+    change: impl FnOnce(&mut Vec<Compagnon>, &mut Value) -> Result<(T, Value), String>,
+) -> Result<T, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, id)?;
+    let mut compagnons = lire_compagnons(&data, kind)?;
+    let (result, mut payload) = change(&mut compagnons, &mut data)?;
+    ecrire_compagnons(&mut data, kind, &compagnons)?;
+    let now = store_personnage_data(&tx, id, &data)?;
+
+    payload["kind"] = serde_json::to_value(kind).map_err(|e| e.to_string())?;
+    log_personnage_event(&tx, id, event, &payload, &now)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+fn trouver<'a>(compagnons: &'a mut [Compagnon], uid: &str) -> Result<&'a mut Compagnon, String> {
+    compagnons
+        .iter_mut()
+        .find(|c| c.uid == uid)
+        .ok_or_else(|| format!("Compagnon introuvable: {}", uid))
+}
+
+#[tauri::command]
+pub fn list_companions(
+    id: String,
+    kind: TypeCompagnon,
+    state: State<AppState>,
+) -> Result<Vec<CompagnonDetail>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    Ok(lire_compagnons(&data, kind)?
+        .into_iter()
+        .map(|c| detail(&data, kind, c))
+        .collect())
+}
+
+#[tauri::command]
+pub fn add_companion(
+    id: String,
+    kind: TypeCompagnon,
+    compagnon: Compagnon,
+    state: State<AppState>,
+) -> Result<Compagnon, String> {
+    modifier_compagnons(&state, &id, kind, "companion_add", |compagnons, _| {
+        let mut compagnon = compagnon;
+        compagnon.uid = uuid::Uuid::new_v4().to_string();
+        if compagnon.nom.trim().is_empty() {
+            return Err("Le nom du compagnon est obligatoire".to_string());
+        }
+        compagnons.push(compagnon.clone());
+        let payload = serde_json::json!({ "uid": compagnon.uid, "nom": compagnon.nom });
+        Ok((compagnon, payload))
+    })
+}
+
+/// `compagnon` holds the `uid` and only the fields to change.
+#[tauri::command]
+pub fn update_companion(
+    id: String,
+    kind: TypeCompagnon,
+    compagnon: serde_json::Map<String, Value>,
+    state: State<AppState>,
+) -> Result<Compagnon, String> {
+    let uid = compagnon
+        .get("uid")
+        .and_then(|u| u.as_str())
+        .ok_or("Le compagnon à modifier n'a pas d'uid")?
+        .to_string();
+    modifier_compagnons(&state, &id, kind, "companion_update", |compagnons, _| {
+        let existant = trouver(compagnons, &uid)?;
+        *existant = existant.fusionner(compagnon)?;
+        let compagnon = existant.clone();
+        let payload = serde_json::json!({ "uid": compagnon.uid, "nom": compagnon.nom });
+        Ok((compagnon, payload))
+    })
+}
+
+#[tauri::command]
+pub fn delete_companion(
+    id: String,
+    kind: TypeCompagnon,
+    uid: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    modifier_compagnons(&state, &id, kind, "companion_delete", |compagnons, data| {
+        let avant = compagnons.len();
+        compagnons.retain(|c| c.uid != uid);
+        if compagnons.len() == avant {
+            return Err(format!("Compagnon introuvable: {}", uid));
+        }
+        if read_str(data, "/status/monture_active") == uid {
+            set_value(data, "/status/monture_active", Value::Null);
+        }
+        Ok(((), serde_json::json!({ "uid": uid })))
+    })
+}
+
+/// Companions go down at 0 PV; there is no dying threshold for them.
+#[tauri::command]
+pub fn damage_companion(
+    id: String,
+    kind: TypeCompagnon,
+    uid: String,
+    amount: i64,
+    state: State<AppState>,
+) -> Result<Compagnon, String> {
+    modifier_compagnons(&state, &id, kind, "companion_damage", |compagnons, _| {
+        let compagnon = trouver(compagnons, &uid)?;
+        let mut pv = compagnon.jauge();
+        let variation = pv.subir(amount, 0);
+        compagnon.pv_current = pv.current;
+        let payload = serde_json::json!({ "uid": uid, "amount": amount, "variation": variation });
+        Ok((compagnon.clone(), payload))
+    })
+}
+
+#[tauri::command]
+pub fn heal_companion(
+    id: String,
+    kind: TypeCompagnon,
+    uid: String,
+    amount: i64,
+    state: State<AppState>,
+) -> Result<Compagnon, String> {
+    modifier_compagnons(&state, &id, kind, "companion_heal", |compagnons, _| {
+        let compagnon = trouver(compagnons, &uid)?;
+        let mut pv = compagnon.jauge();
+        let variation = pv.recuperer(amount);
+        compagnon.pv_current = pv.current;
+        let payload = serde_json::json!({ "uid": uid, "amount": amount, "variation": variation });
+        Ok((compagnon.clone(), payload))
+    })
+}
+
+/// Gets on a mount (`uid`) or off it (`None`). The rider bonus and the mount
+/// movement apply while mounted.
+#[tauri::command]
+pub fn set_active_mount(
+    id: String,
+    uid: Option<String>,
+    state: State<AppState>,
+) -> Result<Option<CompagnonDetail>, String> {
+    modifier_compagnons(
+        &state,
+        &id,
+        TypeCompagnon::Monture,
+        "companion_ride",
+        |montures, data| {
+            if let Some(uid) = &uid {
+                trouver(montures, uid)?;
+            }
+            set_value(
+                data,
+                "/status/monture_active",
+                uid.clone().map(Value::from).unwrap_or(Value::Null),
+            );
+            let active = monture_active(data).map(|m| detail(data, TypeCompagnon::Monture, m));
+            Ok((active, serde_json::json!({ "uid": uid })))
+        },
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LigneEntretien {
+    pub kind: TypeCompagnon,
+    pub uid: String,
+    pub nom: String,
+    /// `None` when `entretien` could not be read
+    pub cout: Option<Montant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoutEntretien {
+    pub jours: i64,
+    pub lignes: Vec<LigneEntretien>,
+    /// Total per currency
+    pub total: BTreeMap<String, i64>,
+}
+
+pub fn cout_entretien(data: &Value, jours: i64) -> CoutEntretien {
+    let mut lignes = Vec::new();
+    let mut total = BTreeMap::new();
+    for kind in [
+        TypeCompagnon::Monture,
+        TypeCompagnon::Familier,
+        TypeCompagnon::Invocation,
+    ] {
+        for compagnon in lire_compagnons(data, kind).unwrap_or_default() {
+            let cout = compagnon.entretien_par_periode().map(|e| e.pour(jours));
+            if let Some(cout) = &cout {
+                *total.entry(cout.monnaie.clone()).or_insert(0) += cout.montant;
+            }
+            lignes.push(LigneEntretien {
+                kind,
+                uid: compagnon.uid,
+                nom: compagnon.nom,
+                cout,
+            });
+        }
+    }
+    CoutEntretien {
+        jours,
+        lignes,
+        total,
+    }
+}
+
+#[tauri::command]
+pub fn companion_upkeep(
+    id: String,
+    days: i64,
+    state: State<AppState>,
+) -> Result<CoutEntretien, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    Ok(cout_entretien(&data, days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheval() -> Compagnon {
+        serde_json::from_value(serde_json::json!({
+            "uid": "c1",
+            "nom": "Cheval",
+            "pv_current": "25",
+            "pv_max": 30,
+            "entretien": "3 PA / semaine",
+            "charge_max": "150 kg",
+            "bonus_cavalier": "+1 AT, -1 PRD",
+            "sacoches": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_loose_fields_are_typed() {
+        let cheval = cheval();
+        assert_eq!(cheval.pv_current, 25);
+        assert_eq!(cheval.charge_max_grammes(), Some(150_000));
+        assert_eq!(
+            cheval.bonus_cavalier(),
+            vec![
+                BonusCavalier {
+                    caracteristique: "attaque".to_string(),
+                    valeur: 1
+                },
+                BonusCavalier {
+                    caracteristique: "parade".to_string(),
+                    valeur: -1
+                },
+            ]
+        );
+        // Unknown fields survive a round-trip
+        assert!(serde_json::to_value(&cheval)
+            .unwrap()
+            .get("sacoches")
+            .is_some());
+    }
+
+    #[test]
+    fn test_upkeep_per_started_period() {
+        let entretien = cheval().entretien_par_periode().unwrap();
+        assert_eq!(entretien.jours, 7);
+        assert_eq!(entretien.pour(10).montant, 6);
+        assert_eq!(entretien.pour(10).monnaie, "argent");
+    }
+
+    #[test]
+    fn test_rider_bonus_only_while_mounted() {
+        let mut data = serde_json::json!({ "mounts": [cheval()] });
+        assert_eq!(bonus_cavalier_actif(&data, "attaque"), 0);
+        set_value(&mut data, "/status/monture_active", Value::from("c1"));
+        assert_eq!(bonus_cavalier_actif(&data, "attaque"), 1);
+        assert_eq!(bonus_cavalier_actif(&data, "parade"), -1);
+    }
+
+    #[test]
+    fn test_update_keeps_mount_inventory() {
+        let mut monture = cheval();
+        monture.extra.insert(
+            "inventaire".to_string(),
+            serde_json::json!([{ "uid": "i1", "nom": "Corde", "quantite": 1 }]),
+        );
+        let champs = serde_json::json!({ "uid": "c1", "nom": "Tornado", "pv_current": 12 });
+
+        let maj = monture
+            .fusionner(champs.as_object().unwrap().clone())
+            .unwrap();
+        assert_eq!((maj.nom.as_str(), maj.pv_current), ("Tornado", 12));
+        assert_eq!(maj.pv_max, 30);
+        assert_eq!(maj.bonus_cavalier, "+1 AT, -1 PRD");
+        assert_eq!(maj.extra["inventaire"][0]["nom"], "Corde");
+        assert!(maj.extra.contains_key("sacoches"));
+    }
+
+    #[test]
+    fn test_malformed_list_is_an_error() {
+        let data = serde_json::json!({ "mounts": [cheval(), { "uid": 12 }] });
+        assert!(lire_compagnons(&data, TypeCompagnon::Monture).is_err());
+        assert!(lire_compagnons(&data, TypeCompagnon::Familier)
+            .unwrap()
+            .is_empty());
+    }
+}
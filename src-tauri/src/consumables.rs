@@ -0,0 +1,319 @@
+use crate::db::{
+    load_personnage_data, load_ref_items, log_personnage_event, store_personnage_data, AppState,
+    RefEquipement,
+};
+use crate::inventory::{pointeur_objet, pointeurs_objets, quantite_objet, reference};
+use crate::sheet::{read_i64, read_str};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Stacks lose units, charged items (the "(3c.)" rings) lose charges and keep
+// their place once empty. Food ages with in-game days: `age_jours` counts the
+// days since it was bought and `perime` is set once `peremption` is reached.
+
+/// Shelf life in days, from `peremption` ("6 décades", "2 semaines", "3 jours").
+pub fn duree_en_jours(texte: &str) -> Option<i64> {
+    let texte = texte.trim().to_lowercase();
+    let (nombre, unite) = texte
+        .split_once(char::is_whitespace)
+        .unwrap_or((&texte, "jour"));
+    let nombre: i64 = nombre.parse().ok()?;
+    let jours = match unite.trim().trim_end_matches('s') {
+        "jour" | "j" => 1,
+        "semaine" => 7,
+        "décade" | "decade" => 10,
+        "moi" | "mois" => 30,
+        "an" | "année" | "annee" => 365,
+        _ => return None,
+    };
+    Some(nombre * jours)
+}
+
+/// Charges of a magic item, from the `(3c.)` in its name or `details.charge`.
+pub fn charges_max(reference: &RefEquipement) -> Option<i64> {
+    let depuis_nom = reference.nom.split('(').find_map(|morceau| {
+        morceau
+            .trim()
+            .strip_suffix("c.)")
+            .and_then(|n| n.trim().parse::<i64>().ok())
+    });
+    depuis_nom
+        .or_else(|| Some(read_i64(&reference.details, "/charge")))
+        .filter(|c| *c > 0)
+}
+
+fn detail<'a>(reference: Option<&'a RefEquipement>, key: &str) -> &'a str {
+    reference
+        .map(|r| read_str(&r.details, &format!("/{}", key)))
+        .unwrap_or("")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Consommation {
+    pub uid: String,
+    pub nom: String,
+    pub quantite: i64,
+    pub effet: String,
+    /// PA spent to drink or apply a potion
+    pub cout_pa: i64,
+    /// What is left in hand: "Fiole", "Seringue"…
+    pub contenant: Option<String>,
+    pub perime: bool,
+    /// Units or charges left
+    pub restant: i64,
+    /// The stack was used up and removed from the sheet
+    pub retire: bool,
+}
+
+pub fn consommer(
+    data: &mut Value,
+    refs: &[RefEquipement],
+    uid: &str,
+    quantite: i64,
+) -> Result<Consommation, String> {
+    if quantite <= 0 {
+        return Err("La quantité doit être positive".to_string());
+    }
+    let pointer = pointeur_objet(data, uid).ok_or_else(|| format!("Objet introuvable: {}", uid))?;
+    let item = data.pointer(&pointer).cloned().unwrap_or_default();
+    let reference = reference(&item, refs);
+
+    let nom = reference
+        .map(|r| r.nom.clone())
+        .unwrap_or_else(|| read_str(&item, "/nom").to_string());
+    let mut consommation = Consommation {
+        uid: uid.to_string(),
+        nom,
+        quantite,
+        effet: detail(reference, "effet").to_string(),
+        cout_pa: reference.map_or(0, |r| read_i64(&r.details, "/cout_pa")) * quantite,
+        contenant: Some(detail(reference, "contenant").to_string()).filter(|c| !c.is_empty()),
+        perime: item
+            .get("perime")
+            .and_then(|p| p.as_bool())
+            .unwrap_or(false),
+        restant: 0,
+        retire: false,
+    };
+
+    let charges = item
+        .get("charges")
+        .map(|_| read_i64(&item, "/charges"))
+        .or_else(|| reference.and_then(charges_max));
+    if let Some(charges) = charges {
+        if quantite > charges {
+            return Err(format!(
+                "Pas assez de charges: {} demandées, {} restantes",
+                quantite, charges
+            ));
+        }
+        consommation.restant = charges - quantite;
+        data.pointer_mut(&pointer).expect("just found")["charges"] =
+            Value::from(consommation.restant);
+        return Ok(consommation);
+    }
+
+    let disponible = quantite_objet(&item);
+    if quantite > disponible {
+        return Err(format!(
+            "Quantité insuffisante: {} demandés, {} disponibles",
+            quantite, disponible
+        ));
+    }
+    consommation.restant = disponible - quantite;
+    if consommation.restant > 0 {
+        data.pointer_mut(&pointer).expect("just found")["quantite"] =
+            Value::from(consommation.restant);
+    } else {
+        let (liste, index) = pointer.rsplit_once('/').expect("item pointer");
+        let index: usize = index.parse().map_err(|_| "Pointeur invalide".to_string())?;
+        if let Some(items) = data.pointer_mut(liste).and_then(|v| v.as_array_mut()) {
+            // A used-up container leaves what it held where it stood
+            let contenu = match items.remove(index).get_mut("contenu") {
+                Some(Value::Array(contenu)) => std::mem::take(contenu),
+                _ => Vec::new(),
+            };
+            items.splice(index..index, contenu);
+        }
+        consommation.retire = true;
+    }
+    Ok(consommation)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peremption {
+    pub uid: String,
+    pub nom: String,
+    pub age_jours: i64,
+    pub duree_jours: i64,
+    pub perime: bool,
+}
+
+/// Ages every perishable item by `jours` days and flags what went off.
+pub fn vieillir(data: &mut Value, refs: &[RefEquipement], jours: i64) -> Vec<Peremption> {
+    let mut rapport = Vec::new();
+    for pointer in pointeurs_objets(data) {
+        let Some(item) = data.pointer_mut(&pointer) else {
+            continue;
+        };
+        let reference = reference(item, refs);
+        let texte = item
+            .get("peremption")
+            .and_then(|p| p.as_str())
+            .unwrap_or_else(|| detail(reference, "peremption"));
+        let Some(duree_jours) = duree_en_jours(texte) else {
+            continue;
+        };
+        let nom = reference
+            .map(|r| r.nom.clone())
+            .unwrap_or_else(|| read_str(item, "/nom").to_string());
+
+        let age_jours = read_i64(item, "/age_jours") + jours.max(0);
+        let perime = age_jours >= duree_jours;
+        item["age_jours"] = Value::from(age_jours);
+        item["perime"] = Value::from(perime);
+        rapport.push(Peremption {
+            uid: read_str(item, "/uid").to_string(),
+            nom,
+            age_jours,
+            duree_jours,
+            perime,
+        });
+    }
+    rapport
+}
+
+/// Uses `quantite` units or charges (1 by default) of an item, wherever it is
+/// stored, and returns what it does.
+#[tauri::command]
+pub fn consume_item(
+    id: String,
+    uid: String,
+    quantite: Option<i64>,
+    state: State<AppState>,
+) -> Result<Consommation, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let refs = load_ref_items(&db)?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let consommation = consommer(&mut data, &refs, &uid, quantite.unwrap_or(1))?;
+    let now = store_personnage_data(&tx, &id, &data)?;
+    let payload = serde_json::to_value(&consommation).map_err(|e| e.to_string())?;
+    log_personnage_event(&tx, &id, "consume_item", &payload, &now)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(consommation)
+}
+
+#[tauri::command]
+pub fn advance_food_expiry(
+    id: String,
+    jours: i64,
+    state: State<AppState>,
+) -> Result<Vec<Peremption>, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let refs = load_ref_items(&db)?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let rapport = vieillir(&mut data, &refs, jours);
+    let now = store_personnage_data(&tx, &id, &data)?;
+    log_personnage_event(
+        &tx,
+        &id,
+        "food_expiry",
+        &serde_json::json!({ "jours": jours }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(rapport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bague() -> RefEquipement {
+        RefEquipement {
+            id: 7,
+            category: "Objets_magiques".to_string(),
+            ref_id: 1,
+            nom: "Bague de Puissance des Universitaires (niv. 1) (3c.)".to_string(),
+            degats: Value::Null,
+            caracteristiques: Value::Null,
+            protections: Value::Null,
+            prix_info: Value::Null,
+            craft: Value::Null,
+            details: serde_json::json!({ "effet": "Dégâts +4", "charge": "0" }),
+        }
+    }
+
+    #[test]
+    fn test_stacks_and_charges_are_used_up() {
+        let refs = vec![bague()];
+        let mut data = serde_json::json!({
+            "inventory": [
+                { "uid": "b1", "refId": 7 },
+                { "uid": "sac", "nom": "Sac", "contenu": [
+                    { "uid": "f1", "nom": "Flèches", "quantite": 2 }
+                ] }
+            ]
+        });
+
+        let tir = consommer(&mut data, &refs, "f1", 1).unwrap();
+        assert_eq!((tir.restant, tir.retire), (1, false));
+        assert!(consommer(&mut data, &refs, "f1", 2).is_err());
+        assert!(consommer(&mut data, &refs, "f1", 1).unwrap().retire);
+        assert_eq!(data.pointer("/inventory/1/contenu/0"), None);
+
+        let bague = consommer(&mut data, &refs, "b1", 2).unwrap();
+        assert_eq!(bague.effet, "Dégâts +4");
+        assert_eq!(bague.restant, 1);
+        assert!(consommer(&mut data, &refs, "b1", 2).is_err());
+        // An empty ring stays in the inventory
+        consommer(&mut data, &refs, "b1", 1).unwrap();
+        assert_eq!(read_i64(&data, "/inventory/0/charges"), 0);
+    }
+
+    #[test]
+    fn test_food_goes_off() {
+        assert_eq!(duree_en_jours("6 décades"), Some(60));
+        assert_eq!(duree_en_jours("2 semaines"), Some(14));
+        assert_eq!(duree_en_jours(""), None);
+
+        let mut data = serde_json::json!({
+            "inventory": [
+                { "uid": "pain", "nom": "Pain", "peremption": "3 jours" },
+                { "uid": "corde", "nom": "Corde" }
+            ]
+        });
+        assert!(!vieillir(&mut data, &[], 2)[0].perime);
+        let rapport = vieillir(&mut data, &[], 1);
+        assert_eq!(rapport.len(), 1);
+        assert!(rapport[0].perime);
+        assert!(consommer(&mut data, &[], "pain", 1).unwrap().perime);
+    }
+
+    #[test]
+    fn test_used_up_container_keeps_its_contents() {
+        let mut data = serde_json::json!({
+            "inventory": [
+                { "uid": "corde", "nom": "Corde" },
+                { "uid": "panier", "nom": "Panier de pommes", "contenu": [
+                    { "uid": "p1", "nom": "Pomme" },
+                    { "uid": "c1", "nom": "Couteau" }
+                ] }
+            ]
+        });
+
+        assert!(consommer(&mut data, &[], "panier", 1).unwrap().retire);
+        let uids: Vec<&str> = data["inventory"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| read_str(i, "/uid"))
+            .collect();
+        assert_eq!(uids, vec!["corde", "p1", "c1"]);
+    }
+}
@@ -1,14 +1,26 @@
-use rusqlite::{Connection, Result};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::Value; // Keep generic JSON for flexibility
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+pub const DB_FILENAME: &str = "codex_debilium.db";
+/// Where builds before the app-data move kept the database, relative to the working directory
+pub const LEGACY_DB_PATH: &str = "../codex_debilium.db";
+/// Overrides the database location (tests, portable installs)
+pub const DB_PATH_ENV: &str = "CODEX_DEBILIUM_DB";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Personnage {
     pub id: String,
     pub name: String,
     pub data: Value,        // Complete sheet data
     pub updated_at: String, // ISO timestamp for sync
+    /// Set while the character is in the trash; pushed to the cloud as a tombstone.
+    /// Always serialized (`null` for live rows): a bulk upsert needs the same keys
+    /// on every object.
+    #[serde(default)]
+    pub deleted_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,57 +37,191 @@ pub struct RefEquipement {
     pub details: serde_json::Value,          // JSON: { aura, type, effet, poids, ... }
 }
 
+pub fn load_ref_items(conn: &Connection) -> std::result::Result<Vec<RefEquipement>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, category, ref_id, nom, degats, caracteristiques, protections, prix_info, craft, details FROM ref_items")
+        .map_err(|e| e.to_string())?;
+
+    let items_iter = stmt
+        .query_map([], |row| {
+            Ok(RefEquipement {
+                id: row.get(0)?,
+                category: row.get(1)?,
+                ref_id: row.get(2)?,
+                nom: row.get(3)?,
+                degats: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+                caracteristiques: serde_json::from_str(&row.get::<_, String>(5)?)
+                    .unwrap_or_default(),
+                protections: serde_json::from_str(&row.get::<_, String>(6)?).unwrap_or_default(),
+                prix_info: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+                craft: serde_json::from_str(&row.get::<_, String>(8)?).unwrap_or_default(),
+                details: serde_json::from_str(&row.get::<_, String>(9)?).unwrap_or_default(),
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut items = Vec::new();
+    for item in items_iter {
+        items.push(item.map_err(|e| e.to_string())?);
+    }
+
+    Ok(items)
+}
+
 pub struct AppState {
     pub db: Mutex<Connection>,
 }
 
-pub fn init_db() -> Result<Connection> {
-    let conn = Connection::open("../codex_debilium.db")?;
+/// The database file: `$CODEX_DEBILIUM_DB` when set, else inside the app data dir.
+pub fn resolve_db_path(app_data_dir: &Path) -> PathBuf {
+    db_path_with_override(
+        app_data_dir,
+        std::env::var_os(DB_PATH_ENV).map(PathBuf::from),
+    )
+}
+
+fn db_path_with_override(app_data_dir: &Path, override_path: Option<PathBuf>) -> PathBuf {
+    override_path
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| app_data_dir.join(DB_FILENAME))
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS personnages (
-            id TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            data TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        )",
-        [],
-    )?;
-
-    // Updated Table Schema to match Supabase
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS ref_items (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            category TEXT NOT NULL,
-            ref_id INTEGER DEFAULT 0,
-            nom TEXT NOT NULL,
-            degats TEXT DEFAULT '{}',
-            caracteristiques TEXT DEFAULT '{}',
-            protections TEXT DEFAULT '{}',
-            prix_info TEXT DEFAULT '{}',
-            craft TEXT DEFAULT '{}',
-            details TEXT DEFAULT '{}'
-        )",
-        [],
-    )?;
+/// Copies a database left at the old location to `target` on first launch.
+/// The old file is kept as a backup. Returns whether a copy was made.
+///
+/// The copy is written next to `target` and renamed into place once complete,
+/// so a failed copy leaves no `target` behind and is retried on next launch.
+pub fn migrate_legacy_db(legacy: &Path, target: &Path) -> std::result::Result<bool, String> {
+    if target.exists() || !legacy.is_file() {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    // Copy through SQLite so a half-written journal is folded in. VACUUM INTO
+    // refuses to overwrite a leftover, which write_atomic removes first.
+    let source = Connection::open(legacy).map_err(|e| e.to_string())?;
+    crate::files::write_atomic(target, |part| {
+        source
+            .execute("VACUUM INTO ?1", [part.to_string_lossy()])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })?;
+    Ok(true)
+}
+
+/// Opens the database as it is, without touching its schema.
+pub fn open_db(path: &Path) -> std::result::Result<Connection, String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        // Reported by Connection::open below if it really is a problem
+        let _ = std::fs::create_dir_all(parent);
+    }
+    Connection::open(path).map_err(|e| e.to_string())
+}
 
+/// Opens the database and applies pending schema migrations.
+pub fn init_db(path: &Path) -> std::result::Result<Connection, String> {
+    let mut conn = open_db(path)?;
+    crate::migrations::run_migrations(&mut conn)?;
+    Ok(conn)
+}
+
+/// Loads the JSON sheet of a character, for commands that update it server-side.
+/// A character in the trash is not found: it has to be restored first.
+pub fn load_personnage_data(conn: &Connection, id: &str) -> std::result::Result<Value, String> {
+    let data_str: String = conn
+        .query_row(
+            "SELECT data FROM personnages WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Personnage introuvable: {}", e))?;
+
+    serde_json::from_str(&data_str).map_err(|e| e.to_string())
+}
+
+/// Writes back a sheet modified server-side. Unlike `save_personnage_local`,
+/// no version is kept: the change goes to the undo journal and the caller is
+/// expected to log its own event. The label of the last save no longer
+/// describes the sheet and is dropped.
+pub fn store_personnage_data(
+    conn: &Connection,
+    id: &str,
+    data: &Value,
+) -> std::result::Result<String, String> {
+    if let Some(old) = crate::journal::previous_data(conn, id)? {
+        crate::journal::record_change(conn, id, &old, data)?;
+    }
+    let now = chrono::Utc::now().to_rfc3339();
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS personnages_versions (
-            version_id INTEGER PRIMARY KEY AUTOINCREMENT,
-            personnage_id TEXT NOT NULL,
-            data TEXT NOT NULL,
-            saved_at TEXT NOT NULL
-        )",
-        [],
-    )?;
+        "UPDATE personnages SET data = ?1, updated_at = ?2, version_label = NULL WHERE id = ?3",
+        rusqlite::params![data.to_string(), now, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(now)
+}
 
+/// Appends a compact entry to the per-character event log.
+pub fn log_personnage_event(
+    conn: &Connection,
+    id: &str,
+    kind: &str,
+    payload: &Value,
+    created_at: &str,
+) -> std::result::Result<i64, String> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS db_meta (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        )",
-        [],
-    )?;
+        "INSERT INTO personnages_events (personnage_id, kind, payload, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![id, kind, payload.to_string(), created_at],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
 
-    Ok(conn)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    #[test]
+    fn test_db_path_override() {
+        let app_data = Path::new("/data/com.valkiros.codex-debilium");
+        assert_eq!(
+            db_path_with_override(app_data, None),
+            app_data.join(DB_FILENAME)
+        );
+        assert_eq!(
+            db_path_with_override(app_data, Some(PathBuf::from("/tmp/test.db"))),
+            PathBuf::from("/tmp/test.db")
+        );
+    }
+
+    #[test]
+    fn test_legacy_db_is_migrated_once() {
+        let dir = test_dir();
+        let legacy = dir.join("old.db");
+        let target = dir.join("app_data").join(DB_FILENAME);
+
+        let conn = init_db(&legacy).unwrap();
+        conn.execute(
+            "INSERT INTO personnages (id, name, data, updated_at) VALUES ('p1', 'Gurdil', '{}', 'now')",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        assert!(migrate_legacy_db(&legacy, &target).unwrap());
+        let conn = init_db(&target).unwrap();
+        let name: String = conn
+            .query_row("SELECT name FROM personnages WHERE id = 'p1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(name, "Gurdil");
+        assert!(!crate::files::part_path(&target).exists());
+        // The new database wins from then on
+        assert!(!migrate_legacy_db(&legacy, &target).unwrap());
+        assert!(!migrate_legacy_db(&dir.join("missing.db"), &dir.join("other.db")).unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
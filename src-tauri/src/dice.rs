@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+// Dice expressions as written in the item tables: "1D6+2", "2D", "1D20", or a
+// plain number. A bare "D" is a six-sided die.
+
+pub trait Lanceur {
+    /// One die, from 1 to `faces`.
+    fn lancer(&mut self, faces: i64) -> i64;
+}
+
+/// xorshift64* seeded from the OS random source behind `Uuid::new_v4`; plenty
+/// for a tabletop game and no extra dependency.
+pub struct Des {
+    etat: u64,
+}
+
+impl Des {
+    pub fn new() -> Self {
+        let graine = uuid::Uuid::new_v4().as_u128();
+        Des {
+            etat: (graine as u64 ^ (graine >> 64) as u64) | 1,
+        }
+    }
+}
+
+impl Default for Des {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lanceur for Des {
+    fn lancer(&mut self, faces: i64) -> i64 {
+        if faces <= 1 {
+            return faces.max(0);
+        }
+        self.etat ^= self.etat >> 12;
+        self.etat ^= self.etat << 25;
+        self.etat ^= self.etat >> 27;
+        let tirage = self.etat.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (tirage % faces as u64) as i64 + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expression {
+    pub des: i64,
+    pub faces: i64,
+    pub bonus: i64,
+}
+
+impl Expression {
+    pub fn parse(texte: &str) -> Result<Self, String> {
+        let erreur = || format!("Expression de dés invalide: {}", texte);
+        let compact: String = texte
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+        if compact.is_empty() {
+            return Err(erreur());
+        }
+
+        // Split off a trailing "+N" / "-N"
+        let (jet, bonus) = match compact.rfind(['+', '-']) {
+            Some(i) if i > 0 => {
+                let bonus = compact[i..].parse::<i64>().map_err(|_| erreur())?;
+                (&compact[..i], bonus)
+            }
+            _ => (compact.as_str(), 0),
+        };
+
+        match jet.split_once('D') {
+            None => {
+                let fixe = jet.parse::<i64>().map_err(|_| erreur())?;
+                Ok(Expression {
+                    des: 0,
+                    faces: 0,
+                    bonus: fixe + bonus,
+                })
+            }
+            Some((des, faces)) => {
+                let des = if des.is_empty() {
+                    1
+                } else {
+                    des.parse().map_err(|_| erreur())?
+                };
+                let faces = if faces.is_empty() {
+                    6
+                } else {
+                    faces.parse().map_err(|_| erreur())?
+                };
+                if des < 0 || faces < 1 || des > 100 {
+                    return Err(erreur());
+                }
+                Ok(Expression { des, faces, bonus })
+            }
+        }
+    }
+
+    pub fn lancer(&self, lanceur: &mut impl Lanceur) -> Jet {
+        let des: Vec<i64> = (0..self.des).map(|_| lanceur.lancer(self.faces)).collect();
+        Jet {
+            expression: *self,
+            total: des.iter().sum::<i64>() + self.bonus,
+            des,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jet {
+    pub expression: Expression,
+    pub des: Vec<i64>,
+    pub total: i64,
+}
+
+pub fn lancer(texte: &str, lanceur: &mut impl Lanceur) -> Result<Jet, String> {
+    Ok(Expression::parse(texte)?.lancer(lanceur))
+}
+
+#[tauri::command]
+pub fn roll_dice(expression: String) -> Result<Jet, String> {
+    lancer(&expression, &mut Des::new())
+}
+
+/// Replays given values, for tests.
+#[cfg(test)]
+pub struct DesPipes(pub std::collections::VecDeque<i64>);
+
+#[cfg(test)]
+impl Lanceur for DesPipes {
+    fn lancer(&mut self, faces: i64) -> i64 {
+        self.0.pop_front().expect("no more dice").clamp(1, faces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_table_expressions() {
+        let e = |t| Expression::parse(t).unwrap();
+        assert_eq!(
+            e("1D"),
+            Expression {
+                des: 1,
+                faces: 6,
+                bonus: 0
+            }
+        );
+        assert_eq!(
+            e("2d8 + 3"),
+            Expression {
+                des: 2,
+                faces: 8,
+                bonus: 3
+            }
+        );
+        assert_eq!(
+            e("1D20-1"),
+            Expression {
+                des: 1,
+                faces: 20,
+                bonus: -1
+            }
+        );
+        assert_eq!(
+            e("4"),
+            Expression {
+                des: 0,
+                faces: 0,
+                bonus: 4
+            }
+        );
+        assert!(Expression::parse("D&D").is_err());
+    }
+
+    #[test]
+    fn test_rolls_stay_on_the_die() {
+        let mut des = Des::new();
+        for _ in 0..1000 {
+            let jet = lancer("3D6", &mut des).unwrap();
+            assert!((3..=18).contains(&jet.total));
+        }
+        let mut pipes = DesPipes([2, 5].into());
+        assert_eq!(lancer("2D6+1", &mut pipes).unwrap().total, 8);
+    }
+}
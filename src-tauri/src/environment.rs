@@ -0,0 +1,225 @@
+use crate::db::{load_personnage_data, load_ref_items, AppState};
+use crate::equipement::{pieces_portees, PieceEquipee};
+use crate::logic::StatDetail;
+use crate::modifiers::pousser_effets;
+use crate::sheet::read_i64;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Exposure beyond what the gear covers costs 1 point of malus per point of
+// shortfall, and cold or heat deal that shortfall in PV every few hours. The cap
+// and the pace are house rules, kept in `data/config/environnement.json`.
+
+/// How hard the weather bites, from `data/config/environnement.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReglesEnvironnement {
+    /// Largest malus the weather can give, whatever the shortfall
+    #[serde(rename = "Malus max")]
+    pub malus_max: i32,
+    /// Hours of exposure per wound (cold or heat) or per soaking step (rain)
+    #[serde(rename = "Heures par blessure")]
+    pub heures_par_blessure: i64,
+}
+
+pub fn regles_environnement() -> Result<ReglesEnvironnement, String> {
+    serde_json::from_str(include_str!("../data/config/environnement.json"))
+        .map_err(|e| format!("Failed to parse environnement.json: {}", e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Element {
+    Pluie,
+    Froid,
+    Chaleur,
+}
+
+impl Element {
+    fn key(&self) -> &'static str {
+        match self {
+            Element::Pluie => "pluie",
+            Element::Froid => "froid",
+            Element::Chaleur => "chaleur",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConditionsMeteo {
+    pub element: Element,
+    /// Harshness decided by the GM: 1 for a drizzle or a chilly night, 7+ for a
+    /// blizzard or the middle of the desert
+    pub severite: i64,
+    pub duree_heures: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionsEnvironnement {
+    pub pluie: StatDetail,
+    pub froid: StatDetail,
+    pub chaleur: StatDetail,
+}
+
+impl ProtectionsEnvironnement {
+    fn get(&self, element: Element) -> &StatDetail {
+        match element {
+            Element::Pluie => &self.pluie,
+            Element::Froid => &self.froid,
+            Element::Chaleur => &self.chaleur,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exposition {
+    pub element: Element,
+    pub severite: i64,
+    pub protection: i64,
+    /// Severity not covered by the gear
+    pub exces: i64,
+    /// Malus on every test while exposed
+    pub malus: i32,
+    pub degats_pv: i64,
+    /// Suggested value for `status.senses.humectation`
+    pub humectation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentReport {
+    pub protections: ProtectionsEnvironnement,
+    pub exposition: Option<Exposition>,
+}
+
+pub fn protections_environnement(
+    data: &Value,
+    pieces: &[PieceEquipee],
+) -> ProtectionsEnvironnement {
+    let total = |element: Element| {
+        let mut detail = StatDetail::new("Protections + Accessoires");
+        for piece in pieces {
+            detail.push(piece.nom(), piece.protection(element.key()) as i32);
+        }
+        detail.push(
+            "Temporaire",
+            read_i64(data, &format!("/magic/protection_{}/temp", element.key())) as i32,
+        );
+        pousser_effets(&mut detail, data, &format!("protection_{}", element.key()));
+        detail
+    };
+
+    ProtectionsEnvironnement {
+        pluie: total(Element::Pluie),
+        froid: total(Element::Froid),
+        chaleur: total(Element::Chaleur),
+    }
+}
+
+pub fn evaluer_exposition(
+    regles: &ReglesEnvironnement,
+    protection: i64,
+    conditions: &ConditionsMeteo,
+) -> Exposition {
+    let exces = (conditions.severite - protection).max(0);
+    let duree = conditions.duree_heures.max(0);
+    let expose = exces > 0 && duree > 0;
+    let heures = regles.heures_par_blessure.max(1);
+
+    let malus = if expose {
+        -(exces.min(regles.malus_max as i64) as i32)
+    } else {
+        0
+    };
+    let degats_pv = match conditions.element {
+        Element::Froid | Element::Chaleur if expose => exces * (duree / heures),
+        _ => 0,
+    };
+    let humectation = expose.then(|| {
+        match conditions.element {
+            Element::Pluie if exces * duree >= heures => "Trempé",
+            Element::Pluie => "Mouillé",
+            Element::Froid if degats_pv > 0 => "Frigorifié",
+            Element::Froid => "Froid",
+            Element::Chaleur if degats_pv > 0 => "Calciné",
+            Element::Chaleur => "Desséché",
+        }
+        .to_string()
+    });
+
+    Exposition {
+        element: conditions.element,
+        severite: conditions.severite,
+        protection,
+        exces,
+        malus,
+        degats_pv,
+        humectation,
+    }
+}
+
+/// Sums the rain, cold and heat protection of the worn items. With `conditions`,
+/// also tells what the weather does to the character; nothing is applied to the sheet.
+#[tauri::command]
+pub fn compute_environment_protection(
+    id: String,
+    conditions: Option<ConditionsMeteo>,
+    state: State<AppState>,
+) -> Result<EnvironmentReport, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    let refs = load_ref_items(&db)?;
+    drop(db);
+
+    let pieces = pieces_portees(&data, &refs);
+    let protections = protections_environnement(&data, &pieces);
+    let exposition = match conditions {
+        Some(c) => {
+            let protection = protections.get(c.element).total as i64;
+            Some(evaluer_exposition(&regles_environnement()?, protection, &c))
+        }
+        None => None,
+    };
+
+    Ok(EnvironmentReport {
+        protections,
+        exposition,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(element: Element, severite: i64, duree_heures: i64) -> ConditionsMeteo {
+        ConditionsMeteo {
+            element,
+            severite,
+            duree_heures,
+        }
+    }
+
+    fn regles() -> ReglesEnvironnement {
+        regles_environnement().unwrap()
+    }
+
+    #[test]
+    fn test_protected_character_is_unaffected() {
+        let exposition = evaluer_exposition(&regles(), 3, &conditions(Element::Froid, 3, 12));
+        assert_eq!(exposition.exces, 0);
+        assert_eq!(exposition.malus, 0);
+        assert_eq!(exposition.degats_pv, 0);
+        assert_eq!(exposition.humectation, None);
+    }
+
+    #[test]
+    fn test_cold_exposure_hurts_over_time() {
+        let exposition = evaluer_exposition(&regles(), 1, &conditions(Element::Froid, 4, 9));
+        assert_eq!(exposition.malus, -3);
+        assert_eq!(exposition.degats_pv, 6);
+        assert_eq!(exposition.humectation.as_deref(), Some("Frigorifié"));
+
+        let pluie = evaluer_exposition(&regles(), 0, &conditions(Element::Pluie, 1, 2));
+        assert_eq!(pluie.degats_pv, 0);
+        assert_eq!(pluie.humectation.as_deref(), Some("Mouillé"));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::db::RefEquipement;
+use crate::modifiers::total_effets;
+use crate::sheet::read_i64;
+use serde_json::Value;
+
+// Worn equipment as the character sheet sees it: only Protections and Accessoires
+// count, and a shield only while `defenses.bouclier_actif` is set. Weapons at hand
+// are the rows of the Armes and Mains nues tables, not what is packed in a bag.
+
+pub struct PieceEquipee<'a> {
+    pub item: &'a Value,
+    pub reference: Option<&'a RefEquipement>,
+}
+
+impl PieceEquipee<'_> {
+    pub fn nom(&self) -> String {
+        self.reference
+            .map(|r| r.nom.clone())
+            .or_else(|| {
+                self.item
+                    .get("nom")
+                    .and_then(|n| n.as_str())
+                    .map(String::from)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Value of `caracteristiques.<key>` on the reference item.
+    pub fn carac(&self, key: &str) -> i64 {
+        self.reference
+            .map(|r| read_i64(&r.caracteristiques, &format!("/{}", key)))
+            .unwrap_or(0)
+    }
+
+    /// Value of `protections.<key>` on the reference item.
+    pub fn protection(&self, key: &str) -> i64 {
+        self.reference
+            .map(|r| read_i64(&r.protections, &format!("/{}", key)))
+            .unwrap_or(0)
+    }
+
+    /// Reference PR solide plus the per-item modifier.
+    pub fn pr_solide(&self) -> i64 {
+        self.protection("pr_sol") + read_i64(self.item, "/modif_pr_sol")
+    }
+}
+
+pub fn pieces_portees<'a>(data: &'a Value, refs: &'a [RefEquipement]) -> Vec<PieceEquipee<'a>> {
+    let bouclier_actif = data
+        .pointer("/defenses/bouclier_actif")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    data.get("inventory")
+        .and_then(|i| i.as_array())
+        .map(|items| items.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|item| {
+            matches!(
+                item.get("equipement_type").and_then(|t| t.as_str()),
+                Some("Protections" | "Accessoires")
+            )
+        })
+        .map(|item| {
+            let ref_id = read_i64(item, "/refId");
+            PieceEquipee {
+                item,
+                reference: refs.iter().find(|r| r.id == ref_id),
+            }
+        })
+        .filter(|piece| {
+            let bouclier = piece
+                .reference
+                .and_then(|r| r.details.get("type"))
+                .and_then(|t| t.as_str())
+                == Some("Bouclier");
+            !bouclier || bouclier_actif
+        })
+        .collect()
+}
+
+/// PR solide of the worn equipment, plus the temporary value and effects of the sheet.
+pub fn pr_solide_totale(data: &Value, pieces: &[PieceEquipee]) -> i64 {
+    pieces.iter().map(|p| p.pr_solide()).sum::<i64>()
+        + read_i64(data, "/defenses/solide/temp")
+        + total_effets(data, "pr_sol") as i64
+}
+
+/// Weapons at hand, Armes before Mains nues, as listed on the sheet. Broken
+/// ones and rows with no reference picked are left out.
+pub fn armes_portees<'a>(data: &'a Value, refs: &'a [RefEquipement]) -> Vec<PieceEquipee<'a>> {
+    let inventaire = data
+        .get("inventory")
+        .and_then(|i| i.as_array())
+        .map(|items| items.as_slice())
+        .unwrap_or_default();
+
+    ["Armes", "MainsNues"]
+        .iter()
+        .flat_map(|kind| {
+            inventaire.iter().filter(move |item| {
+                item.get("equipement_type").and_then(|t| t.as_str()) == Some(kind)
+            })
+        })
+        .filter(|item| item.get("etat").and_then(|e| e.as_str()) != Some("Cassé"))
+        .filter_map(|item| {
+            let ref_id = read_i64(item, "/refId");
+            refs.iter()
+                .find(|r| r.id == ref_id)
+                .map(|reference| PieceEquipee {
+                    item,
+                    reference: Some(reference),
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,375 @@
+use crate::db::{load_personnage_data, log_personnage_event, store_personnage_data, AppState};
+use crate::sheet::{read_i64, read_str, set_value};
+use crate::vitals::{read_jauge, Jauge, Pool};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+pub const EPUISEMENT_MAX: u8 = 10;
+
+// Hours of marching that cost one fatigue level
+pub const HEURES_MARCHE_PAR_NIVEAU: i64 = 4;
+
+// A rest at least this long (and not of poor quality) ends in "Reposé"
+pub const HEURES_NUIT_COMPLETE: i64 = 8;
+
+/// Fatigue levels, labelled as in the status panel ("Reposé", "Normal", "Fatigué", "Epuisé 1".."Epuisé 10").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NiveauFatigue {
+    Repose,
+    Normal,
+    Fatigue,
+    Epuise(u8),
+}
+
+impl NiveauFatigue {
+    pub fn from_label(label: &str) -> Self {
+        match label.trim() {
+            "Reposé" => NiveauFatigue::Repose,
+            "Fatigué" => NiveauFatigue::Fatigue,
+            other => match other.strip_prefix("Epuisé") {
+                Some(n) => NiveauFatigue::Epuise(
+                    n.trim().parse::<u8>().unwrap_or(1).clamp(1, EPUISEMENT_MAX),
+                ),
+                None => NiveauFatigue::Normal,
+            },
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            NiveauFatigue::Repose => "Reposé".to_string(),
+            NiveauFatigue::Normal => "Normal".to_string(),
+            NiveauFatigue::Fatigue => "Fatigué".to_string(),
+            NiveauFatigue::Epuise(n) => format!("Epuisé {}", n),
+        }
+    }
+
+    /// Modifier applied to characteristics: +1 when rested, -N when exhausted.
+    pub fn modificateur(&self) -> i32 {
+        match self {
+            NiveauFatigue::Repose => 1,
+            NiveauFatigue::Normal | NiveauFatigue::Fatigue => 0,
+            NiveauFatigue::Epuise(n) => -(*n as i32),
+        }
+    }
+
+    /// Malus as expected by `Etats::fatigue` (positive means a penalty).
+    pub fn malus(&self) -> i32 {
+        -self.modificateur()
+    }
+
+    fn rang(&self) -> i64 {
+        match self {
+            NiveauFatigue::Repose => 0,
+            NiveauFatigue::Normal => 1,
+            NiveauFatigue::Fatigue => 2,
+            NiveauFatigue::Epuise(n) => 2 + *n as i64,
+        }
+    }
+
+    fn depuis_rang(rang: i64) -> Self {
+        match rang.clamp(0, 2 + EPUISEMENT_MAX as i64) {
+            0 => NiveauFatigue::Repose,
+            1 => NiveauFatigue::Normal,
+            2 => NiveauFatigue::Fatigue,
+            n => NiveauFatigue::Epuise((n - 2) as u8),
+        }
+    }
+
+    pub fn aggraver(&self, niveaux: i64) -> Self {
+        Self::depuis_rang(self.rang() + niveaux.max(0))
+    }
+
+    /// Recovery brings the character back to "Normal" at best.
+    pub fn recuperer(&self, niveaux: i64) -> Self {
+        let rang = (self.rang() - niveaux.max(0)).max(NiveauFatigue::Normal.rang());
+        Self::depuis_rang(rang.min(self.rang()))
+    }
+}
+
+impl Serialize for NiveauFatigue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.label())
+    }
+}
+
+impl<'de> Deserialize<'de> for NiveauFatigue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        Ok(NiveauFatigue::from_label(&label))
+    }
+}
+
+/// Recovery quality, labelled as in the status panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualiteRepos {
+    Excellente,
+    Normale,
+    Basse,
+}
+
+impl QualiteRepos {
+    pub fn from_label(label: &str) -> Self {
+        if label.contains("Excellente") {
+            QualiteRepos::Excellente
+        } else if label.contains("Basse") {
+            QualiteRepos::Basse
+        } else {
+            QualiteRepos::Normale
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualiteRepos::Excellente => "Excellente",
+            QualiteRepos::Normale => "Normale",
+            QualiteRepos::Basse => "Basse",
+        }
+    }
+
+    // Same rates as the recovery preview of the status panel
+    fn pv_par_heure(&self) -> f64 {
+        match self {
+            QualiteRepos::Excellente => 1.0,
+            QualiteRepos::Normale => 0.5,
+            QualiteRepos::Basse => 0.25,
+        }
+    }
+
+    fn pm_par_heure(&self) -> i64 {
+        match self {
+            QualiteRepos::Excellente => 4,
+            QualiteRepos::Normale => 3,
+            QualiteRepos::Basse => 2,
+        }
+    }
+
+    fn heures_par_niveau(&self) -> i64 {
+        match self {
+            QualiteRepos::Excellente => 2,
+            QualiteRepos::Normale => 3,
+            QualiteRepos::Basse => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatigueReport {
+    pub avant: NiveauFatigue,
+    pub apres: NiveauFatigue,
+    pub modificateur: i32,
+    pub pv_recuperes: i64,
+    pub pm_recuperes: i64,
+    pub pv: Jauge,
+    pub pm: Jauge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResultatRepos {
+    pub niveau: NiveauFatigue,
+    pub pv: i64,
+    pub pm: i64,
+}
+
+pub fn simuler_repos(niveau: NiveauFatigue, heures: i64, qualite: QualiteRepos) -> ResultatRepos {
+    let heures = heures.max(0);
+    let mut apres = niveau.recuperer(heures / qualite.heures_par_niveau());
+    if heures >= HEURES_NUIT_COMPLETE
+        && qualite != QualiteRepos::Basse
+        && apres == NiveauFatigue::Normal
+    {
+        apres = NiveauFatigue::Repose;
+    }
+
+    ResultatRepos {
+        niveau: apres,
+        pv: (heures as f64 * qualite.pv_par_heure()).floor() as i64,
+        pm: heures * qualite.pm_par_heure(),
+    }
+}
+
+/// Marching hours accumulate in `heures_marche`; each full block costs one level.
+pub fn simuler_marche(
+    niveau: NiveauFatigue,
+    heures_deja: i64,
+    heures: i64,
+) -> (NiveauFatigue, i64) {
+    let total = heures_deja.max(0) + heures.max(0);
+    let niveaux = total / HEURES_MARCHE_PAR_NIVEAU;
+    // A rested character first loses the bonus, then gets tired as usual
+    (niveau.aggraver(niveaux), total % HEURES_MARCHE_PAR_NIVEAU)
+}
+
+pub fn niveau_depuis_fiche(data: &Value) -> NiveauFatigue {
+    NiveauFatigue::from_label(read_str(data, "/status/fatigue/etat"))
+}
+
+/// Rests `heures` hours: updates fatigue, PV and PM on the sheet.
+pub fn appliquer_repos(
+    data: &mut Value,
+    heures: i64,
+    qualite: Option<QualiteRepos>,
+) -> Result<FatigueReport, String> {
+    let avant = niveau_depuis_fiche(data);
+    let qualite = qualite.unwrap_or_else(|| {
+        QualiteRepos::from_label(read_str(data, "/status/fatigue/recuperation"))
+    });
+    let resultat = simuler_repos(avant, heures, qualite);
+
+    let mut pv = read_jauge(data, Pool::Pv)?;
+    let mut pm = read_jauge(data, Pool::Pm)?;
+    let pv_recuperes = if pv.current > 0 {
+        pv.recuperer(resultat.pv).current
+    } else {
+        // Unconscious or dying characters do not heal by resting
+        0
+    };
+    let pm_recuperes = pm.recuperer(resultat.pm).current;
+
+    set_value(
+        data,
+        "/status/fatigue/etat",
+        Value::from(resultat.niveau.label()),
+    );
+    set_value(
+        data,
+        "/status/fatigue/recuperation",
+        Value::from(qualite.label()),
+    );
+    set_value(data, "/status/fatigue/nb_heure", Value::from(heures));
+    set_value(data, "/status/fatigue/heures_marche", Value::from(0));
+    set_value(
+        data,
+        "/vitals/pv",
+        serde_json::to_value(pv).map_err(|e| e.to_string())?,
+    );
+    set_value(
+        data,
+        "/vitals/pm",
+        serde_json::to_value(pm).map_err(|e| e.to_string())?,
+    );
+
+    Ok(FatigueReport {
+        avant,
+        apres: resultat.niveau,
+        modificateur: resultat.niveau.modificateur(),
+        pv_recuperes,
+        pm_recuperes,
+        pv,
+        pm,
+    })
+}
+
+/// Marches `heures` hours: updates the fatigue level and the leftover hours.
+pub fn appliquer_marche(data: &mut Value, heures: i64) -> FatigueReport {
+    let avant = niveau_depuis_fiche(data);
+    let (apres, reste) = simuler_marche(
+        avant,
+        read_i64(data, "/status/fatigue/heures_marche"),
+        heures,
+    );
+
+    set_value(data, "/status/fatigue/etat", Value::from(apres.label()));
+    set_value(data, "/status/fatigue/heures_marche", Value::from(reste));
+
+    FatigueReport {
+        avant,
+        apres,
+        modificateur: apres.modificateur(),
+        pv_recuperes: 0,
+        pm_recuperes: 0,
+        pv: read_jauge(data, Pool::Pv).unwrap_or_default(),
+        pm: read_jauge(data, Pool::Pm).unwrap_or_default(),
+    }
+}
+
+#[tauri::command]
+pub fn rest(
+    id: String,
+    hours: i64,
+    quality: Option<QualiteRepos>,
+    state: State<AppState>,
+) -> Result<FatigueReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let report = appliquer_repos(&mut data, hours, quality)?;
+    let now = store_personnage_data(&tx, &id, &data)?;
+
+    log_personnage_event(
+        &tx,
+        &id,
+        "rest",
+        &serde_json::json!({
+            "hours": hours,
+            "quality": read_str(&data, "/status/fatigue/recuperation"),
+            "avant": report.avant,
+            "apres": report.apres,
+            "pv": report.pv_recuperes,
+            "pm": report.pm_recuperes,
+        }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn march(id: String, hours: i64, state: State<AppState>) -> Result<FatigueReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let report = appliquer_marche(&mut data, hours);
+    let now = store_personnage_data(&tx, &id, &data)?;
+
+    log_personnage_event(
+        &tx,
+        &id,
+        "march",
+        &serde_json::json!({ "hours": hours, "avant": report.avant, "apres": report.apres }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_and_modifiers() {
+        assert_eq!(NiveauFatigue::from_label("Reposé").modificateur(), 1);
+        assert_eq!(NiveauFatigue::from_label("Fatigué").modificateur(), 0);
+        assert_eq!(NiveauFatigue::from_label("Epuisé 3").modificateur(), -3);
+        assert_eq!(NiveauFatigue::from_label("Epuisé 3").malus(), 3);
+        assert_eq!(NiveauFatigue::Epuise(7).label(), "Epuisé 7");
+        assert_eq!(NiveauFatigue::from_label(""), NiveauFatigue::Normal);
+    }
+
+    #[test]
+    fn test_march_accumulates_hours() {
+        let (niveau, reste) = simuler_marche(NiveauFatigue::Normal, 0, 10);
+        assert_eq!(niveau, NiveauFatigue::Epuise(1));
+        assert_eq!(reste, 2);
+
+        let (niveau, _) = simuler_marche(NiveauFatigue::Epuise(9), 0, 40);
+        assert_eq!(niveau, NiveauFatigue::Epuise(EPUISEMENT_MAX));
+    }
+
+    #[test]
+    fn test_rest_recovers_levels_and_points() {
+        let resultat = simuler_repos(NiveauFatigue::Epuise(4), 8, QualiteRepos::Normale);
+        assert_eq!(resultat.niveau, NiveauFatigue::Epuise(2));
+        assert_eq!(resultat.pv, 4);
+        assert_eq!(resultat.pm, 24);
+
+        let resultat = simuler_repos(NiveauFatigue::Fatigue, 8, QualiteRepos::Excellente);
+        assert_eq!(resultat.niveau, NiveauFatigue::Repose);
+    }
+}
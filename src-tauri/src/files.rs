@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+// Files written for the user (exports, backups, the moved database) go to a
+// `.part` file next to their destination first and are renamed into place once
+// complete, so an interrupted write never leaves a truncated file under the
+// real name.
+
+/// `path` with `.part` appended to the whole file name: `Gurdil.codex.part`.
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Has `write` fill the `.part` file of `path`, then renames it to `path`.
+/// A leftover from an interrupted attempt is removed first, and the partial
+/// file is removed again if `write` fails.
+pub fn write_atomic<F>(path: &Path, write: F) -> Result<(), String>
+where
+    F: FnOnce(&Path) -> Result<(), String>,
+{
+    let part = part_path(path);
+    if part.exists() {
+        std::fs::remove_file(&part).map_err(|e| e.to_string())?;
+    }
+    if let Err(e) = write(&part) {
+        let _ = std::fs::remove_file(&part);
+        return Err(e);
+    }
+    std::fs::rename(&part, path).map_err(|e| e.to_string())
+}
+
+/// A fresh empty directory under the system temp dir, for tests touching files.
+#[cfg(test)]
+pub fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("codex-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = test_dir();
+        let path = dir.join("Gurdil.codex");
+        assert_eq!(part_path(&path), dir.join("Gurdil.codex.part"));
+
+        write_atomic(&path, |part| {
+            std::fs::write(part, "{}").map_err(|e| e.to_string())
+        })
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+        // A failed write keeps the previous file and leaves no partial one
+        let result = write_atomic(&path, |part| {
+            std::fs::write(part, "{").unwrap();
+            Err("disque plein".to_string())
+        });
+        assert_eq!(result, Err("disque plein".to_string()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        assert!(!part_path(&path).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,627 @@
+use crate::companions::{lire_compagnons, TypeCompagnon};
+use crate::db::{
+    load_personnage_data, load_ref_items, log_personnage_event, store_personnage_data, AppState,
+    RefEquipement,
+};
+use crate::sheet::{read_i64, read_str};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Items carried by a companion live in its `inventaire` array, with the same
+// shape as the character inventory. Bags (Sacs, limited in grams) and pouches
+// (Sacoches, limited in places) own their items in a `contenu` array, and may
+// themselves sit in another container.
+
+const COMPAGNONS: [TypeCompagnon; 3] = [
+    TypeCompagnon::Monture,
+    TypeCompagnon::Familier,
+    TypeCompagnon::Invocation,
+];
+
+/// Where an item is stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Emplacement {
+    /// `data.inventory`: what the character carries
+    Personnage,
+    /// `inventaire` of a mount, familiar or invocation
+    Compagnon { kind: TypeCompagnon, uid: String },
+    /// `contenu` of a bag or pouch, wherever it is
+    Conteneur { uid: String },
+}
+
+/// Limits of a container. An item-level `capacite`/`places` overrides the reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capacite {
+    /// Grams
+    pub poids: Option<f64>,
+    pub places: Option<i64>,
+}
+
+/// Unit weight in grams, following `getItemWeight` on the frontend.
+pub fn poids_unitaire(item: &Value, refs: &[RefEquipement]) -> f64 {
+    if let Some(poids) = item.get("poids") {
+        // Custom items carry their own weight
+        return read_f64(poids);
+    }
+    match reference(item, refs) {
+        Some(r) if r.category == "Boissons" => {
+            if r.nom == "Outre d'abondance (enchantée)" {
+                12.5
+            } else {
+                250.0
+            }
+        }
+        Some(r) => r.details.get("poids").map(read_f64).unwrap_or(0.0),
+        None => 0.0,
+    }
+}
+
+pub(crate) fn reference<'a>(item: &Value, refs: &'a [RefEquipement]) -> Option<&'a RefEquipement> {
+    let ref_id = read_i64(item, "/refId");
+    refs.iter().find(|r| r.id == ref_id)
+}
+
+fn read_f64(value: &Value) -> f64 {
+    match value {
+        Value::String(s) => s.trim().replace(',', ".").parse().unwrap_or(0.0),
+        v => v.as_f64().unwrap_or(0.0),
+    }
+}
+
+pub fn quantite_objet(item: &Value) -> i64 {
+    item.get("quantite")
+        .map(|_| read_i64(item, "/quantite"))
+        .unwrap_or(1)
+}
+
+fn contenu(item: &Value) -> &[Value] {
+    item.get("contenu")
+        .and_then(|c| c.as_array())
+        .map(|c| c.as_slice())
+        .unwrap_or_default()
+}
+
+/// `None` when the item is not a container.
+pub fn capacite(item: &Value, refs: &[RefEquipement]) -> Option<Capacite> {
+    let positif = |v: &Value| Some(read_f64(v)).filter(|n| *n > 0.0);
+    let reference = reference(item, refs);
+    let details = reference.map(|r| &r.details);
+
+    let poids = item
+        .get("capacite")
+        .and_then(positif)
+        .or_else(|| details.and_then(|d| d.get("capacite")).and_then(positif));
+    let places = item
+        .get("places")
+        .and_then(positif)
+        .or_else(|| details.and_then(|d| d.get("places")).and_then(positif))
+        .map(|p| p as i64);
+
+    let conteneur = poids.is_some()
+        || places.is_some()
+        || item.get("contenu").is_some()
+        || matches!(
+            reference.map(|r| r.category.as_str()),
+            Some("Sacs" | "Sacoches")
+        );
+    conteneur.then_some(Capacite { poids, places })
+}
+
+/// Weight of a stack, contents included.
+pub fn poids_objet(item: &Value, refs: &[RefEquipement]) -> f64 {
+    poids_unitaire(item, refs) * quantite_objet(item) as f64 + poids_total(contenu(item), refs)
+}
+
+pub fn poids_total(items: &[Value], refs: &[RefEquipement]) -> f64 {
+    items.iter().map(|item| poids_objet(item, refs)).sum()
+}
+
+/// Places used in a pouch: one per unit, whatever its size.
+pub fn places_occupees(items: &[Value]) -> i64 {
+    items.iter().map(quantite_objet).sum()
+}
+
+fn racines(data: &Value) -> Vec<(Emplacement, String)> {
+    let mut racines = vec![(Emplacement::Personnage, "/inventory".to_string())];
+    for kind in COMPAGNONS {
+        for (index, compagnon) in lire_compagnons(data, kind)
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+        {
+            racines.push((
+                Emplacement::Compagnon {
+                    kind,
+                    uid: compagnon.uid,
+                },
+                format!("/{}/{}/inventaire", kind.key(), index),
+            ));
+        }
+    }
+    racines
+}
+
+/// An item found in the sheet: pointers to it and to the containers it sits
+/// in, innermost first, and who carries it.
+struct Localisation {
+    chemin: Vec<String>,
+    racine: Emplacement,
+}
+
+fn chercher(items: &[Value], pointer: &str, uid: &str) -> Option<Vec<String>> {
+    items.iter().enumerate().find_map(|(index, item)| {
+        let item_pointer = format!("{}/{}", pointer, index);
+        if read_str(item, "/uid") == uid {
+            Some(vec![item_pointer])
+        } else {
+            let mut chemin = chercher(contenu(item), &format!("{}/contenu", item_pointer), uid)?;
+            chemin.push(item_pointer);
+            Some(chemin)
+        }
+    })
+}
+
+fn localiser(data: &Value, uid: &str) -> Option<Localisation> {
+    racines(data).into_iter().find_map(|(racine, pointer)| {
+        let items = data.pointer(&pointer).and_then(|v| v.as_array())?;
+        chercher(items, &pointer, uid).map(|chemin| Localisation { chemin, racine })
+    })
+}
+
+/// Pointer to the item `uid`, wherever it is stored.
+pub(crate) fn pointeur_objet(data: &Value, uid: &str) -> Option<String> {
+    localiser(data, uid).map(|mut l| l.chemin.swap_remove(0))
+}
+
+fn lister(items: &[Value], pointer: &str, pointeurs: &mut Vec<String>) {
+    for (index, item) in items.iter().enumerate() {
+        let item_pointer = format!("{}/{}", pointer, index);
+        lister(
+            contenu(item),
+            &format!("{}/contenu", item_pointer),
+            pointeurs,
+        );
+        pointeurs.push(item_pointer);
+    }
+}
+
+/// Pointers to every stored item, contents before their container.
+pub(crate) fn pointeurs_objets(data: &Value) -> Vec<String> {
+    let mut pointeurs = Vec::new();
+    for (_, pointer) in racines(data) {
+        if let Some(items) = data.pointer(&pointer).and_then(|v| v.as_array()) {
+            lister(items, &pointer, &mut pointeurs);
+        }
+    }
+    pointeurs
+}
+
+/// Pointer to the object holding the item array, and the array field.
+fn conteneur(data: &Value, emplacement: &Emplacement) -> Result<(String, &'static str), String> {
+    match emplacement {
+        Emplacement::Personnage => Ok((String::new(), "inventory")),
+        Emplacement::Compagnon { kind, uid } => {
+            let index = lire_compagnons(data, *kind)?
+                .iter()
+                .position(|c| &c.uid == uid)
+                .ok_or_else(|| format!("Compagnon introuvable: {}", uid))?;
+            Ok((format!("/{}/{}", kind.key(), index), "inventaire"))
+        }
+        Emplacement::Conteneur { uid } => pointeur_objet(data, uid)
+            .map(|pointer| (pointer, "contenu"))
+            .ok_or_else(|| format!("Conteneur introuvable: {}", uid)),
+    }
+}
+
+pub fn objets(data: &Value, emplacement: &Emplacement) -> Result<Vec<Value>, String> {
+    let (pointer, champ) = conteneur(data, emplacement)?;
+    Ok(data
+        .pointer(&format!("{}/{}", pointer, champ))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default())
+}
+
+fn ecrire_objets(
+    data: &mut Value,
+    emplacement: &Emplacement,
+    items: Vec<Value>,
+) -> Result<(), String> {
+    let (pointer, champ) = conteneur(data, emplacement)?;
+    let parent = data
+        .pointer_mut(&pointer)
+        .and_then(|v| v.as_object_mut())
+        .ok_or_else(|| "Fiche de personnage invalide".to_string())?;
+    parent.insert(champ.to_string(), Value::Array(items));
+    Ok(())
+}
+
+/// Maximum load in grams of a companion, `None` when unlimited or unknown.
+fn charge_max(data: &Value, kind: TypeCompagnon, uid: &str) -> Result<Option<f64>, String> {
+    Ok(lire_compagnons(data, kind)?
+        .into_iter()
+        .find(|c| c.uid == uid)
+        .and_then(|c| c.charge_max_grammes())
+        .map(|g| g as f64))
+}
+
+/// Checks the limits of `emplacement` and of whoever carries it, once `ajout` is in.
+fn verifier_limites(
+    data: &Value,
+    refs: &[RefEquipement],
+    emplacement: &Emplacement,
+    ajout: &Value,
+) -> Result<(), String> {
+    let poids_ajout = poids_objet(ajout, refs);
+
+    let racine = match emplacement {
+        Emplacement::Conteneur { uid } => {
+            let localisation =
+                localiser(data, uid).ok_or_else(|| format!("Conteneur introuvable: {}", uid))?;
+            for (niveau, pointer) in localisation.chemin.iter().enumerate() {
+                let sac = data.pointer(pointer).cloned().unwrap_or_default();
+                let limites = capacite(&sac, refs)
+                    .ok_or_else(|| "Cet objet n'est pas un conteneur".to_string())?;
+
+                // Enclosing containers only feel the extra weight
+                if let Some(max) = limites.poids {
+                    let poids = poids_total(contenu(&sac), refs) + poids_ajout;
+                    if poids > max {
+                        return Err(format!("Sac plein: {} g sur {} g", poids, max));
+                    }
+                }
+                if let (0, Some(max)) = (niveau, limites.places) {
+                    let places = places_occupees(contenu(&sac)) + quantite_objet(ajout);
+                    if places > max {
+                        return Err(format!("Sacoche pleine: {} places sur {}", places, max));
+                    }
+                }
+            }
+            localisation.racine
+        }
+        racine => racine.clone(),
+    };
+
+    if let Emplacement::Compagnon { kind, uid } = &racine {
+        if let Some(max) = charge_max(data, *kind, uid)? {
+            let charge = poids_total(&objets(data, &racine)?, refs) + poids_ajout;
+            if charge > max {
+                return Err(format!(
+                    "Charge maximale dépassée: {} g sur {} g",
+                    charge, max
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves `quantite` units (the whole stack by default) of the item `item_uid`.
+/// A partial move splits the stack; the moved part gets a new uid. The sheet is
+/// left untouched when the move is rejected.
+pub fn deplacer_objet(
+    data: &mut Value,
+    refs: &[RefEquipement],
+    item_uid: &str,
+    from: &Emplacement,
+    to: &Emplacement,
+    quantite_demandee: Option<i64>,
+) -> Result<Value, String> {
+    if from == to {
+        return Err("L'objet est déjà à cet endroit".to_string());
+    }
+
+    let mut copie = data.clone();
+    let mut source = objets(&copie, from)?;
+    let index = source
+        .iter()
+        .position(|i| read_str(i, "/uid") == item_uid)
+        .ok_or_else(|| format!("Objet introuvable: {}", item_uid))?;
+    let disponible = quantite_objet(&source[index]);
+    let nombre = quantite_demandee.unwrap_or(disponible);
+    if nombre <= 0 || nombre > disponible {
+        return Err(format!(
+            "Quantité invalide: {} demandés, {} disponibles",
+            nombre, disponible
+        ));
+    }
+
+    if let Emplacement::Conteneur { uid } = to {
+        let item = &source[index];
+        if read_str(item, "/uid") == uid || chercher(contenu(item), "", uid).is_some() {
+            return Err("Un conteneur ne peut pas être rangé dans lui-même".to_string());
+        }
+    }
+
+    let mut deplace = source[index].clone();
+    if nombre == disponible {
+        source.remove(index);
+    } else {
+        source[index]["quantite"] = Value::from(disponible - nombre);
+        deplace["uid"] = Value::from(uuid::Uuid::new_v4().to_string());
+        deplace["quantite"] = Value::from(nombre);
+    }
+    // Write the source first: the destination may be nested in it, or contain it
+    ecrire_objets(&mut copie, from, source)?;
+
+    verifier_limites(&copie, refs, to, &deplace)?;
+    let mut destination = objets(&copie, to)?;
+    destination.push(deplace.clone());
+    ecrire_objets(&mut copie, to, destination)?;
+
+    *data = copie;
+    Ok(deplace)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChargeCompagnon {
+    pub kind: TypeCompagnon,
+    pub uid: String,
+    pub nom: String,
+    pub charge: f64,
+    pub charge_max: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Charges {
+    /// What the character carries: inventory and custom bag items
+    pub personnage: f64,
+    pub compagnons: Vec<ChargeCompagnon>,
+}
+
+pub fn charges(data: &Value, refs: &[RefEquipement]) -> Charges {
+    let inventaire = objets(data, &Emplacement::Personnage).unwrap_or_default();
+    let custom: Vec<Value> = data
+        .get("custom_sac_items")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut compagnons = Vec::new();
+    for kind in COMPAGNONS {
+        for compagnon in lire_compagnons(data, kind).unwrap_or_default() {
+            let emplacement = Emplacement::Compagnon {
+                kind,
+                uid: compagnon.uid.clone(),
+            };
+            let items = objets(data, &emplacement).unwrap_or_default();
+            compagnons.push(ChargeCompagnon {
+                kind,
+                charge: poids_total(&items, refs),
+                charge_max: compagnon.charge_max_grammes().map(|g| g as f64),
+                uid: compagnon.uid,
+                nom: compagnon.nom,
+            });
+        }
+    }
+
+    Charges {
+        personnage: poids_total(&inventaire, refs) + poids_total(&custom, refs),
+        compagnons,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeConteneur {
+    pub uid: String,
+    pub nom: String,
+    /// Who carries it, directly or through other containers
+    pub porte_par: Emplacement,
+    /// Container it sits in, if any
+    pub parent: Option<String>,
+    pub capacite: Capacite,
+    pub poids_contenu: f64,
+    pub poids_libre: Option<f64>,
+    pub places_occupees: i64,
+    pub places_libres: Option<i64>,
+    pub objets: usize,
+}
+
+fn resumer(
+    items: &[Value],
+    refs: &[RefEquipement],
+    porte_par: &Emplacement,
+    parent: Option<&str>,
+    resumes: &mut Vec<ResumeConteneur>,
+) {
+    for item in items {
+        let Some(limites) = capacite(item, refs) else {
+            continue;
+        };
+        let uid = read_str(item, "/uid");
+        let nom = reference(item, refs)
+            .map(|r| r.nom.clone())
+            .unwrap_or_else(|| read_str(item, "/nom").to_string());
+        let poids_contenu = poids_total(contenu(item), refs);
+        let places = places_occupees(contenu(item));
+
+        resumes.push(ResumeConteneur {
+            uid: uid.to_string(),
+            nom,
+            porte_par: porte_par.clone(),
+            parent: parent.map(String::from),
+            capacite: limites,
+            poids_contenu,
+            poids_libre: limites.poids.map(|max| (max - poids_contenu).max(0.0)),
+            places_occupees: places,
+            places_libres: limites.places.map(|max| (max - places).max(0)),
+            objets: contenu(item).len(),
+        });
+        resumer(contenu(item), refs, porte_par, Some(uid), resumes);
+    }
+}
+
+pub fn resume_conteneurs(data: &Value, refs: &[RefEquipement]) -> Vec<ResumeConteneur> {
+    let mut resumes = Vec::new();
+    for (racine, pointer) in racines(data) {
+        if let Some(items) = data.pointer(&pointer).and_then(|v| v.as_array()) {
+            resumer(items, refs, &racine, None, &mut resumes);
+        }
+    }
+    resumes
+}
+
+#[tauri::command]
+pub fn move_item(
+    id: String,
+    item_uid: String,
+    from: Emplacement,
+    to: Emplacement,
+    quantite: Option<i64>,
+    state: State<AppState>,
+) -> Result<Value, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let refs = load_ref_items(&db)?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let deplace = deplacer_objet(&mut data, &refs, &item_uid, &from, &to, quantite)?;
+    let now = store_personnage_data(&tx, &id, &data)?;
+    log_personnage_event(
+        &tx,
+        &id,
+        "move_item",
+        &serde_json::json!({
+            "item_uid": item_uid,
+            "from": from,
+            "to": to,
+            "quantite": quantite_objet(&deplace),
+        }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(deplace)
+}
+
+#[tauri::command]
+pub fn get_inventory_loads(id: String, state: State<AppState>) -> Result<Charges, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    let refs = load_ref_items(&db)?;
+    Ok(charges(&data, &refs))
+}
+
+/// Every bag and pouch of the character and companions, with free capacity.
+#[tauri::command]
+pub fn container_summary(
+    id: String,
+    state: State<AppState>,
+) -> Result<Vec<ResumeConteneur>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    let refs = load_ref_items(&db)?;
+    Ok(resume_conteneurs(&data, &refs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fiche() -> Value {
+        serde_json::json!({
+            "inventory": [
+                { "uid": "corde", "nom": "Corde", "poids": 2000, "quantite": 1 },
+                { "uid": "rations", "nom": "Rations", "poids": 500, "quantite": 6 },
+                { "uid": "sac", "nom": "Sac à dos", "poids": 1000, "capacite": "3000", "contenu": [] },
+                { "uid": "bourse", "nom": "Bourse", "poids": 100, "places": 2 }
+            ],
+            "mounts": [
+                { "uid": "c1", "nom": "Mule", "charge_max": "4 kg" }
+            ]
+        })
+    }
+
+    fn mule() -> Emplacement {
+        Emplacement::Compagnon {
+            kind: TypeCompagnon::Monture,
+            uid: "c1".to_string(),
+        }
+    }
+
+    fn dans(uid: &str) -> Emplacement {
+        Emplacement::Conteneur {
+            uid: uid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_move_part_of_a_stack_to_a_mount() {
+        let mut data = fiche();
+        deplacer_objet(
+            &mut data,
+            &[],
+            "rations",
+            &Emplacement::Personnage,
+            &mule(),
+            Some(4),
+        )
+        .unwrap();
+
+        let loads = charges(&data, &[]);
+        assert_eq!(loads.personnage, 2000.0 + 1000.0 + 1000.0 + 100.0);
+        assert_eq!(loads.compagnons[0].charge, 2000.0);
+        assert_eq!(read_i64(&data, "/inventory/1/quantite"), 2);
+    }
+
+    #[test]
+    fn test_mount_load_is_checked() {
+        let mut data = fiche();
+        deplacer_objet(
+            &mut data,
+            &[],
+            "corde",
+            &Emplacement::Personnage,
+            &mule(),
+            None,
+        )
+        .unwrap();
+        let trop = deplacer_objet(
+            &mut data,
+            &[],
+            "rations",
+            &Emplacement::Personnage,
+            &mule(),
+            None,
+        );
+        assert!(trop.is_err());
+        // Nothing moved on failure
+        assert_eq!(read_i64(&data, "/inventory/0/quantite"), 6);
+    }
+
+    #[test]
+    fn test_nested_containers_enforce_their_limits() {
+        let mut data = fiche();
+        let perso = Emplacement::Personnage;
+        // The pouch (100 g) goes in the bag, then rations go in the pouch
+        deplacer_objet(&mut data, &[], "bourse", &perso, &dans("sac"), None).unwrap();
+        deplacer_objet(&mut data, &[], "rations", &perso, &dans("bourse"), Some(2)).unwrap();
+        // Only two places in the pouch
+        assert!(
+            deplacer_objet(&mut data, &[], "rations", &perso, &dans("bourse"), Some(1)).is_err()
+        );
+        // 100 + 1000 + 2000 > 3000 g
+        assert!(deplacer_objet(&mut data, &[], "corde", &perso, &dans("sac"), None).is_err());
+        assert!(deplacer_objet(&mut data, &[], "sac", &perso, &dans("bourse"), None).is_err());
+        // A roomier pouch, but the bag around it is full
+        data["inventory"][2]["contenu"][0]["places"] = Value::from(5);
+        assert!(deplacer_objet(&mut data, &[], "corde", &perso, &dans("bourse"), None).is_err());
+
+        let resume = resume_conteneurs(&data, &[]);
+        let sac = resume.iter().find(|c| c.uid == "sac").unwrap();
+        assert_eq!(sac.poids_contenu, 1100.0);
+        assert_eq!(sac.poids_libre, Some(1900.0));
+        let bourse = resume.iter().find(|c| c.uid == "bourse").unwrap();
+        assert_eq!(bourse.parent.as_deref(), Some("sac"));
+        assert_eq!(bourse.places_libres, Some(3));
+
+        // Back out of the nested pouch into the inventory
+        let rations = read_str(&data, "/inventory/2/contenu/0/contenu/0/uid").to_string();
+        deplacer_objet(&mut data, &[], &rations, &dans("bourse"), &perso, None).unwrap();
+        assert_eq!(
+            charges(&data, &[]).personnage,
+            2000.0 + 3000.0 + 1000.0 + 100.0
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use crate::db::{load_personnage_data, AppState};
+use crate::versions::{insert_version, prune_versions};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Undo/redo journal. Every write of a sheet records the JSON Patch (RFC 6902
+// add/remove/replace subset) from the previous sheet, and the patch going back.
+// Undone entries stay in the table as the redo stack until a new change comes
+// in. Once the journal grows past `JOURNAL_MAX`, its oldest entries are folded
+// into a version snapshot of the sheet as it was after them.
+
+const JOURNAL_MAX: i64 = 200;
+const JOURNAL_KEEP: i64 = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+fn diff_into(old: &Value, new: &Value, path: &str, ops: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let child = format!("{}/{}", path, escape(key));
+                match b.get(key) {
+                    Some(other) => diff_into(value, other, &child, ops),
+                    None => ops.push(PatchOp::Remove { path: child }),
+                }
+            }
+            for (key, value) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                ops.push(PatchOp::Add {
+                    path: format!("{}/{}", path, escape(key)),
+                    value: value.clone(),
+                });
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let common = a.len().min(b.len());
+            for index in 0..common {
+                diff_into(&a[index], &b[index], &format!("{}/{}", path, index), ops);
+            }
+            // From the end, so each index is still valid when applied
+            for index in (common..a.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: format!("{}/{}", path, index),
+                });
+            }
+            for (index, value) in b.iter().enumerate().skip(common) {
+                ops.push(PatchOp::Add {
+                    path: format!("{}/{}", path, index),
+                    value: value.clone(),
+                });
+            }
+        }
+        _ if old != new => ops.push(PatchOp::Replace {
+            path: path.to_string(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Patch turning `old` into `new`.
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+    diff_into(old, new, "", &mut ops);
+    ops
+}
+
+fn apply_op(data: &mut Value, op: &PatchOp) -> Result<(), String> {
+    let path = match op {
+        PatchOp::Add { path, .. } | PatchOp::Remove { path } | PatchOp::Replace { path, .. } => {
+            path
+        }
+    };
+    let erreur = || format!("Le journal ne correspond plus à la fiche ({})", path);
+
+    let Some((parent, token)) = path.rsplit_once('/') else {
+        // Whole document
+        return match op {
+            PatchOp::Add { value, .. } | PatchOp::Replace { value, .. } => {
+                *data = value.clone();
+                Ok(())
+            }
+            PatchOp::Remove { .. } => Err(erreur()),
+        };
+    };
+    let key = unescape(token);
+    match data.pointer_mut(parent).ok_or_else(erreur)? {
+        Value::Object(map) => match op {
+            PatchOp::Add { value, .. } => {
+                map.insert(key, value.clone());
+            }
+            PatchOp::Replace { value, .. } => {
+                *map.get_mut(&key).ok_or_else(erreur)? = value.clone();
+            }
+            PatchOp::Remove { .. } => {
+                map.remove(&key).ok_or_else(erreur)?;
+            }
+        },
+        Value::Array(items) => {
+            let index = if key == "-" {
+                items.len()
+            } else {
+                key.parse::<usize>().map_err(|_| erreur())?
+            };
+            match op {
+                PatchOp::Add { value, .. } if index <= items.len() => {
+                    items.insert(index, value.clone())
+                }
+                PatchOp::Replace { value, .. } if index < items.len() => {
+                    items[index] = value.clone()
+                }
+                PatchOp::Remove { .. } if index < items.len() => {
+                    items.remove(index);
+                }
+                _ => return Err(erreur()),
+            }
+        }
+        _ => return Err(erreur()),
+    }
+    Ok(())
+}
+
+/// Applies a whole patch, or nothing if one operation does not fit.
+pub fn apply(data: &mut Value, ops: &[PatchOp]) -> Result<(), String> {
+    let mut patched = data.clone();
+    for op in ops {
+        apply_op(&mut patched, op)?;
+    }
+    *data = patched;
+    Ok(())
+}
+
+/// Journals the change from `old` to `new`. Drops the redo stack.
+pub fn record_change(conn: &Connection, id: &str, old: &Value, new: &Value) -> Result<(), String> {
+    let patch = diff(old, new);
+    if patch.is_empty() {
+        return Ok(());
+    }
+    let inverse = diff(new, old);
+
+    conn.execute(
+        "DELETE FROM personnages_journal WHERE personnage_id = ?1 AND undone = 1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO personnages_journal (personnage_id, patch, inverse, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            id,
+            serde_json::to_string(&patch).map_err(|e| e.to_string())?,
+            serde_json::to_string(&inverse).map_err(|e| e.to_string())?,
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    squash(conn, id, new, JOURNAL_MAX, JOURNAL_KEEP)
+}
+
+/// Drops undo history, e.g. when the sheet is replaced by a synced copy.
+pub fn clear(conn: &Connection, id: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM personnages_journal WHERE personnage_id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+struct Entry {
+    entry_id: i64,
+    patch: Vec<PatchOp>,
+    inverse: Vec<PatchOp>,
+    created_at: String,
+}
+
+fn entries(conn: &Connection, sql: &str, id: &str) -> Result<Vec<Entry>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (entry_id, patch, inverse, created_at) = row.map_err(|e| e.to_string())?;
+        entries.push(Entry {
+            entry_id,
+            patch: serde_json::from_str(&patch).map_err(|e| e.to_string())?,
+            inverse: serde_json::from_str(&inverse).map_err(|e| e.to_string())?,
+            created_at,
+        });
+    }
+    Ok(entries)
+}
+
+/// Once more than `max` entries can be undone, keeps the `keep` latest and
+/// stores the sheet as it was before them as a version.
+fn squash(conn: &Connection, id: &str, current: &Value, max: i64, keep: i64) -> Result<(), String> {
+    // Counted first: this runs on every save and rarely has anything to fold
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM personnages_journal WHERE personnage_id = ?1 AND undone = 0",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if count <= max {
+        return Ok(());
+    }
+
+    let active = entries(
+        conn,
+        "SELECT entry_id, patch, inverse, created_at FROM personnages_journal
+         WHERE personnage_id = ?1 AND undone = 0 ORDER BY entry_id DESC",
+        id,
+    )?;
+    let (kept, folded) = active.split_at(keep.max(0) as usize);
+    let mut data = current.clone();
+    for entry in kept {
+        apply(&mut data, &entry.inverse)?;
+    }
+    let last_folded = &folded[0];
+    insert_version(
+        conn,
+        id,
+        &data,
+        &last_folded.created_at,
+        Some("Journal compacté"),
+        Some("journal"),
+    )?;
+    conn.execute(
+        "DELETE FROM personnages_journal WHERE personnage_id = ?1 AND entry_id <= ?2",
+        params![id, last_folded.entry_id],
+    )
+    .map_err(|e| e.to_string())?;
+    prune_versions(conn, id)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalState {
+    pub can_undo: i64,
+    pub can_redo: i64,
+}
+
+fn journal_state(conn: &Connection, id: &str) -> Result<JournalState, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(undone = 0), 0), COALESCE(SUM(undone = 1), 0)
+         FROM personnages_journal WHERE personnage_id = ?1",
+        [id],
+        |row| {
+            Ok(JournalState {
+                can_undo: row.get(0)?,
+                can_redo: row.get(1)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoResult {
+    pub data: Value,
+    pub state: JournalState,
+}
+
+/// Shared by undo and redo: picks the entry, applies one of its patches and
+/// flips its flag, without journaling the write itself.
+fn step(conn: &Connection, id: &str, undo: bool) -> Result<UndoResult, String> {
+    let sql = if undo {
+        "SELECT entry_id, patch, inverse, created_at FROM personnages_journal
+         WHERE personnage_id = ?1 AND undone = 0 ORDER BY entry_id DESC LIMIT 1"
+    } else {
+        "SELECT entry_id, patch, inverse, created_at FROM personnages_journal
+         WHERE personnage_id = ?1 AND undone = 1 ORDER BY entry_id ASC LIMIT 1"
+    };
+    let entry = entries(conn, sql, id)?.pop().ok_or_else(|| {
+        if undo {
+            "Rien à annuler".to_string()
+        } else {
+            "Rien à rétablir".to_string()
+        }
+    })?;
+
+    let mut data = load_personnage_data(conn, id)?;
+    apply(&mut data, if undo { &entry.inverse } else { &entry.patch })?;
+    // The money columns replay the ledger, which the patch does not touch
+    let note = if undo {
+        "Annulation"
+    } else {
+        "Rétablissement"
+    };
+    crate::richesse::reconcile_after_journal_step(conn, id, &data, note)?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE personnages SET data = ?1, updated_at = ?2, version_label = NULL WHERE id = ?3",
+        params![data.to_string(), now, id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE personnages_journal SET undone = ?1 WHERE entry_id = ?2",
+        params![undo, entry.entry_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(UndoResult {
+        data,
+        state: journal_state(conn, id)?,
+    })
+}
+
+#[tauri::command]
+pub fn get_journal_state(id: String, state: State<AppState>) -> Result<JournalState, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    journal_state(&db, &id)
+}
+
+/// Reverts the last change of the sheet and returns the sheet.
+#[tauri::command]
+pub fn undo_personnage_edit(id: String, state: State<AppState>) -> Result<UndoResult, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let result = step(&tx, &id, true)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn redo_personnage_edit(id: String, state: State<AppState>) -> Result<UndoResult, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let result = step(&tx, &id, false)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Returns the sheet last stored for `id`, if any, for callers about to overwrite it.
+pub fn previous_data(conn: &Connection, id: &str) -> Result<Option<Value>, String> {
+    let data: Option<String> = conn
+        .query_row("SELECT data FROM personnages WHERE id = ?1", [id], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::test_db_with as db_with;
+
+    fn save(conn: &Connection, new: &Value) {
+        crate::db::store_personnage_data(conn, "p1", new).unwrap();
+    }
+
+    #[test]
+    fn test_patch_round_trip() {
+        let old = serde_json::json!({
+            "vitals": { "pv": 20 },
+            "inventory": [{ "uid": "a" }, { "uid": "b" }, { "uid": "c" }],
+            "notes": "x"
+        });
+        let new = serde_json::json!({
+            "vitals": { "pv": 12, "pm": 3 },
+            "inventory": [{ "uid": "a", "quantite": 2 }]
+        });
+
+        let mut data = old.clone();
+        apply(&mut data, &diff(&old, &new)).unwrap();
+        assert_eq!(data, new);
+        apply(&mut data, &diff(&new, &old)).unwrap();
+        assert_eq!(data, old);
+
+        // A patch that does not fit leaves the sheet untouched
+        let mut other = serde_json::json!({ "vitals": 3 });
+        assert!(apply(&mut other, &diff(&old, &new)).is_err());
+        assert_eq!(other, serde_json::json!({ "vitals": 3 }));
+    }
+
+    #[test]
+    fn test_undo_redo_and_squash() {
+        let v0 = serde_json::json!({ "vitals": { "pv": 20 } });
+        let v1 = serde_json::json!({ "vitals": { "pv": 15 } });
+        let v2 = serde_json::json!({ "vitals": { "pv": 10 } });
+        let conn = db_with(&v0);
+        save(&conn, &v1);
+        save(&conn, &v2);
+
+        assert_eq!(step(&conn, "p1", true).unwrap().data, v1);
+        let undone = step(&conn, "p1", true).unwrap();
+        assert_eq!(undone.data, v0);
+        assert_eq!((undone.state.can_undo, undone.state.can_redo), (0, 2));
+        assert_eq!(step(&conn, "p1", false).unwrap().data, v1);
+
+        // A new change drops what could be redone
+        save(&conn, &serde_json::json!({ "vitals": { "pv": 1 } }));
+        assert!(step(&conn, "p1", false).is_err());
+
+        // Folding all but the latest entry stores the sheet as it was before it
+        let current = previous_data(&conn, "p1").unwrap().unwrap();
+        squash(&conn, "p1", &current, 1, 1).unwrap();
+        let state = journal_state(&conn, "p1").unwrap();
+        assert_eq!(state.can_undo, 1);
+        let folded: String = conn
+            .query_row(
+                "SELECT data FROM personnages_versions WHERE client = 'journal'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&folded).unwrap(), v1);
+    }
+
+    #[test]
+    fn test_undone_transaction_is_compensated_in_the_ledger() {
+        use crate::richesse::{load_entries, rebuild_balance, record_transaction};
+        use crate::richesse::{LedgerKind, NewLedgerEntry};
+
+        let conn = db_with(&serde_json::json!({
+            "richesse": { "monnaies": { "or": { "sur_soi": 10 } } }
+        }));
+        let achat = NewLedgerEntry {
+            kind: LedgerKind::Achat,
+            amount: 4,
+            currency: "or".to_string(),
+            location: "sur_soi".to_string(),
+            destination: None,
+            note: String::new(),
+        };
+        record_transaction(&conn, "p1", achat).unwrap();
+        let balance = || {
+            rebuild_balance(&load_entries(&conn, "p1").unwrap())
+                .or
+                .sur_soi
+        };
+        assert_eq!(balance(), 6);
+
+        let undone = step(&conn, "p1", true).unwrap();
+        assert_eq!(undone.data["richesse"]["monnaies"]["or"]["sur_soi"], 10);
+        assert_eq!(balance(), 10);
+        step(&conn, "p1", false).unwrap();
+        assert_eq!(balance(), 6);
+
+        let notes: Vec<String> = load_entries(&conn, "p1")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.note)
+            .collect();
+        assert_eq!(notes[2..], ["Annulation", "Rétablissement"]);
+    }
+}
@@ -1,10 +1,38 @@
+mod alcohol;
+mod backup;
+mod calendar;
+mod codex_file;
+mod combat;
 mod commands;
+mod companions;
+mod consumables;
 mod db;
+mod dice;
+mod environment;
+mod equipement;
+mod fatigue;
+mod files;
+mod inventory;
+mod journal;
 mod logic;
+mod migrations;
+mod modifiers;
+mod movement;
+mod pdf;
+mod progression;
+mod richesse;
 mod seeds;
+mod senses;
+mod sheet;
+mod summary;
 mod sync;
+mod text_export;
+mod trash;
+mod versions;
+mod vitals;
 
 use db::AppState;
+use std::path::Path;
 use std::sync::Mutex;
 use tauri::Manager;
 
@@ -17,7 +45,25 @@ fn greet(name: &str) -> String {
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
-            let mut conn = db::init_db().expect("failed to initialize sqlite");
+            let app_data_dir = app.path().app_data_dir()?;
+            let db_path = db::resolve_db_path(&app_data_dir);
+            // Opening a fresh database instead would hide the legacy one for good
+            let moved = db::migrate_legacy_db(Path::new(db::LEGACY_DB_PATH), &db_path)
+                .map_err(|e| format!("Failed to migrate legacy database: {}", e))?;
+            if moved {
+                eprintln!("Database moved to {:?}", db_path);
+            }
+            let mut conn = db::open_db(&db_path).expect("failed to initialize sqlite");
+
+            // Before the migrations, so that the backup holds the database as
+            // the previous version of the app left it
+            let version = app.package_info().version.to_string();
+            let backups = app_data_dir.join(backup::BACKUPS_DIR);
+            if let Err(e) = backup::auto_backup(&conn, &backups, &version) {
+                eprintln!("Failed to back up database: {}", e);
+            }
+            migrations::run_migrations(&mut conn)
+                .map_err(|e| format!("Failed to migrate database: {}", e))?;
 
             // Run seeds
             if let Err(e) = seeds::seed_reference_data(&mut conn, app.handle().clone()) {
@@ -44,6 +90,8 @@ pub fn run() {
             commands::get_local_items_count,
             commands::get_ref_items,
             commands::compute_stats,
+            commands::get_personnage_etats,
+            commands::compute_magic_stats,
             commands::get_all_personnages,
             commands::get_personnage,
             commands::create_personnage,
@@ -52,11 +100,78 @@ pub fn run() {
             commands::save_personnage_local,
             commands::get_personnage_versions,
             commands::restore_personnage_version,
+            versions::get_version_retention,
+            versions::set_version_retention,
+            versions::pin_personnage_version,
+            versions::label_personnage_version,
+            versions::diff_personnage_versions,
+            journal::get_journal_state,
+            journal::undo_personnage_edit,
+            journal::redo_personnage_edit,
+            trash::list_deleted_personnages,
+            trash::restore_deleted_personnage,
+            trash::purge_trash,
+            backup::export_backup,
+            backup::import_backup,
+            backup::get_auto_backup,
+            backup::set_auto_backup,
+            codex_file::export_personnage,
+            codex_file::import_personnage_file,
+            pdf::export_personnage_pdf,
+            text_export::export_personnage_text,
             commands::get_game_rules,
             commands::get_competences,
             commands::create_ref_equipement,
             commands::update_ref_equipement,
-            commands::delete_ref_equipement
+            commands::delete_ref_equipement,
+            richesse::record_richesse_transaction,
+            richesse::get_richesse_ledger,
+            richesse::rebuild_richesse_balance,
+            progression::get_progression,
+            progression::add_experience,
+            progression::add_craft_experience,
+            progression::apply_level_up,
+            progression::get_level_up_history,
+            vitals::apply_damage,
+            vitals::heal,
+            vitals::spend_pm,
+            vitals::restore_pm,
+            vitals::set_temp,
+            vitals::get_vitals_events,
+            fatigue::rest,
+            fatigue::march,
+            senses::get_senses,
+            senses::perception_test_modifiers,
+            movement::compute_movement,
+            movement::travel_time,
+            environment::compute_environment_protection,
+            companions::list_companions,
+            companions::add_companion,
+            companions::update_companion,
+            companions::delete_companion,
+            companions::damage_companion,
+            companions::heal_companion,
+            companions::set_active_mount,
+            companions::companion_upkeep,
+            inventory::move_item,
+            inventory::get_inventory_loads,
+            inventory::container_summary,
+            consumables::consume_item,
+            consumables::advance_food_expiry,
+            calendar::get_game_time,
+            calendar::join_game_clock,
+            calendar::advance_time,
+            modifiers::list_temp_effects,
+            modifiers::add_temp_effect,
+            modifiers::remove_temp_effect,
+            dice::roll_dice,
+            combat::create_encounter,
+            combat::get_encounter,
+            combat::list_encounters,
+            combat::combat_attack,
+            combat::combat_spend_action,
+            combat::combat_next_turn,
+            combat::end_encounter
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,11 +1,55 @@
+use crate::alcohol::alcohol_modifiers;
+use crate::commands::{palier_corruption, GameRules, Metier};
+use crate::companions::{bonus_cavalier_actif, monture_active};
+use crate::db::RefEquipement;
+use crate::equipement::{pieces_portees, pr_solide_totale};
+use crate::fatigue::niveau_depuis_fiche;
+use crate::inventory::{poids_objet, poids_total, reference};
+use crate::modifiers::{effets_appliques, lire_effets, pousser_effets, total_effets};
+use crate::sheet::{read_i64, read_str};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// The stats engine, mirroring src/utils/statsEngine.ts that the character sheet
+// displays. Both run tests/fixtures/stats_parity.json: keep them in step.
+
+/// Characteristics of the sheet, with the abbreviation the rules files use.
+pub const CARACTERISTIQUES: [(&str, &str); 10] = [
+    ("courage", "COU"),
+    ("intelligence", "INT"),
+    ("charisme", "CHA"),
+    ("adresse", "AD"),
+    ("force", "FO"),
+    ("perception", "PER"),
+    ("esquive", "ES"),
+    ("attaque", "AT"),
+    ("parade", "PRD"),
+    ("degats", "DEG"),
+];
+
+/// Esquive modifier standing for "Impossible", above 7 PR solide.
+pub const ESQUIVE_IMPOSSIBLE: i32 = -999;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BaseStats {
     pub esquive_naturelle: i32,
+    /// Rider bonus and temporary effects on esquive
+    #[serde(default)]
+    pub bonus_temporaires: i32,
     // Add other stats as needed
 }
 
+impl BaseStats {
+    pub fn depuis_fiche(data: &Value) -> Self {
+        BaseStats {
+            esquive_naturelle: read_i64(data, "/characteristics/esquive/naturel") as i32,
+            bonus_temporaires: bonus_cavalier_actif(data, "esquive")
+                + total_effets(data, "esquive"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub enum EquipmentType {
     #[default]
@@ -30,11 +74,67 @@ pub struct Etats {
     pub blessure_tete: i32, // Value of malus
 }
 
+impl Etats {
+    /// Reads the state malus from the character sheet. Only the esquive column
+    /// of the alcohol tables matters here.
+    pub fn depuis_fiche(data: &Value) -> Self {
+        let jours_retard = read_i64(data, "/status/drug/jours_retard") as i32;
+        let drogue = match read_str(data, "/status/drug/type") {
+            "ADD" => jours_retard / 2,
+            "ADD+" | "ADD++" => jours_retard,
+            _ => 0,
+        };
+
+        Etats {
+            fatigue: niveau_depuis_fiche(data).malus(),
+            alcool: -alcohol_modifiers(data).total().esquive,
+            drogue,
+            blessure_tete: read_i64(data, "/general/malus_tete") as i32,
+        }
+    }
+}
+
+/// One line of a stat breakdown, same shape as `StatComponent` on the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatComponent {
+    pub label: String,
+    pub value: i32,
+}
+
+/// A derived stat with its formula and the lines that make it up.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatDetail {
+    pub total: i32,
+    pub formula: String,
+    pub components: Vec<StatComponent>,
+}
+
+impl StatDetail {
+    pub fn new(formula: &str) -> Self {
+        StatDetail {
+            formula: formula.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a line to the breakdown; zero values are left out.
+    pub fn push(&mut self, label: impl Into<String>, value: i32) {
+        if value != 0 {
+            self.total += value;
+            self.components.push(StatComponent {
+                label: label.into(),
+                value,
+            });
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinalStats {
     pub esquive_totale: i32,
     pub esquive_naturelle: i32,
     pub bonus_equipement: i32,
+    pub bonus_temporaires: i32,
     pub malus_poids: i32,
     pub malus_etats: i32,
 }
@@ -52,7 +152,9 @@ pub fn calculer_stats_finales(
     let malus_etats = etats.fatigue + etats.alcool + etats.drogue + etats.blessure_tete;
 
     // 3. Final Calculation
-    let raw_esquive = base.esquive_naturelle + bonus_equipement - malus_poids - malus_etats;
+    let raw_esquive = base.esquive_naturelle + bonus_equipement + base.bonus_temporaires
+        - malus_poids
+        - malus_etats;
 
     // 4. Clamp to 0
     let esquive_totale = raw_esquive.max(0);
@@ -61,18 +163,303 @@ pub fn calculer_stats_finales(
         esquive_totale,
         esquive_naturelle: base.esquive_naturelle,
         bonus_equipement,
+        bonus_temporaires: base.bonus_temporaires,
         malus_poids,
         malus_etats,
     }
 }
 
+/// Magic and stealth stats derived from the characteristics, with their sources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MagicStats {
+    pub magie_physique: StatDetail,
+    pub magie_psychique: StatDetail,
+    pub resistance_magique: StatDetail,
+    pub discretion: StatDetail,
+}
+
+/// `attributs_automatisables` of the character's specialisation and
+/// sub-specialisation, keyed as in metiers.json ("AT", "RM", "MVTm"...).
+#[derive(Debug, Clone, Default)]
+pub struct BonusMetier {
+    pub specialisation: BTreeMap<String, i32>,
+    pub sous_specialisation: BTreeMap<String, i32>,
+}
+
+fn attributs(attributs: &Value) -> BTreeMap<String, i32> {
+    attributs
+        .as_object()
+        .map(|o| {
+            o.iter()
+                .filter_map(|(k, v)| v.as_i64().map(|v| (k.clone(), v as i32)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl BonusMetier {
+    /// A sub-specialisation only counts under the specialisation it belongs to.
+    pub fn depuis_fiche(data: &Value, metiers: &[Metier]) -> Self {
+        let choisi = |pointer: &str, name_m: &str, name_f: &str| {
+            let nom = read_str(data, pointer);
+            !nom.is_empty() && (nom == name_m || nom == name_f)
+        };
+        let specialisation = metiers
+            .iter()
+            .find(|m| choisi("/identity/metier", &m.name_m, &m.name_f))
+            .and_then(|m| m.specialisations.as_ref())
+            .and_then(|s| {
+                s.iter()
+                    .find(|s| choisi("/identity/specialisation", &s.name_m, &s.name_f))
+            });
+        let sous_specialisation = specialisation
+            .and_then(|s| s.sous_specialisations.as_ref())
+            .and_then(|s| {
+                s.iter()
+                    .find(|s| choisi("/identity/sous_specialisation", &s.name_m, &s.name_f))
+            });
+
+        BonusMetier {
+            specialisation: specialisation
+                .map(|s| attributs(&s.attributs_automatisables))
+                .unwrap_or_default(),
+            sous_specialisation: sous_specialisation
+                .map(|s| attributs(&s.attributs_automatisables))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Adds the specialisation and sub-specialisation lines for `cle`.
+    pub fn pousser(&self, detail: &mut StatDetail, cle: &str) {
+        let bonus = |b: &BTreeMap<String, i32>| b.get(cle).copied().unwrap_or(0);
+        detail.push("Spécialisation", bonus(&self.specialisation));
+        detail.push("Sous-spécialisation", bonus(&self.sous_specialisation));
+    }
+}
+
+/// Esquive modifier for the PR solide worn: +1 up to 1, then down to
+/// "Impossible" above 7.
+pub fn modificateur_encombrement(pr_solide: i64) -> i32 {
+    match pr_solide {
+        0..=1 => 1,
+        3..=4 => -2,
+        5 => -4,
+        6 => -5,
+        7 => -6,
+        8.. => ESQUIVE_IMPOSSIBLE,
+        _ => 0,
+    }
+}
+
+/// -2 esquive once the backpack holds 90% of its capacity. The backpack is the
+/// first `Sacs` row whose reference is a bag; the other rows count as its load.
+fn malus_sac(data: &Value, refs: &[RefEquipement]) -> i32 {
+    let sacs: Vec<&Value> = data
+        .get("inventory")
+        .and_then(|i| i.as_array())
+        .map(|items| items.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|item| item.get("equipement_type").and_then(|t| t.as_str()) == Some("Sacs"))
+        .collect();
+    let Some((sac, capacite)) = sacs.iter().find_map(|item| {
+        reference(item, refs)
+            .filter(|r| r.category == "Sacs")
+            .map(|r| (*item, read_i64(&r.details, "/capacite")))
+    }) else {
+        return 0;
+    };
+
+    let charge = sacs
+        .iter()
+        .filter(|item| read_str(item, "/uid") != read_str(sac, "/uid"))
+        .map(|item| poids_objet(item, refs))
+        .sum::<f64>()
+        + sac
+            .get("contenu")
+            .and_then(|c| c.as_array())
+            .map(|c| poids_total(c, refs))
+            .unwrap_or(0.0);
+    if capacite > 0 && charge >= 0.9 * capacite as f64 {
+        -2
+    } else {
+        0
+    }
+}
+
+/// The "Equipé" column of the characteristics table, with its breakdown.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaracteristiquesEquipees {
+    pub valeurs: BTreeMap<String, StatDetail>,
+}
+
+impl CaracteristiquesEquipees {
+    pub fn get(&self, key: &str) -> i32 {
+        self.valeurs.get(key).map(|d| d.total).unwrap_or(0)
+    }
+}
+
+/// Characteristics as the sheet shows them: the naturel and t1..t3 columns, state
+/// modifiers (head, fatigue, alcohol, drug), the AD > 12 bonus, specialisation
+/// bonuses, encumbrance and an overloaded bag on esquive, worn items, the rider
+/// bonus of the mount the character is on and temporary effects.
+pub fn caracteristiques_equipees(
+    data: &Value,
+    refs: &[RefEquipement],
+    metiers: &[Metier],
+) -> CaracteristiquesEquipees {
+    let pieces = pieces_portees(data, refs);
+    let etats = Etats::depuis_fiche(data);
+    let etat_fatigue = match read_str(data, "/status/fatigue/etat") {
+        "" => "Normal",
+        etat => etat,
+    };
+    let fatigue = niveau_depuis_fiche(data).modificateur();
+    let alcool = alcohol_modifiers(data);
+    let drogue = format!("Manque (Drogue: {})", read_str(data, "/status/drug/type"));
+    let bonus_ad_12 = read_str(data, "/general/bonus_ad_12");
+    let metier = BonusMetier::depuis_fiche(data, metiers);
+    let pr_solide = pr_solide_totale(data, &pieces);
+    let encombrement = modificateur_encombrement(pr_solide);
+    let monture = monture_active(data);
+    let effets = lire_effets(data).unwrap_or_default();
+
+    let valeurs = CARACTERISTIQUES
+        .iter()
+        .map(|(key, abreviation)| {
+            let column =
+                |c: &str| read_i64(data, &format!("/characteristics/{}/{}", key, c)) as i32;
+            let mut detail = StatDetail::new("Naturel + T1 + T2 + T3 + États + Métier + Objets");
+            detail.push("Naturel", column("naturel"));
+            detail.push("T1", column("t1"));
+            detail.push("T2", column("t2"));
+            detail.push("T3", column("t3"));
+            detail.push("Malus Tête", -etats.blessure_tete);
+            detail.push(format!("Etat de fatigue ({})", etat_fatigue), fatigue);
+            detail.push("Alcool (léger)", alcool.leger.get(key));
+            detail.push("Alcool (fort)", alcool.fort.get(key));
+            detail.push("Gueule de bois", alcool.gueule_de_bois.get(key));
+            detail.push(drogue.clone(), -etats.drogue);
+            if matches!((*key, bonus_ad_12), ("attaque", "AT") | ("parade", "PRD")) {
+                detail.push("Base AD > 12", 1);
+            }
+            metier.pousser(&mut detail, abreviation);
+            if *key == "esquive" {
+                let label = if encombrement > 0 {
+                    "Légèreté"
+                } else {
+                    "Encombrement"
+                };
+                detail.push(format!("{} (PR Sol. {})", label, pr_solide), encombrement);
+                detail.push("Sac surchargé", malus_sac(data, refs));
+            }
+            for piece in &pieces {
+                detail.push(piece.nom(), piece.carac(key) as i32);
+            }
+            if let Some(monture) = &monture {
+                detail.push(
+                    format!("Bonus cavalier ({})", monture.nom),
+                    bonus_cavalier_actif(data, key),
+                );
+            }
+            for effet in effets_appliques(&effets, key) {
+                detail.push(effet.label, effet.value);
+            }
+            (key.to_string(), detail)
+        })
+        .collect();
+
+    CaracteristiquesEquipees { valeurs }
+}
+
+fn moyenne_sup(values: &[i32]) -> i32 {
+    let sum: i32 = values.iter().sum();
+    (sum as f64 / values.len() as f64).ceil() as i32
+}
+
+pub fn calculer_stats_magiques(
+    data: &Value,
+    refs: &[RefEquipement],
+    rules: &GameRules,
+) -> MagicStats {
+    let caracs = caracteristiques_equipees(data, refs, &rules.metiers);
+    let cou = caracs.get("courage");
+    let int = caracs.get("intelligence");
+    let cha = caracs.get("charisme");
+    let ad = caracs.get("adresse");
+    let fo = caracs.get("force");
+
+    let mut magie_physique = StatDetail::new("Moyenne sup. (Intelligence + Adresse) + Objets");
+    magie_physique.push(
+        format!("Moyenne sup. (INT {} + AD {})", int, ad),
+        moyenne_sup(&[int, ad]),
+    );
+
+    let mut magie_psychique = StatDetail::new("Moyenne sup. (Intelligence + Charisme) + Objets");
+    magie_psychique.push(
+        format!("Moyenne sup. (INT {} + CHA {})", int, cha),
+        moyenne_sup(&[int, cha]),
+    );
+
+    let mut resistance_magique =
+        StatDetail::new("Moyenne sup. (Courage + Intelligence + Force) + Objets + Corruption");
+    resistance_magique.push(
+        format!("Moyenne sup. (COU {} + INT {} + FO {})", cou, int, fo),
+        moyenne_sup(&[cou, int, fo]),
+    );
+
+    let mut discretion = StatDetail::new("Adresse Naturelle + Objets");
+    discretion.push(
+        "Adresse (Naturelle)",
+        read_i64(data, "/characteristics/adresse/naturel") as i32,
+    );
+
+    for piece in pieces_portees(data, refs) {
+        let nom = piece.nom();
+        magie_physique.push(nom.clone(), piece.carac("mag_phy") as i32);
+        magie_psychique.push(nom.clone(), piece.carac("mag_psy") as i32);
+        resistance_magique.push(nom.clone(), piece.carac("rm") as i32);
+        discretion.push(nom, piece.carac("discretion") as i32);
+    }
+    BonusMetier::depuis_fiche(data, &rules.metiers).pousser(&mut resistance_magique, "RM");
+    let corruption = read_i64(data, "/vitals/corruption/current");
+    resistance_magique.push(
+        "Palier de corruption",
+        palier_corruption(&rules.corruption_palier, corruption)
+            .map(|p| p.rm)
+            .unwrap_or(0),
+    );
+
+    for (stat, key) in [
+        (&mut magie_physique, "magie_physique"),
+        (&mut magie_psychique, "magie_psychique"),
+        (&mut resistance_magique, "resistance_magique"),
+        (&mut discretion, "discretion"),
+    ] {
+        stat.push(
+            "Temporaire",
+            read_i64(data, &format!("/magic/{}/temp", key)) as i32,
+        );
+        pousser_effets(stat, data, key);
+    }
+
+    MagicStats {
+        magie_physique,
+        magie_psychique,
+        resistance_magique,
+        discretion,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::get_game_rules;
 
     fn mock_stats() -> BaseStats {
         BaseStats {
             esquive_naturelle: 10,
+            bonus_temporaires: 0,
         }
     }
 
@@ -139,10 +526,30 @@ mod tests {
         assert_eq!(final_stats.esquive_totale, 9);
     }
 
+    #[test]
+    fn test_etats_from_sheet() {
+        let data = serde_json::json!({
+            "general": { "malus_tete": 2 },
+            "status": {
+                "fatigue": { "etat": "Epuisé 2" },
+                "drug": { "type": "ADD", "jours_retard": 3 }
+            }
+        });
+
+        let etats = Etats::depuis_fiche(&data);
+        assert_eq!(etats.fatigue, 2);
+        assert_eq!(etats.drogue, 1);
+        assert_eq!(etats.blessure_tete, 2);
+
+        let final_stats = calculer_stats_finales(mock_stats(), vec![], etats);
+        assert_eq!(final_stats.esquive_totale, 5);
+    }
+
     #[test]
     fn test_esquive_cannot_be_negative() {
         let base = BaseStats {
             esquive_naturelle: 0,
+            bonus_temporaires: 0,
         };
         let mut etats = mock_etats();
         etats.fatigue = 10;
@@ -152,4 +559,91 @@ mod tests {
         let final_stats = calculer_stats_finales(base, eq, etats);
         assert_eq!(final_stats.esquive_totale, 0);
     }
+
+    #[test]
+    fn test_base_stats_from_sheet() {
+        let data = serde_json::json!({
+            "characteristics": { "esquive": { "naturel": 9 } },
+            "mounts": [{ "uid": "m1", "nom": "Pégase", "bonus_cavalier": "+1 ES" }],
+            "status": { "monture_active": "m1" },
+            "temp_effects": [{
+                "uid": "e1", "cible": "esquive", "valeur": 2, "source": "Potion",
+                "duree": null, "restant": null
+            }]
+        });
+
+        let base = BaseStats::depuis_fiche(&data);
+        assert_eq!(base.bonus_temporaires, 3);
+        let final_stats = calculer_stats_finales(base, vec![], mock_etats());
+        assert_eq!(final_stats.esquive_totale, 12);
+    }
+
+    #[test]
+    fn test_magic_stats_rounded_up() {
+        let data = serde_json::json!({
+            "characteristics": {
+                "courage": { "naturel": 11 },
+                "intelligence": { "naturel": 12 },
+                "charisme": { "naturel": 9 },
+                "adresse": { "naturel": 11 },
+                "force": { "naturel": 10 }
+            },
+            "vitals": { "corruption": { "current": 12 } },
+            "magic": { "discretion": { "temp": -2 } }
+        });
+
+        let stats = calculer_stats_magiques(&data, &[], &get_game_rules().unwrap());
+        assert_eq!(stats.magie_physique.total, 12); // (12 + 11) / 2 = 11.5
+        assert_eq!(stats.magie_psychique.total, 11); // (12 + 9) / 2 = 10.5
+        assert_eq!(stats.resistance_magique.total, 11 - 1);
+        assert_eq!(stats.discretion.total, 11 - 2);
+    }
+
+    #[test]
+    fn test_dodge_encumbrance() {
+        assert_eq!(modificateur_encombrement(0), 1);
+        assert_eq!(modificateur_encombrement(2), 0);
+        assert_eq!(modificateur_encombrement(4), -2);
+        assert_eq!(modificateur_encombrement(7), -6);
+        assert_eq!(modificateur_encombrement(8), ESQUIVE_IMPOSSIBLE);
+    }
+
+    /// Same sheet and expected values as scripts/check_stats_parity.mjs, which
+    /// runs the frontend engine: both must agree with what the sheet shows.
+    #[test]
+    fn test_stats_parity_fixture() {
+        let fixture: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/stats_parity.json")).unwrap();
+        let data = &fixture["sheet"];
+        let refs: Vec<RefEquipement> = serde_json::from_value(fixture["refs"].clone()).unwrap();
+        let expected = &fixture["expected"];
+        let rules = get_game_rules().unwrap();
+
+        let caracs = caracteristiques_equipees(data, &refs, &rules.metiers);
+        for (key, _) in CARACTERISTIQUES {
+            assert_eq!(
+                caracs.get(key) as i64,
+                expected[key].as_i64().unwrap(),
+                "{}: {:?}",
+                key,
+                caracs.valeurs[key].components
+            );
+        }
+
+        let magic = calculer_stats_magiques(data, &refs, &rules);
+        for (key, stat) in [
+            ("magie_physique", &magic.magie_physique),
+            ("magie_psychique", &magic.magie_psychique),
+            ("resistance_magique", &magic.resistance_magique),
+            ("discretion", &magic.discretion),
+        ] {
+            assert_eq!(
+                stat.total as i64,
+                expected[key].as_i64().unwrap(),
+                "{}: {:?}",
+                key,
+                stat.components
+            );
+        }
+    }
 }
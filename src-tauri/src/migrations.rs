@@ -0,0 +1,303 @@
+use rusqlite::Connection;
+
+// Numbered schema migrations, tracked with `PRAGMA user_version`. Each one runs
+// in its own transaction together with the version bump, so a failure leaves
+// the database exactly as it was before that step.
+//
+// Never edit a migration that has shipped: add a new one at the end.
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        // Everything created before migrations existed. `IF NOT EXISTS` lets
+        // databases from those builds (user_version 0) go through it untouched.
+        description: "initial schema",
+        sql: "
+        CREATE TABLE IF NOT EXISTS personnages (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            data TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        -- Same columns as the Supabase table
+        CREATE TABLE IF NOT EXISTS ref_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            category TEXT NOT NULL,
+            ref_id INTEGER DEFAULT 0,
+            nom TEXT NOT NULL,
+            degats TEXT DEFAULT '{}',
+            caracteristiques TEXT DEFAULT '{}',
+            protections TEXT DEFAULT '{}',
+            prix_info TEXT DEFAULT '{}',
+            craft TEXT DEFAULT '{}',
+            details TEXT DEFAULT '{}'
+        );
+
+        CREATE TABLE IF NOT EXISTS personnages_versions (
+            version_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            personnage_id TEXT NOT NULL,
+            data TEXT NOT NULL,
+            saved_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS db_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS richesse_ledger (
+            entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            personnage_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            currency TEXT NOT NULL,
+            location TEXT NOT NULL,
+            destination TEXT,
+            note TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE TABLE IF NOT EXISTS personnages_level_ups (
+            level_up_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            personnage_id TEXT NOT NULL,
+            niveau INTEGER NOT NULL,
+            choices TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS personnages_events (
+            event_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            personnage_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            payload TEXT NOT NULL DEFAULT '{}',
+            created_at TEXT NOT NULL
+        );
+
+        -- In-game time, in minutes since the start of the campaign. A character
+        -- without a membership row keeps its own clock, named after its id.
+        CREATE TABLE IF NOT EXISTS game_clocks (
+            clock_id TEXT PRIMARY KEY,
+            minutes INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS game_clock_members (
+            personnage_id TEXT PRIMARY KEY,
+            clock_id TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS combat_encounters (
+            encounter_id TEXT PRIMARY KEY,
+            nom TEXT NOT NULL,
+            data TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+    ",
+    },
+    Migration {
+        version: 2,
+        description: "version labels, clients and pins",
+        sql: "
+        ALTER TABLE personnages_versions ADD COLUMN label TEXT;
+        ALTER TABLE personnages_versions ADD COLUMN client TEXT;
+        ALTER TABLE personnages_versions ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+    ",
+    },
+    Migration {
+        version: 3,
+        description: "undo journal",
+        sql: "
+        CREATE TABLE personnages_journal (
+            entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            personnage_id TEXT NOT NULL,
+            patch TEXT NOT NULL,
+            inverse TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX idx_personnages_journal ON personnages_journal (personnage_id, entry_id);
+    ",
+    },
+    Migration {
+        version: 4,
+        description: "trash and sync tombstones",
+        sql: "
+        ALTER TABLE personnages ADD COLUMN deleted_at TEXT;
+        CREATE TABLE personnages_tombstones (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            deleted_at TEXT NOT NULL
+        );
+        -- Left behind by the hard deletes of earlier builds
+        DELETE FROM personnages_versions WHERE personnage_id NOT IN (SELECT id FROM personnages);
+    ",
+    },
+    Migration {
+        version: 5,
+        description: "label and client of the current sheet",
+        sql: "
+        ALTER TABLE personnages ADD COLUMN version_label TEXT;
+        ALTER TABLE personnages ADD COLUMN version_client TEXT;
+    ",
+    },
+    Migration {
+        version: 6,
+        description: "tombstones pushed once",
+        sql: "
+        ALTER TABLE personnages_tombstones ADD COLUMN synced_at TEXT;
+    ",
+    },
+];
+
+pub fn schema_version(conn: &Connection) -> Result<i64, String> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Brings the database up to the latest schema. Returns the versions applied.
+pub fn run_migrations(conn: &mut Connection) -> Result<Vec<i64>, String> {
+    apply(conn, MIGRATIONS)
+}
+
+fn apply(conn: &mut Connection, migrations: &[Migration]) -> Result<Vec<i64>, String> {
+    let current = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(format!(
+            "Base de données trop récente (schéma {}, cette version connaît {}): mettez l'application à jour",
+            current, latest
+        ));
+    }
+
+    let mut applied = Vec::new();
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration.sql)
+            .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+            .map_err(|e| {
+                format!(
+                    "Migration {} ({}) échouée: {}",
+                    migration.version, migration.description, e
+                )
+            })?;
+        tx.commit().map_err(|e| e.to_string())?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+/// Empty in-memory database at the latest schema, for the tests of other modules.
+#[cfg(test)]
+pub fn test_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    run_migrations(&mut conn).unwrap();
+    conn
+}
+
+/// `test_db` holding one character, `p1` named Gurdil, with the given sheet.
+#[cfg(test)]
+pub fn test_db_with(data: &serde_json::Value) -> Connection {
+    let conn = test_db();
+    conn.execute(
+        "INSERT INTO personnages (id, name, data, updated_at)
+         VALUES ('p1', 'Gurdil', ?1, '2024-01-01T00:00:00+00:00')",
+        [data.to_string()],
+    )
+    .unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database as written by the builds before migrations: no user_version,
+    /// fewer tables.
+    fn old_fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE personnages (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                data TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE db_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO personnages VALUES ('p1', 'Gurdil', '{\"identity\":{\"nom\":\"Gurdil\"}}', '2024-01-01');
+            INSERT INTO db_meta VALUES ('db_version', '12');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_old_database_is_upgraded() {
+        let mut conn = old_fixture();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        let applied = run_migrations(&mut conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(
+            schema_version(&conn).unwrap(),
+            MIGRATIONS.last().unwrap().version
+        );
+
+        let name: String = conn
+            .query_row("SELECT name FROM personnages WHERE id = 'p1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(name, "Gurdil");
+        let encounters: i64 = conn
+            .query_row("SELECT COUNT(*) FROM combat_encounters", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(encounters, 0);
+
+        // Nothing left to do on the next launch
+        assert!(run_migrations(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let mut conn = old_fixture();
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "ok",
+                sql: "ALTER TABLE personnages ADD COLUMN deleted_at TEXT;",
+            },
+            Migration {
+                version: 2,
+                description: "broken",
+                sql: "CREATE TABLE half_done (id INTEGER);
+                      ALTER TABLE table_qui_n_existe_pas ADD COLUMN x TEXT;",
+            },
+        ];
+
+        let err = apply(&mut conn, &migrations).unwrap_err();
+        assert!(err.contains("Migration 2"));
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        let half_done: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'half_done'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(half_done, 0);
+
+        // A database from a newer build is left alone
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        assert!(apply(&mut conn, &migrations).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::calendar::{MINUTES_PAR_HEURE, MINUTES_PAR_JOUR};
+use crate::db::{load_personnage_data, log_personnage_event, store_personnage_data, AppState};
+use crate::logic::{StatComponent, StatDetail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Structured temporary effects, stored in `data.temp_effects`. The free-text
+// `temp_modifiers` slots and the `{ base, temp }` values stay as they are; these
+// effects come on top and are listed by source in the stat breakdowns.
+//
+// `cible` uses the sheet keys: characteristics ("courage", "attaque"…),
+// "magie_physique", "magie_psychique", "resistance_magique", "discretion",
+// "marche", "course", "pr_sol" and "protection_pluie/froid/chaleur".
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unite {
+    Rounds,
+    Heures,
+    Jours,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DureeEffet {
+    pub valeur: i64,
+    pub unite: Unite,
+}
+
+/// How an effect combines with the others on the same stat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cumul {
+    /// Adds up with everything
+    #[default]
+    Cumulable,
+    /// A new effect from the same source replaces the previous one
+    Remplace,
+    /// Only the strongest bonus and the strongest malus of this kind apply
+    PlusFort,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffetTemporaire {
+    pub uid: String,
+    pub cible: String,
+    pub valeur: i32,
+    pub source: String,
+    /// `None` lasts until removed by hand
+    pub duree: Option<DureeEffet>,
+    #[serde(default)]
+    pub cumul: Cumul,
+    /// Rounds, or minutes of game time, before it wears off
+    pub restant: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NouvelEffet {
+    pub cible: String,
+    pub valeur: i32,
+    pub source: String,
+    pub duree: Option<DureeEffet>,
+    #[serde(default)]
+    pub cumul: Cumul,
+}
+
+impl DureeEffet {
+    fn restant(&self) -> i64 {
+        match self.unite {
+            Unite::Rounds => self.valeur,
+            Unite::Heures => self.valeur * MINUTES_PAR_HEURE,
+            Unite::Jours => self.valeur * MINUTES_PAR_JOUR,
+        }
+    }
+}
+
+/// Effects still running, in the order they were added.
+pub fn lire_effets(data: &Value) -> Result<Vec<EffetTemporaire>, String> {
+    crate::sheet::read_list(data, "/temp_effects")
+        .map_err(|e| format!("Effets temporaires illisibles: {}", e))
+}
+
+fn ecrire_effets(data: &mut Value, effets: &[EffetTemporaire]) -> Result<(), String> {
+    let value = serde_json::to_value(effets).map_err(|e| e.to_string())?;
+    crate::sheet::set_value(data, "/temp_effects", value);
+    Ok(())
+}
+
+/// Adds an effect. Returns it with the effects it replaced.
+pub fn ajouter_effet(
+    data: &mut Value,
+    nouvel: NouvelEffet,
+) -> Result<(EffetTemporaire, Vec<EffetTemporaire>), String> {
+    if nouvel.cible.trim().is_empty() {
+        return Err("La cible de l'effet est obligatoire".to_string());
+    }
+    if nouvel.duree.is_some_and(|d| d.valeur <= 0) {
+        return Err("La durée doit être positive".to_string());
+    }
+
+    let effet = EffetTemporaire {
+        uid: uuid::Uuid::new_v4().to_string(),
+        cible: nouvel.cible,
+        valeur: nouvel.valeur,
+        source: nouvel.source,
+        duree: nouvel.duree,
+        cumul: nouvel.cumul,
+        restant: nouvel.duree.map(|d| d.restant()),
+    };
+
+    let (remplaces, mut effets): (Vec<_>, Vec<_>) = lire_effets(data)?.into_iter().partition(|e| {
+        effet.cumul == Cumul::Remplace && e.cible == effet.cible && e.source == effet.source
+    });
+    effets.push(effet.clone());
+    ecrire_effets(data, &effets)?;
+    Ok((effet, remplaces))
+}
+
+/// Effects on `cible` that currently apply, once stacking rules are resolved.
+pub fn effets_appliques(effets: &[EffetTemporaire], cible: &str) -> Vec<StatComponent> {
+    let sur_cible = || effets.iter().filter(|e| e.cible == cible && e.valeur != 0);
+    let plus_forts = |bonus: bool| {
+        sur_cible()
+            .filter(|e| e.cumul == Cumul::PlusFort && (e.valeur > 0) == bonus)
+            .max_by_key(|e| e.valeur.abs())
+    };
+
+    sur_cible()
+        .filter(|e| e.cumul != Cumul::PlusFort)
+        .chain(plus_forts(true))
+        .chain(plus_forts(false))
+        .map(|e| StatComponent {
+            label: e.source.clone(),
+            value: e.valeur,
+        })
+        .collect()
+}
+
+pub fn total_effets(data: &Value, cible: &str) -> i32 {
+    effets_appliques(&lire_effets(data).unwrap_or_default(), cible)
+        .iter()
+        .map(|c| c.value)
+        .sum()
+}
+
+/// Adds one line per applied effect to a stat breakdown.
+pub fn pousser_effets(detail: &mut StatDetail, data: &Value, cible: &str) {
+    for component in effets_appliques(&lire_effets(data).unwrap_or_default(), cible) {
+        detail.push(component.label, component.value);
+    }
+}
+
+/// Counts down and drops expired effects. `rounds` is for combat; any game time
+/// passing ends round-based effects, the fight being over.
+pub fn ecouler(
+    data: &mut Value,
+    rounds: i64,
+    minutes: i64,
+) -> Result<Vec<EffetTemporaire>, String> {
+    let effets = lire_effets(data)?;
+    let mut expires = Vec::new();
+    let mut restants = Vec::new();
+    for mut effet in effets.iter().cloned() {
+        let ecoule = match effet.duree.map(|d| d.unite) {
+            None => 0,
+            Some(Unite::Rounds) if minutes > 0 => i64::MAX,
+            Some(Unite::Rounds) => rounds,
+            Some(_) => minutes,
+        };
+        match effet.restant {
+            Some(restant) if ecoule >= restant => expires.push(effet),
+            Some(restant) => {
+                effet.restant = Some(restant - ecoule);
+                restants.push(effet);
+            }
+            None => restants.push(effet),
+        }
+    }
+    // A sheet without running effects is left untouched
+    if restants != effets {
+        ecrire_effets(data, &restants)?;
+    }
+    Ok(expires)
+}
+
+#[tauri::command]
+pub fn list_temp_effects(
+    id: String,
+    state: State<AppState>,
+) -> Result<Vec<EffetTemporaire>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    lire_effets(&data)
+}
+
+#[tauri::command]
+pub fn add_temp_effect(
+    id: String,
+    effet: NouvelEffet,
+    state: State<AppState>,
+) -> Result<EffetTemporaire, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let (effet, remplaces) = ajouter_effet(&mut data, effet)?;
+    let now = store_personnage_data(&tx, &id, &data)?;
+    log_personnage_event(
+        &tx,
+        &id,
+        "add_temp_effect",
+        &serde_json::json!({ "effet": effet, "remplaces": remplaces }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(effet)
+}
+
+#[tauri::command]
+pub fn remove_temp_effect(id: String, uid: String, state: State<AppState>) -> Result<(), String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let (retire, effets): (Vec<_>, Vec<_>) =
+        lire_effets(&data)?.into_iter().partition(|e| e.uid == uid);
+    if retire.is_empty() {
+        return Err(format!("Effet introuvable: {}", uid));
+    }
+    ecrire_effets(&mut data, &effets)?;
+    let now = store_personnage_data(&tx, &id, &data)?;
+    log_personnage_event(
+        &tx,
+        &id,
+        "remove_temp_effect",
+        &serde_json::json!({ "effet": retire[0] }),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effet(
+        cible: &str,
+        valeur: i32,
+        source: &str,
+        cumul: Cumul,
+        duree: Option<DureeEffet>,
+    ) -> NouvelEffet {
+        NouvelEffet {
+            cible: cible.to_string(),
+            valeur,
+            source: source.to_string(),
+            duree,
+            cumul,
+        }
+    }
+
+    #[test]
+    fn test_stacking_rules() {
+        let mut data = serde_json::json!({});
+        ajouter_effet(
+            &mut data,
+            effet("force", 2, "Potion de force", Cumul::Remplace, None),
+        )
+        .unwrap();
+        let (_, remplaces) = ajouter_effet(
+            &mut data,
+            effet("force", 3, "Potion de force", Cumul::Remplace, None),
+        )
+        .unwrap();
+        assert_eq!(remplaces.len(), 1);
+        ajouter_effet(
+            &mut data,
+            effet("force", 1, "Bénédiction", Cumul::PlusFort, None),
+        )
+        .unwrap();
+        ajouter_effet(
+            &mut data,
+            effet("force", 2, "Chant de guerre", Cumul::PlusFort, None),
+        )
+        .unwrap();
+        ajouter_effet(
+            &mut data,
+            effet("force", -1, "Poison", Cumul::PlusFort, None),
+        )
+        .unwrap();
+        ajouter_effet(
+            &mut data,
+            effet("adresse", 5, "Autre", Cumul::Cumulable, None),
+        )
+        .unwrap();
+
+        // 3 (latest potion) + 2 (strongest bonus) - 1 (strongest malus)
+        assert_eq!(total_effets(&data, "force"), 4);
+    }
+
+    #[test]
+    fn test_effects_wear_off() {
+        let mut data = serde_json::json!({});
+        let rounds = Some(DureeEffet {
+            valeur: 3,
+            unite: Unite::Rounds,
+        });
+        let heures = Some(DureeEffet {
+            valeur: 2,
+            unite: Unite::Heures,
+        });
+        ajouter_effet(
+            &mut data,
+            effet("attaque", 1, "Rage", Cumul::Cumulable, rounds),
+        )
+        .unwrap();
+        ajouter_effet(
+            &mut data,
+            effet("courage", 1, "Vin", Cumul::Cumulable, heures),
+        )
+        .unwrap();
+        ajouter_effet(
+            &mut data,
+            effet("parade", 1, "Anneau", Cumul::Cumulable, None),
+        )
+        .unwrap();
+
+        assert!(ecouler(&mut data, 2, 0).unwrap().is_empty());
+        assert_eq!(lire_effets(&data).unwrap()[0].restant, Some(1));
+
+        // An hour later the fight is long over, the wine is still there
+        let expires = ecouler(&mut data, 0, 60).unwrap();
+        assert_eq!(expires[0].source, "Rage");
+        assert_eq!(ecouler(&mut data, 0, 60).unwrap()[0].source, "Vin");
+        assert_eq!(lire_effets(&data).unwrap().len(), 1);
+
+        let mut vide = serde_json::json!({});
+        assert!(ecouler(&mut vide, 1, 60).unwrap().is_empty());
+        assert!(vide.get("temp_effects").is_none());
+    }
+
+    #[test]
+    fn test_malformed_effects_are_not_overwritten() {
+        let mut data = serde_json::json!({
+            "temp_effects": [{ "uid": "a", "cible": "force", "valeur": "beaucoup" }]
+        });
+        assert!(ecouler(&mut data, 1, 0).is_err());
+        assert!(
+            ajouter_effet(&mut data, effet("force", 1, "Vin", Cumul::Cumulable, None)).is_err()
+        );
+        assert_eq!(data["temp_effects"][0]["uid"], "a");
+    }
+}
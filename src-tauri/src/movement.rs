@@ -0,0 +1,318 @@
+use crate::commands::{get_game_rules, GameRules};
+use crate::companions::monture_active;
+use crate::db::{load_personnage_data, load_ref_items, AppState};
+use crate::equipement::{pieces_portees, pr_solide_totale};
+use crate::logic::{BonusMetier, StatDetail};
+use crate::modifiers::pousser_effets;
+use crate::sheet::{read_i64, read_str};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// On foot, one point of marche is half a kilometre per hour, and a travel day
+// is eight hours on the road.
+const KMH_PAR_POINT_MARCHE: f64 = 0.5;
+pub const HEURES_VOYAGE_PAR_JOUR: f64 = 8.0;
+
+/// Walking multiplier (in hundredths of the origin speed) for a PR solide.
+pub fn multiplicateur_marche(pr_solide: i64) -> i64 {
+    match pr_solide {
+        i64::MIN..=1 => 8,
+        2 => 6,
+        3..=5 => 4,
+        6 => 3,
+        7 => 2,
+        _ => 1,
+    }
+}
+
+/// Running multiplier (in hundredths of the origin speed) for a PR solide.
+pub fn multiplicateur_course(pr_solide: i64) -> i64 {
+    match pr_solide {
+        i64::MIN..=1 => 12,
+        2 => 10,
+        3..=4 => 8,
+        5 => 6,
+        6 => 4,
+        7 => 3,
+        _ => 2,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ModeDeplacement {
+    #[default]
+    APied,
+    /// Riding the mount with this `uid` from `data.mounts`
+    Monture { uid: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deplacement {
+    pub mode: ModeDeplacement,
+    pub pr_solide: i64,
+    pub marche: StatDetail,
+    pub course: StatDetail,
+    /// Distance covered in a full travel day
+    pub voyage_km_jour: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempsDeTrajet {
+    pub distance_km: f64,
+    pub km_par_jour: f64,
+    pub heures: f64,
+    pub jours_complets: i64,
+    /// Hours on the road during the last, incomplete day
+    pub heures_restantes: f64,
+}
+
+/// Movement bonuses of one worn item.
+#[derive(Debug, Clone, Default)]
+pub struct BonusMouvement {
+    pub nom: String,
+    /// Applies to both marche and course
+    pub mvt: i64,
+    pub marche: i64,
+    pub course: i64,
+}
+
+fn vitesse_origine(rules: &GameRules, origine: &str) -> Result<i64, String> {
+    rules
+        .origines
+        .iter()
+        .find(|o| o.name_m == origine || o.name_f == origine)
+        .map(|o| o.vitesse as i64)
+        .ok_or_else(|| format!("Origine inconnue: {}", origine))
+}
+
+fn trouver_monture<'a>(data: &'a Value, uid: &str) -> Result<&'a Value, String> {
+    data.get("mounts")
+        .and_then(|m| m.as_array())
+        .and_then(|mounts| mounts.iter().find(|m| read_str(m, "/uid") == uid))
+        .ok_or_else(|| format!("Monture introuvable: {}", uid))
+}
+
+/// Movement on foot: origin speed scaled by the PR solide of the worn equipment,
+/// plus the `MVTm`/`MVTc` bonuses of the specialisations and the `mvt`, `marche`
+/// and `course` bonuses of protections and accessories.
+pub fn deplacement_a_pied(
+    data: &Value,
+    vitesse: i64,
+    pr_solide: i64,
+    metier: &BonusMetier,
+    bonus: &[BonusMouvement],
+) -> Deplacement {
+    let formula = "Arrondi sup. (Vitesse Origine * Encombrement PR sol) + Objets";
+    let mut marche = StatDetail::new(formula);
+    let mut course = StatDetail::new(formula);
+
+    let mult_marche = multiplicateur_marche(pr_solide);
+    let mult_course = multiplicateur_course(pr_solide);
+    marche.push(
+        format!(
+            "Base origine: {} * (PR Sol {} => x{})",
+            vitesse as f64 / 100.0,
+            pr_solide,
+            mult_marche
+        ),
+        (vitesse * mult_marche + 99).div_euclid(100) as i32,
+    );
+    course.push(
+        format!(
+            "Base origine: {} * (PR Sol {} => x{})",
+            vitesse as f64 / 100.0,
+            pr_solide,
+            mult_course
+        ),
+        (vitesse * mult_course + 99).div_euclid(100) as i32,
+    );
+    metier.pousser(&mut marche, "MVTm");
+    metier.pousser(&mut course, "MVTc");
+
+    for b in bonus {
+        marche.push(format!("{} (Mvt)", b.nom), b.mvt as i32);
+        course.push(format!("{} (Mvt)", b.nom), b.mvt as i32);
+        marche.push(b.nom.clone(), b.marche as i32);
+        course.push(b.nom.clone(), b.course as i32);
+    }
+
+    marche.push("Temporaire", read_i64(data, "/movement/marche/temp") as i32);
+    course.push("Temporaire", read_i64(data, "/movement/course/temp") as i32);
+    pousser_effets(&mut marche, data, "marche");
+    pousser_effets(&mut course, data, "course");
+
+    let voyage_km_jour = marche.total.max(0) as f64 * KMH_PAR_POINT_MARCHE * HEURES_VOYAGE_PAR_JOUR;
+    Deplacement {
+        mode: ModeDeplacement::APied,
+        pr_solide,
+        marche,
+        course,
+        voyage_km_jour,
+    }
+}
+
+/// Movement while riding: the mount's own values replace the rider's, whatever
+/// the rider wears. A mount without `mvt_voyage` travels at its walking pace.
+pub fn deplacement_monture(uid: &str, monture: &Value, pr_solide: i64) -> Deplacement {
+    let nom = read_str(monture, "/nom");
+    let mut marche = StatDetail::new("Marche de la monture");
+    let mut course = StatDetail::new("Course de la monture");
+    marche.push(nom, read_i64(monture, "/mvt_marche") as i32);
+    course.push(nom, read_i64(monture, "/mvt_course") as i32);
+
+    let mvt_voyage = read_i64(monture, "/mvt_voyage");
+    let voyage_km_jour = if mvt_voyage > 0 {
+        mvt_voyage as f64
+    } else {
+        marche.total.max(0) as f64 * KMH_PAR_POINT_MARCHE * HEURES_VOYAGE_PAR_JOUR
+    };
+
+    Deplacement {
+        mode: ModeDeplacement::Monture {
+            uid: uid.to_string(),
+        },
+        pr_solide,
+        marche,
+        course,
+        voyage_km_jour,
+    }
+}
+
+pub fn temps_de_trajet(distance_km: f64, km_par_jour: f64) -> Result<TempsDeTrajet, String> {
+    if distance_km < 0.0 {
+        return Err("La distance doit être positive".to_string());
+    }
+    if km_par_jour <= 0.0 {
+        return Err("Le personnage ne peut pas se déplacer".to_string());
+    }
+
+    let heures = distance_km / (km_par_jour / HEURES_VOYAGE_PAR_JOUR);
+    let jours_complets = (heures / HEURES_VOYAGE_PAR_JOUR).floor();
+    Ok(TempsDeTrajet {
+        distance_km,
+        km_par_jour,
+        heures,
+        jours_complets: jours_complets as i64,
+        heures_restantes: heures - jours_complets * HEURES_VOYAGE_PAR_JOUR,
+    })
+}
+
+fn calculer_deplacement(
+    state: &State<AppState>,
+    id: &str,
+    mode: Option<&ModeDeplacement>,
+) -> Result<Deplacement, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, id)?;
+    let refs = load_ref_items(&db)?;
+    drop(db);
+
+    let pieces = pieces_portees(&data, &refs);
+    let pr_solide = pr_solide_totale(&data, &pieces);
+
+    // Without an explicit mode, a mounted character moves at the mount's pace
+    let mode = match (mode, monture_active(&data)) {
+        (None, Some(monture)) => ModeDeplacement::Monture { uid: monture.uid },
+        (mode, _) => mode.cloned().unwrap_or_default(),
+    };
+
+    match &mode {
+        ModeDeplacement::APied => {
+            let rules = get_game_rules()?;
+            let vitesse = vitesse_origine(&rules, read_str(&data, "/identity/origine"))?;
+            let metier = BonusMetier::depuis_fiche(&data, &rules.metiers);
+            let bonus: Vec<_> = pieces
+                .iter()
+                .map(|p| BonusMouvement {
+                    nom: p.nom(),
+                    mvt: p.carac("mvt"),
+                    marche: p.carac("marche"),
+                    course: p.carac("course"),
+                })
+                .collect();
+            Ok(deplacement_a_pied(
+                &data, vitesse, pr_solide, &metier, &bonus,
+            ))
+        }
+        ModeDeplacement::Monture { uid } => {
+            let monture = trouver_monture(&data, uid)?;
+            Ok(deplacement_monture(uid, monture, pr_solide))
+        }
+    }
+}
+
+#[tauri::command]
+pub fn compute_movement(
+    id: String,
+    mode: Option<ModeDeplacement>,
+    state: State<AppState>,
+) -> Result<Deplacement, String> {
+    calculer_deplacement(&state, &id, mode.as_ref())
+}
+
+#[tauri::command]
+pub fn travel_time(
+    id: String,
+    distance_km: f64,
+    mode: Option<ModeDeplacement>,
+    state: State<AppState>,
+) -> Result<TempsDeTrajet, String> {
+    let deplacement = calculer_deplacement(&state, &id, mode.as_ref())?;
+    temps_de_trajet(distance_km, deplacement.voyage_km_jour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encumbrance_slows_down() {
+        let data = serde_json::json!({});
+        let leger = deplacement_a_pied(&data, 100, 0, &BonusMetier::default(), &[]);
+        assert_eq!((leger.marche.total, leger.course.total), (8, 12));
+        assert_eq!(leger.voyage_km_jour, 32.0);
+
+        // Nain (80) in heavy armour, with boots giving +1
+        let bottes = vec![BonusMouvement {
+            nom: "Bottes de sept lieues".to_string(),
+            mvt: 1,
+            ..Default::default()
+        }];
+        let lourd = deplacement_a_pied(&data, 80, 5, &BonusMetier::default(), &bottes);
+        assert_eq!(lourd.marche.total, 4 + 1);
+        assert_eq!(lourd.course.total, 5 + 1);
+    }
+
+    #[test]
+    fn test_specialisation_speeds_up() {
+        let rules = get_game_rules().unwrap();
+        let data = serde_json::json!({
+            "identity": {
+                "metier": "Pirate",
+                "specialisation": "Contrebandier",
+                "sous_specialisation": "Véritable Anguille"
+            }
+        });
+        let metier = BonusMetier::depuis_fiche(&data, &rules.metiers);
+        let deplacement = deplacement_a_pied(&data, 100, 0, &metier, &[]);
+        assert_eq!(deplacement.marche.total, 8 + 2);
+        assert_eq!(deplacement.course.total, 12 + 4);
+        assert_eq!(deplacement.voyage_km_jour, 40.0);
+    }
+
+    #[test]
+    fn test_mounted_travel_time() {
+        let cheval = serde_json::json!({
+            "uid": "c1", "nom": "Cheval", "mvt_marche": 16, "mvt_course": 40, "mvt_voyage": 60
+        });
+        let deplacement = deplacement_monture("c1", &cheval, 7);
+        assert_eq!(deplacement.marche.total, 16);
+
+        let trajet = temps_de_trajet(150.0, deplacement.voyage_km_jour).unwrap();
+        assert_eq!(trajet.jours_complets, 2);
+        assert_eq!(trajet.heures_restantes, 4.0);
+        assert!(temps_de_trajet(10.0, 0.0).is_err());
+    }
+}
@@ -0,0 +1,549 @@
+use crate::db::AppState;
+use crate::files::write_atomic;
+use crate::summary::{charger_fiche, completer_descriptions, Fiche, LIEUX};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+use tauri::State;
+
+// Minimal PDF writer for the printable sheet: A4 pages, the two standard Helvetica
+// fonts (no embedding) in WinAnsiEncoding, text and rules only. Content streams
+// are deflated; everything else is written by hand, xref table included.
+
+const LARGEUR_PAGE: f32 = 595.0;
+const HAUTEUR_PAGE: f32 = 842.0;
+const MARGE: f32 = 50.0;
+const LARGEUR_UTILE: f32 = LARGEUR_PAGE - 2.0 * MARGE;
+const TAILLE_TEXTE: f32 = 10.0;
+const INTERLIGNE: f32 = 13.0;
+
+/// Helvetica advance widths (1/1000 em) for ASCII 32..=126.
+const LARGEURS_HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Police {
+    Normale,
+    Grasse,
+}
+
+impl Police {
+    fn nom(&self) -> &'static str {
+        match self {
+            Police::Normale => "F1",
+            Police::Grasse => "F2",
+        }
+    }
+}
+
+/// WinAnsiEncoding byte of a character, `?` when it has none.
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+        '€' => 0x80,
+        '…' => 0x85,
+        'Œ' => 0x8c,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        'œ' => 0x9c,
+        _ => b'?',
+    }
+}
+
+/// Text as a PDF literal string.
+fn chaine_pdf(texte: &str) -> String {
+    let mut sortie = String::from("(");
+    for octet in texte.chars().map(win_ansi) {
+        match octet {
+            b'(' | b')' | b'\\' => {
+                sortie.push('\\');
+                sortie.push(octet as char);
+            }
+            0x20..=0x7e => sortie.push(octet as char),
+            _ => sortie.push_str(&format!("\\{:03o}", octet)),
+        }
+    }
+    sortie.push(')');
+    sortie
+}
+
+fn largeur_texte(texte: &str, police: Police, taille: f32) -> f32 {
+    let unites: u32 = texte
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => LARGEURS_HELVETICA[c as usize - 32] as u32,
+            _ => 556,
+        })
+        .sum();
+    // Bold glyphs are a little wider; close enough for wrapping
+    let facteur = if police == Police::Grasse { 1.08 } else { 1.0 };
+    unites as f32 * taille * facteur / 1000.0
+}
+
+/// Splits a paragraph into lines no wider than `largeur`.
+fn couper_lignes(texte: &str, police: Police, taille: f32, largeur: f32) -> Vec<String> {
+    let mut lignes = Vec::new();
+    for paragraphe in texte.lines() {
+        let mut ligne = String::new();
+        for mot in paragraphe.split_whitespace() {
+            let essai = if ligne.is_empty() {
+                mot.to_string()
+            } else {
+                format!("{} {}", ligne, mot)
+            };
+            if !ligne.is_empty() && largeur_texte(&essai, police, taille) > largeur {
+                lignes.push(std::mem::replace(&mut ligne, mot.to_string()));
+            } else {
+                ligne = essai;
+            }
+        }
+        lignes.push(ligne);
+    }
+    lignes
+}
+
+/// Shortens a table cell that does not fit its column.
+fn tronquer(texte: &str, police: Police, taille: f32, largeur: f32) -> String {
+    if largeur_texte(texte, police, taille) <= largeur {
+        return texte.to_string();
+    }
+    let mut court: String = texte.to_string();
+    while !court.is_empty() && largeur_texte(&format!("{}…", court), police, taille) > largeur {
+        court.pop();
+    }
+    format!("{}…", court.trim_end())
+}
+
+/// Lays text out top to bottom, starting a new page when the current one is full.
+struct Mise {
+    pages: Vec<String>,
+    courante: String,
+    y: f32,
+}
+
+impl Mise {
+    fn new() -> Self {
+        Mise {
+            pages: Vec::new(),
+            courante: String::new(),
+            y: HAUTEUR_PAGE - MARGE,
+        }
+    }
+
+    fn place(&mut self, hauteur: f32) {
+        if self.y - hauteur < MARGE + INTERLIGNE {
+            self.pages.push(std::mem::take(&mut self.courante));
+            self.y = HAUTEUR_PAGE - MARGE;
+        }
+    }
+
+    fn texte(&mut self, x: f32, y: f32, police: Police, taille: f32, texte: &str) {
+        self.courante.push_str(&format!(
+            "BT /{} {} Tf {:.1} {:.1} Td {} Tj ET\n",
+            police.nom(),
+            taille,
+            x,
+            y,
+            chaine_pdf(texte)
+        ));
+    }
+
+    fn titre(&mut self, texte: &str, sous_titre: &str) {
+        self.place(40.0);
+        self.y -= 18.0;
+        self.texte(MARGE, self.y, Police::Grasse, 18.0, texte);
+        if !sous_titre.is_empty() {
+            self.y -= 16.0;
+            self.texte(MARGE, self.y, Police::Normale, 11.0, sous_titre);
+        }
+        self.y -= 6.0;
+    }
+
+    fn section(&mut self, texte: &str) {
+        // Keep the heading with at least two lines of its content
+        self.place(24.0 + 2.0 * INTERLIGNE);
+        self.y -= 20.0;
+        self.texte(MARGE, self.y, Police::Grasse, 12.0, texte);
+        self.courante.push_str(&format!(
+            "0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n",
+            MARGE,
+            self.y - 3.0,
+            LARGEUR_PAGE - MARGE,
+            self.y - 3.0
+        ));
+        self.y -= 4.0;
+    }
+
+    fn paragraphe(&mut self, texte: &str, police: Police, retrait: f32) {
+        for ligne in couper_lignes(texte, police, TAILLE_TEXTE, LARGEUR_UTILE - retrait) {
+            self.place(INTERLIGNE);
+            self.y -= INTERLIGNE;
+            self.texte(MARGE + retrait, self.y, police, TAILLE_TEXTE, &ligne);
+        }
+    }
+
+    /// One table row; `colonnes` are the left edges of the cells, relative to the margin.
+    fn ligne(&mut self, cellules: &[String], colonnes: &[f32], police: Police) {
+        self.place(INTERLIGNE);
+        self.y -= INTERLIGNE;
+        for (index, (cellule, x)) in cellules.iter().zip(colonnes).enumerate() {
+            let fin = colonnes.get(index + 1).copied().unwrap_or(LARGEUR_UTILE);
+            let texte = tronquer(cellule, police, TAILLE_TEXTE, fin - x - 6.0);
+            self.texte(MARGE + x, self.y, police, TAILLE_TEXTE, &texte);
+        }
+    }
+
+    fn tableau(&mut self, entete: &[&str], colonnes: &[f32], lignes: Vec<Vec<String>>) {
+        let entete: Vec<String> = entete.iter().map(|e| e.to_string()).collect();
+        self.ligne(&entete, colonnes, Police::Grasse);
+        for ligne in lignes {
+            self.ligne(&ligne, colonnes, Police::Normale);
+        }
+    }
+
+    fn terminer(mut self) -> Vec<String> {
+        if !self.courante.is_empty() || self.pages.is_empty() {
+            self.pages.push(self.courante);
+        }
+        self.pages
+    }
+}
+
+/// Assembles the document from the content stream of each page.
+fn ecrire_pdf(pages: &[String], titre: &str) -> Result<Vec<u8>, String> {
+    let mut objets: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", 6 + 2 * i))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        format!(
+            "<< /Title {} /Producer (Codex Debilium) >>",
+            chaine_pdf(titre)
+        )
+        .into_bytes(),
+    ];
+    for (index, contenu) in pages.iter().enumerate() {
+        objets.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                LARGEUR_PAGE,
+                HAUTEUR_PAGE,
+                7 + 2 * index
+            )
+            .into_bytes(),
+        );
+        let mut encodeur = ZlibEncoder::new(Vec::new(), Compression::default());
+        encodeur
+            .write_all(contenu.as_bytes())
+            .map_err(|e| e.to_string())?;
+        let flux = encodeur.finish().map_err(|e| e.to_string())?;
+        let mut objet = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            flux.len()
+        )
+        .into_bytes();
+        objet.extend_from_slice(&flux);
+        objet.extend_from_slice(b"\nendstream");
+        objets.push(objet);
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut positions = Vec::with_capacity(objets.len());
+    for (index, objet) in objets.iter().enumerate() {
+        positions.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        pdf.extend_from_slice(objet);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objets.len() + 1).as_bytes(),
+    );
+    for position in positions {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", position).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objets.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    Ok(pdf)
+}
+
+fn signe(valeur: i64) -> String {
+    format!("{:+}", valeur)
+}
+
+pub fn rendre_fiche(fiche: &Fiche) -> Result<Vec<u8>, String> {
+    let identite = &fiche.identite;
+    let mut mise = Mise::new();
+
+    let sous_titre = [
+        identite.origine.clone(),
+        identite.metier.clone(),
+        format!("Niveau {}", identite.niveau),
+    ]
+    .into_iter()
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>()
+    .join(" • ");
+    mise.titre(&identite.nom, &sous_titre);
+
+    mise.section("Identité");
+    let champs = [
+        ("Sexe", identite.sexe.clone()),
+        ("Spécialisation", identite.specialisation.clone()),
+        ("Sous-spécialisation", identite.sous_specialisation.clone()),
+        ("Domaine", identite.domaine.clone()),
+        ("Expérience", identite.experience.to_string()),
+        ("Points de destin", identite.points_destin.to_string()),
+    ];
+    for (nom, valeur) in champs.iter().filter(|(_, v)| !v.is_empty()) {
+        mise.ligne(
+            &[nom.to_string(), valeur.clone()],
+            &[0.0, 130.0],
+            Police::Normale,
+        );
+    }
+    if !identite.description.is_empty() {
+        mise.paragraphe(&identite.description, Police::Normale, 0.0);
+    }
+
+    mise.section("Caractéristiques");
+    mise.tableau(
+        &["Caractéristique", "Naturel", "Finale"],
+        &[0.0, 150.0, 220.0],
+        fiche
+            .caracteristiques
+            .iter()
+            .map(|c| vec![c.nom.clone(), c.naturel.to_string(), c.finale.to_string()])
+            .collect(),
+    );
+
+    mise.section("Défenses et magie");
+    let defenses: Vec<_> = fiche.defenses.iter().collect();
+    let magie: Vec<_> = fiche.magie.iter().collect();
+    for index in 0..defenses.len().max(magie.len()) {
+        let mut cellules = vec![String::new(); 4];
+        if let Some(d) = defenses.get(index) {
+            cellules[0] = d.nom.clone();
+            cellules[1] = d.valeur.to_string();
+        }
+        if let Some(m) = magie.get(index) {
+            cellules[2] = m.nom.clone();
+            cellules[3] = m.valeur.to_string();
+        }
+        mise.ligne(&cellules, &[0.0, 150.0, 250.0, 400.0], Police::Normale);
+    }
+
+    mise.section("Vitalité");
+    let jauge = |nom: &str, j: &crate::vitals::Jauge| {
+        let mut valeur = format!("{} / {}", j.current, j.max);
+        if j.temp != 0 {
+            valeur.push_str(&format!(" ({} temporaire)", signe(j.temp)));
+        }
+        vec![nom.to_string(), valeur]
+    };
+    mise.ligne(
+        &jauge("Points de vie", &fiche.pv),
+        &[0.0, 150.0],
+        Police::Normale,
+    );
+    mise.ligne(
+        &jauge("Points de mana", &fiche.pm),
+        &[0.0, 150.0],
+        Police::Normale,
+    );
+    mise.ligne(
+        &["Corruption".to_string(), fiche.corruption.to_string()],
+        &[0.0, 150.0],
+        Police::Normale,
+    );
+
+    if !fiche.armes.is_empty() {
+        mise.section("Armes");
+        mise.tableau(
+            &["Arme", "Dégâts"],
+            &[0.0, 250.0],
+            fiche
+                .armes
+                .iter()
+                .map(|a| {
+                    let degats = match a.bonus_force {
+                        0 => a.degats.clone(),
+                        bonus => format!("{} (dont FO {})", a.degats, signe(bonus)),
+                    };
+                    vec![a.nom.clone(), degats]
+                })
+                .collect(),
+        );
+    }
+
+    if !fiche.protections.is_empty() {
+        mise.section("Protections");
+        mise.tableau(
+            &["Protection", "PR sol.", "PR spé.", "PR mag."],
+            &[0.0, 250.0, 320.0, 390.0],
+            fiche
+                .protections
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.nom.clone(),
+                        p.pr_sol.to_string(),
+                        p.pr_spe.to_string(),
+                        p.pr_mag.to_string(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+
+    if !fiche.competences.is_empty() {
+        mise.section("Compétences");
+        for competence in &fiche.competences {
+            let nom = match competence.rang.as_str() {
+                "" => competence.nom.clone(),
+                rang => format!("{} ({})", competence.nom, rang),
+            };
+            mise.paragraphe(&nom, Police::Grasse, 0.0);
+            if !competence.description.is_empty() {
+                mise.paragraphe(&competence.description, Police::Normale, 12.0);
+            }
+        }
+    }
+
+    if !fiche.sac.is_empty() {
+        mise.section("Sac");
+        for objet in &fiche.sac {
+            mise.paragraphe(
+                &format!("{} × {}", objet.quantite, objet.nom),
+                Police::Normale,
+                12.0 * objet.niveau as f32,
+            );
+        }
+    }
+
+    if !fiche.richesse.is_empty() {
+        mise.section("Richesse");
+        let mut entete = vec!["Monnaie"];
+        entete.extend(LIEUX);
+        mise.tableau(
+            &entete,
+            &[0.0, 110.0, 190.0, 270.0, 350.0],
+            fiche
+                .richesse
+                .iter()
+                .map(|r| {
+                    let mut ligne = vec![r.monnaie.clone()];
+                    ligne.extend(r.montants.iter().map(|m| m.to_string()));
+                    ligne
+                })
+                .collect(),
+        );
+    }
+
+    // Footer once the page count is known
+    let mut pages = mise.terminer();
+    let total = pages.len();
+    for (index, page) in pages.iter_mut().enumerate() {
+        let pied = format!("{} — page {} / {}", identite.nom, index + 1, total);
+        let x = LARGEUR_PAGE - MARGE - largeur_texte(&pied, Police::Normale, 8.0);
+        page.push_str(&format!(
+            "BT /F1 8 Tf {:.1} {:.1} Td {} Tj ET\n",
+            x,
+            MARGE - 20.0,
+            chaine_pdf(&pied)
+        ));
+    }
+    ecrire_pdf(&pages, &identite.nom)
+}
+
+/// Writes the printable sheet of a character to `path`.
+#[tauri::command]
+pub fn export_personnage_pdf(
+    id: String,
+    path: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut fiche = charger_fiche(&db, &id)?;
+    drop(db);
+    completer_descriptions(&mut fiche)?;
+    let pdf = rendre_fiche(&fiche)?;
+
+    write_atomic(Path::new(&path), |part| {
+        std::fs::write(part, pdf).map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_encoding_and_wrapping() {
+        assert_eq!(chaine_pdf("Dégâts (x2)"), "(D\\351g\\342ts \\(x2\\))");
+        assert_eq!(chaine_pdf("œ→"), "(\\234?)");
+
+        let lignes = couper_lignes(&"mot ".repeat(100), Police::Normale, 10.0, 100.0);
+        assert!(lignes.len() > 1);
+        assert!(lignes
+            .iter()
+            .all(|l| largeur_texte(l, Police::Normale, 10.0) <= 100.0));
+    }
+
+    #[test]
+    fn test_document_structure() {
+        let mut mise = Mise::new();
+        mise.titre("Gurdil", "");
+        for _ in 0..100 {
+            mise.paragraphe("Une ligne", Police::Normale, 0.0);
+        }
+        let pages = mise.terminer();
+        assert_eq!(pages.len(), 2);
+
+        let pdf = ecrire_pdf(&pages, "Gurdil").unwrap();
+        let texte = String::from_utf8_lossy(&pdf);
+        assert!(texte.starts_with("%PDF-1.4"));
+        assert!(texte.ends_with("%%EOF\n"));
+        assert!(texte.contains("/Count 2"));
+
+        // The xref offsets point at the objects
+        let startxref: usize = texte
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|s| s.lines().next())
+            .and_then(|s| s.parse().ok())
+            .unwrap();
+        assert!(pdf[startxref..].starts_with(b"xref"));
+        let xref = String::from_utf8_lossy(&pdf[startxref..]);
+        let premier: usize = xref.lines().nth(3).unwrap()[..10].parse().unwrap();
+        assert!(pdf[premier..].starts_with(b"1 0 obj"));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::commands::{get_competences, Competence};
+use crate::db::{load_personnage_data, store_personnage_data, AppState};
+use crate::sheet::{add_i64, read_i64, set_value};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Level N is reached at 50 * N * (N - 1) XP (100, 300, 600, 1000...),
+// same curve as the auto-level on the character header. That auto-level keeps
+// `general.niveau` in step with the experience, so the level whose gains were
+// last applied is tracked apart in `general.niveau_applique`.
+pub const XP_STEP: i64 = 50;
+pub const NIVEAU_MAX: i32 = 30;
+
+/// What a level brings, from `data/config/niveaux.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReglesNiveaux {
+    /// Characteristics the level point may go into, on even levels
+    #[serde(rename = "Caractéristiques niveau pair")]
+    pub caracs_niveau_pair: Vec<String>,
+    /// Same, on odd levels
+    #[serde(rename = "Caractéristiques niveau impair")]
+    pub caracs_niveau_impair: Vec<String>,
+    #[serde(rename = "Points de caractéristique")]
+    pub points_caracteristique: i32,
+    #[serde(rename = "Dé PV")]
+    pub de_pv: String,
+    #[serde(rename = "Dé PM")]
+    pub de_pm: String,
+    /// Levels granting a new competence pick
+    #[serde(rename = "Niveaux compétence")]
+    pub niveaux_competence: Vec<i32>,
+}
+
+pub fn regles_niveaux() -> Result<ReglesNiveaux, String> {
+    serde_json::from_str(include_str!("../data/config/niveaux.json"))
+        .map_err(|e| format!("Failed to parse niveaux.json: {}", e))
+}
+
+pub fn xp_threshold(niveau: i32) -> i64 {
+    let n = niveau.max(1) as i64;
+    XP_STEP * n * (n - 1)
+}
+
+pub fn level_for_xp(experience: i64) -> i32 {
+    let mut niveau = 1;
+    while niveau < NIVEAU_MAX && experience >= xp_threshold(niveau + 1) {
+        niveau += 1;
+    }
+    niveau
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LevelGains {
+    pub niveau: i32,
+    pub xp_requis: i64,
+    /// Characteristics among which the level point can be spent
+    pub caracteristiques_au_choix: Vec<String>,
+    pub points_caracteristique: i32,
+    /// PV gained, rolled by the player
+    pub de_pv: String,
+    /// PM gained by characters using astral energy
+    pub de_pm: Option<String>,
+    pub competences: i32,
+}
+
+pub fn gains_for_level(niveau: i32, lanceur_de_sorts: bool, regles: &ReglesNiveaux) -> LevelGains {
+    let caracs = if niveau % 2 == 0 {
+        &regles.caracs_niveau_pair
+    } else {
+        &regles.caracs_niveau_impair
+    };
+
+    LevelGains {
+        niveau,
+        xp_requis: xp_threshold(niveau),
+        caracteristiques_au_choix: caracs.clone(),
+        points_caracteristique: regles.points_caracteristique,
+        de_pv: regles.de_pv.clone(),
+        de_pm: lanceur_de_sorts.then(|| regles.de_pm.clone()),
+        competences: if regles.niveaux_competence.contains(&niveau) {
+            1
+        } else {
+            0
+        },
+    }
+}
+
+/// Highest roll of a "1D6"-style die, 6 when it cannot be read.
+fn max_de(de: &str) -> i32 {
+    let (nombre, faces) = de
+        .to_uppercase()
+        .split_once('D')
+        .map(|(n, f)| (n.trim().parse().unwrap_or(1), f.trim().parse().unwrap_or(6)))
+        .unwrap_or((1, 6));
+    nombre * faces
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressionStatus {
+    pub niveau: i32,
+    /// Last level whose gains went through `apply_level_up`
+    pub niveau_applique: i32,
+    pub experience: i64,
+    /// Level the current experience allows
+    pub niveau_atteint: i32,
+    pub xp_prochain_niveau: Option<i64>,
+    /// Level-ups earned but not applied yet, in order
+    pub niveaux_en_attente: Vec<LevelGains>,
+}
+
+fn is_spellcaster(data: &Value) -> bool {
+    read_i64(data, "/vitals/pm/max") > 0
+}
+
+pub fn progression_status(data: &Value, regles: &ReglesNiveaux) -> ProgressionStatus {
+    let niveau = (read_i64(data, "/general/niveau") as i32).max(1);
+    // Sheets from before the field: levels reached so far were handled by hand
+    let niveau_applique = data
+        .pointer("/general/niveau_applique")
+        .map(|_| read_i64(data, "/general/niveau_applique") as i32)
+        .unwrap_or(niveau)
+        .max(1);
+    let experience = read_i64(data, "/general/experience");
+    let niveau_atteint = level_for_xp(experience);
+    let lanceur = is_spellcaster(data);
+
+    ProgressionStatus {
+        niveau,
+        niveau_applique,
+        experience,
+        niveau_atteint,
+        xp_prochain_niveau: (niveau_atteint < NIVEAU_MAX).then(|| xp_threshold(niveau_atteint + 1)),
+        niveaux_en_attente: ((niveau_applique + 1)..=niveau_atteint)
+            .map(|n| gains_for_level(n, lanceur, regles))
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelUpChoices {
+    pub caracteristique: String,
+    pub pv: i32,
+    #[serde(default)]
+    pub pm: Option<i32>,
+    #[serde(default)]
+    pub competences: Vec<String>,
+}
+
+fn validate_choices(gains: &LevelGains, choices: &LevelUpChoices) -> Result<(), String> {
+    if !gains
+        .caracteristiques_au_choix
+        .contains(&choices.caracteristique)
+    {
+        return Err(format!(
+            "Au niveau {}, le point va dans: {}",
+            gains.niveau,
+            gains.caracteristiques_au_choix.join(", ")
+        ));
+    }
+    if !(1..=max_de(&gains.de_pv)).contains(&choices.pv) {
+        return Err(format!(
+            "Le gain de PV doit être un jet de {} (1 à {})",
+            gains.de_pv,
+            max_de(&gains.de_pv)
+        ));
+    }
+    match (&gains.de_pm, choices.pm) {
+        (Some(de), Some(pm)) if !(1..=max_de(de)).contains(&pm) => {
+            return Err(format!(
+                "Le gain de PM doit être un jet de {} (1 à {})",
+                de,
+                max_de(de)
+            ))
+        }
+        (Some(_), None) => return Err("Le gain de PM est manquant".to_string()),
+        (None, Some(_)) => return Err("Ce personnage ne gagne pas de PM".to_string()),
+        _ => {}
+    }
+    if choices.competences.len() != gains.competences as usize {
+        return Err(format!(
+            "{} compétence(s) à choisir au niveau {}",
+            gains.competences, gains.niveau
+        ));
+    }
+    Ok(())
+}
+
+/// Applies validated choices to the sheet. Competences are looked up in
+/// `competences.json` so the sheet gets the same entries as the picker.
+pub fn apply_choices(
+    data: &mut Value,
+    gains: &LevelGains,
+    choices: &LevelUpChoices,
+    catalogue: &[Competence],
+) -> Result<(), String> {
+    validate_choices(gains, choices)?;
+
+    let mut nouvelles = Vec::new();
+    for nom in &choices.competences {
+        let competence = catalogue
+            .iter()
+            .find(|c| &c.nom == nom)
+            .ok_or_else(|| format!("Compétence inconnue: {}", nom))?;
+        let deja_acquise = data
+            .get("competences")
+            .and_then(|c| c.as_array())
+            .is_some_and(|list| {
+                list.iter()
+                    .any(|c| c.get("nom") == Some(&Value::from(nom.as_str())))
+            });
+        if deja_acquise {
+            return Err(format!("Compétence déjà acquise: {}", nom));
+        }
+        nouvelles.push(serde_json::json!({
+            "id": uuid::Uuid::new_v4().to_string(),
+            "nom": competence.nom,
+            "description": competence.description,
+            "tableau": competence.tableau,
+        }));
+    }
+
+    add_i64(
+        data,
+        &format!("/characteristics/{}/naturel", choices.caracteristique),
+        gains.points_caracteristique as i64,
+    );
+    add_i64(data, "/vitals/pv/max", choices.pv as i64);
+    add_i64(data, "/vitals/pv/current", choices.pv as i64);
+    if let Some(pm) = choices.pm {
+        add_i64(data, "/vitals/pm/max", pm as i64);
+        add_i64(data, "/vitals/pm/current", pm as i64);
+    }
+    if !nouvelles.is_empty() {
+        if !data.get("competences").is_some_and(|c| c.is_array()) {
+            data["competences"] = Value::Array(Vec::new());
+        }
+        if let Some(list) = data["competences"].as_array_mut() {
+            list.extend(nouvelles);
+        }
+    }
+    set_value(data, "/general/niveau_applique", Value::from(gains.niveau));
+    // Normally already there through the header's auto-level
+    if read_i64(data, "/general/niveau") < gains.niveau as i64 {
+        set_value(data, "/general/niveau", Value::from(gains.niveau));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_progression(id: String, state: State<AppState>) -> Result<ProgressionStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    Ok(progression_status(&data, &regles_niveaux()?))
+}
+
+fn add_experience_to(
+    db: &rusqlite::Connection,
+    id: &str,
+    amount: i64,
+) -> Result<ProgressionStatus, String> {
+    let mut data = load_personnage_data(db, id)?;
+    let experience = (read_i64(&data, "/general/experience") + amount).max(0);
+    set_value(&mut data, "/general/experience", Value::from(experience));
+    store_personnage_data(db, id, &data)?;
+    Ok(progression_status(&data, &regles_niveaux()?))
+}
+
+/// Adds (or removes) experience. The level is not raised here: pending
+/// level-ups are returned and must go through `apply_level_up`.
+#[tauri::command]
+pub fn add_experience(
+    id: String,
+    amount: i64,
+    state: State<AppState>,
+) -> Result<ProgressionStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    add_experience_to(&db, &id, amount)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CraftAction {
+    Confection,
+    Reparation,
+}
+
+/// Grants the `xp_confection` / `xp_reparation` of a reference item.
+#[tauri::command]
+pub fn add_craft_experience(
+    id: String,
+    ref_item_id: i64,
+    action: CraftAction,
+    state: State<AppState>,
+) -> Result<ProgressionStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+
+    let craft_str: String = db
+        .query_row(
+            "SELECT craft FROM ref_items WHERE id = ?1",
+            params![ref_item_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Objet introuvable: {}", e))?;
+    let craft: Value = serde_json::from_str(&craft_str).unwrap_or_default();
+
+    let key = match action {
+        CraftAction::Confection => "xp_confection",
+        CraftAction::Reparation => "xp_reparation",
+    };
+    let amount = match craft.get(key) {
+        Some(Value::String(s)) => s.trim().parse::<i64>().unwrap_or(0),
+        Some(v) => v.as_i64().unwrap_or(0),
+        None => 0,
+    };
+
+    add_experience_to(&db, &id, amount)
+}
+
+/// Applies the next pending level and records the choices made.
+#[tauri::command]
+pub fn apply_level_up(
+    id: String,
+    choices: LevelUpChoices,
+    state: State<AppState>,
+) -> Result<ProgressionStatus, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let mut data = load_personnage_data(&tx, &id)?;
+    let regles = regles_niveaux()?;
+    let status = progression_status(&data, &regles);
+    let gains = status
+        .niveaux_en_attente
+        .first()
+        .cloned()
+        .ok_or_else(|| "Aucun passage de niveau en attente".to_string())?;
+
+    let catalogue = get_competences()?;
+    apply_choices(&mut data, &gains, &choices, &catalogue)?;
+    let applied_at = store_personnage_data(&tx, &id, &data)?;
+
+    tx.execute(
+        "INSERT INTO personnages_level_ups (personnage_id, niveau, choices, applied_at) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            id,
+            gains.niveau,
+            serde_json::to_string(&choices).map_err(|e| e.to_string())?,
+            applied_at
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(progression_status(&data, &regles))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LevelUpRecord {
+    pub niveau: i32,
+    pub choices: LevelUpChoices,
+    pub applied_at: String,
+}
+
+#[tauri::command]
+pub fn get_level_up_history(
+    id: String,
+    state: State<AppState>,
+) -> Result<Vec<LevelUpRecord>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare("SELECT niveau, choices, applied_at FROM personnages_level_ups WHERE personnage_id = ?1 ORDER BY niveau ASC")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![id], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut history = Vec::new();
+    for row in rows {
+        let (niveau, choices, applied_at) = row.map_err(|e| e.to_string())?;
+        history.push(LevelUpRecord {
+            niveau,
+            choices: serde_json::from_str(&choices).map_err(|e| e.to_string())?,
+            applied_at,
+        });
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xp_thresholds() {
+        assert_eq!(xp_threshold(1), 0);
+        assert_eq!(xp_threshold(2), 100);
+        assert_eq!(xp_threshold(5), 1000);
+        assert_eq!(level_for_xp(99), 1);
+        assert_eq!(level_for_xp(100), 2);
+        assert_eq!(level_for_xp(650), 4);
+    }
+
+    #[test]
+    fn test_pending_levels_detected() {
+        let regles = regles_niveaux().unwrap();
+        let data = serde_json::json!({
+            "general": { "niveau": 1, "experience": 320 },
+            "vitals": { "pm": { "max": 0 } }
+        });
+        let status = progression_status(&data, &regles);
+        assert_eq!(status.niveau_atteint, 3);
+        assert_eq!(status.niveaux_en_attente.len(), 2);
+        assert_eq!(status.niveaux_en_attente[1].competences, 1);
+        assert_eq!(status.niveaux_en_attente[0].de_pm, None);
+
+        // The header already moved `niveau` along with the experience
+        let data = serde_json::json!({
+            "general": { "niveau": 3, "niveau_applique": 1, "experience": 320 }
+        });
+        let status = progression_status(&data, &regles);
+        assert_eq!(status.niveau_applique, 1);
+        assert_eq!(status.niveaux_en_attente.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_choices_updates_sheet() {
+        let mut data = serde_json::json!({
+            "general": { "niveau": 1, "experience": 100 },
+            "vitals": { "pv": { "current": 20, "max": 30, "temp": 0 }, "pm": { "current": 0, "max": 0, "temp": 0 } },
+            "characteristics": { "force": { "naturel": 12, "t1": 0, "t2": 0, "t3": 0 } },
+            "competences": []
+        });
+        let regles = regles_niveaux().unwrap();
+        let gains = gains_for_level(2, false, &regles);
+        let choices = LevelUpChoices {
+            caracteristique: "force".to_string(),
+            pv: 4,
+            pm: None,
+            competences: vec![],
+        };
+
+        apply_choices(&mut data, &gains, &choices, &[]).unwrap();
+        assert_eq!(data["characteristics"]["force"]["naturel"], 13);
+        assert_eq!(data["vitals"]["pv"]["max"], 34);
+        assert_eq!(data["general"]["niveau"], 2);
+        assert_eq!(data["general"]["niveau_applique"], 2);
+        assert!(apply_choices(
+            &mut data,
+            &gains,
+            &LevelUpChoices {
+                pv: 7,
+                ..choices.clone()
+            },
+            &[]
+        )
+        .is_err());
+
+        let wrong = LevelUpChoices {
+            caracteristique: "attaque".to_string(),
+            ..choices
+        };
+        assert!(
+            apply_choices(&mut data, &gains_for_level(4, false, &regles), &wrong, &[]).is_err()
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::Deserialize;
 use std::fs;
 use std::path::Path;
-use tauri::AppHandle; // Import AppHandle to access app-specific paths if needed
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
 
 #[derive(Deserialize, Debug)]
 struct SourceItem {
@@ -16,8 +17,18 @@ struct SourceItem {
     rupture: Option<String>,
     #[serde(alias = "pr_sol")]
     pr: Option<String>,
-    pr_mag: Option<String>, // ADD THIS
-    pr_spe: Option<String>, // ADD THIS
+    pr_mag: Option<String>,     // ADD THIS
+    pr_spe: Option<String>,     // ADD THIS
+    capacite: Option<String>,   // Sacs, in grams
+    places: Option<String>,     // Sacoches
+    peremption: Option<String>, // Bouffes, e.g. "6 décades"
+    contenant: Option<String>,  // Potions
+    #[serde(rename = "cout_en_PA")]
+    cout_pa: Option<String>,
+    charge: Option<String>, // Objets_magiques
+    pluie: Option<String>,
+    froid: Option<String>,
+    chaleur: Option<String>,
     #[serde(alias = "type")]
     item_type: Option<String>, // Captures "type" from JSON
     effet: Option<String>,
@@ -32,36 +43,180 @@ struct SourceItem {
     perception: Option<String>,
     attaque: Option<String>,
     parade: Option<String>,
+    mvt: Option<String>,
+    mag_phy: Option<String>,
+    mag_psy: Option<String>,
+    rm: Option<String>,
+    discretion: Option<String>,
+
+    // Craft
+    xp_confection: Option<String>,
+    xp_reparation: Option<String>,
 }
 
-pub fn seed_reference_data(conn: &mut Connection, _app_handle: AppHandle) -> Result<(), String> {
-    let count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM ref_items", [], |row| row.get(0))
+/// Seeds from the item tables bundled as app resources.
+pub fn seed_reference_data(conn: &mut Connection, app_handle: AppHandle) -> Result<(), String> {
+    let base_path = app_handle
+        .path()
+        .resolve("data/items", BaseDirectory::Resource)
         .map_err(|e| e.to_string())?;
-    if count > 0 {
-        return Ok(()); // Already seeded
-    }
+    seed_from_dir(conn, &base_path)
+}
 
-    let base_path = Path::new("data/items");
-
-    let categories = vec![
-        ("Mains_nues.json", "Mains_nues"), // Fixed category name
-        ("Armes.json", "Armes"),
-        ("Protections.json", "Protections"),
-        ("Accessoires.json", "Accessoires"),
-        ("Sacs.json", "Sacs"),
-        ("Sacoches.json", "Sacoches"),
-        ("Potions.json", "Potions"),
-        ("Outils.json", "Outils"),
-        ("Munitions.json", "Munitions"),
-        ("Armes_de_jet.json", "Armes_de_jet"),
-        ("Pieges.json", "Pieges"),
-        ("Objets_magiques.json", "Objets_magiques"),
-    ];
+/// Bumped whenever the bundled tables gain fields, so that a database seeded by
+/// an earlier build gets them too (see `backfill`).
+/// 1: `mvt`, `pluie`/`froid`/`chaleur`, `capacite`/`places`, `peremption`/
+///    `contenant`/`cout_pa`/`charge` and the Bouffes and Boissons tables
+/// 2: `xp_confection`/`xp_reparation` in `craft`
+const SEED_VERSION: i64 = 2;
 
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+/// (file, category, seed version that added the table)
+const CATEGORIES: [(&str, &str, i64); 14] = [
+    ("Mains_nues.json", "Mains_nues", 0), // Fixed category name
+    ("Armes.json", "Armes", 0),
+    ("Protections.json", "Protections", 0),
+    ("Accessoires.json", "Accessoires", 0),
+    ("Sacs.json", "Sacs", 0),
+    ("Sacoches.json", "Sacoches", 0),
+    ("Potions.json", "Potions", 0),
+    ("Outils.json", "Outils", 0),
+    ("Munitions.json", "Munitions", 0),
+    ("Armes_de_jet.json", "Armes_de_jet", 0),
+    ("Pieges.json", "Pieges", 0),
+    ("Objets_magiques.json", "Objets_magiques", 0),
+    ("Bouffes.json", "Bouffes", 1),
+    ("Boissons.json", "Boissons", 1),
+];
+
+/// A `ref_items` row built from a bundled table.
+struct SeedRow {
+    category: &'static str,
+    /// Seed version that added its table
+    since: i64,
+    ref_id: i32,
+    nom: String,
+    degats: serde_json::Value,
+    caracteristiques: serde_json::Value,
+    protections: serde_json::Value,
+    prix_info: serde_json::Value,
+    craft: serde_json::Value,
+    details: serde_json::Value,
+}
+
+impl SeedRow {
+    fn from_source(category: &'static str, since: i64, item: SourceItem) -> Self {
+        let ref_id: i32 = item.id.trim().parse().unwrap_or(0);
+        let nom = item.nom;
+
+        // Map legacy fields to new JSON structure
+        let degats = serde_json::json!({
+            "degats": item.degats.unwrap_or_default(),
+            "pi": item.pi.unwrap_or("0".to_string()).trim().parse::<i32>().unwrap_or(0)
+        });
+
+        // Characteristics
+        let mut caracs_map = serde_json::Map::new();
+        let char_fields = [
+            ("courage", &item.courage),
+            ("intelligence", &item.intelligence),
+            ("charisme", &item.charisme),
+            ("adresse", &item.adresse),
+            ("force", &item.force),
+            ("perception", &item.perception),
+            ("attaque", &item.attaque),
+            ("parade", &item.parade),
+            ("mvt", &item.mvt),
+            ("mag_phy", &item.mag_phy),
+            ("mag_psy", &item.mag_psy),
+            ("rm", &item.rm),
+            ("discretion", &item.discretion),
+        ];
+        for (key, val_opt) in char_fields {
+            if let Some(val_str) = val_opt {
+                if let Ok(val) = val_str.trim().parse::<i32>() {
+                    if val != 0 {
+                        caracs_map.insert(key.to_string(), serde_json::Value::Number(val.into()));
+                    }
+                }
+            }
+        }
+        if let Some(serde_json::Value::Object(obj)) = &item.caracteristiques {
+            for (k, v) in obj {
+                caracs_map.insert(k.clone(), v.clone());
+            }
+        }
+        let caracteristiques = serde_json::Value::Object(caracs_map);
 
-    for (filename, category) in categories {
+        // Protections
+        let protections = serde_json::json!({
+            "pr_sol": item.pr.unwrap_or("0".to_string()), // item.pr maps to pr_sol
+            "pr_mag": item.pr_mag.unwrap_or("0".to_string()),
+            "pr_spe": item.pr_spe.unwrap_or("0".to_string()),
+            "pluie": item.pluie.unwrap_or("0".to_string()),
+            "froid": item.froid.unwrap_or("0".to_string()),
+            "chaleur": item.chaleur.unwrap_or("0".to_string())
+        });
+
+        // Details (poids, aura, effet/description, rupture, esquive)
+        let details = serde_json::json!({
+            "poids": item.poids.unwrap_or("0".to_string()),
+            "aura": item.aura.unwrap_or_default(),
+            "effet": item.effet.unwrap_or_default(),
+            "type": item.item_type.unwrap_or_default(),
+            "rupture": item.rupture.unwrap_or_default(),
+            "esquive_bonus": item.esquive.unwrap_or("0".to_string()),
+            "capacite": item.capacite.unwrap_or_default(),
+            "places": item.places.unwrap_or_default(),
+            "peremption": item.peremption.unwrap_or_default(),
+            "contenant": item.contenant.unwrap_or_default(),
+            "cout_pa": item.cout_pa.unwrap_or("0".to_string()),
+            "charge": item.charge.unwrap_or("0".to_string())
+        });
+
+        let prix_info = serde_json::json!({}); // Empty for now
+        let craft = serde_json::json!({
+            "xp_confection": item.xp_confection.unwrap_or("0".to_string()).trim().parse::<i32>().unwrap_or(0),
+            "xp_reparation": item.xp_reparation.unwrap_or("0".to_string()).trim().parse::<i32>().unwrap_or(0)
+        });
+
+        SeedRow {
+            category,
+            since,
+            ref_id,
+            nom,
+            degats,
+            caracteristiques,
+            protections,
+            prix_info,
+            craft,
+            details,
+        }
+    }
+
+    fn insert(&self, conn: &Connection) -> Result<(), String> {
+        conn.execute(
+            "INSERT INTO ref_items (category, ref_id, nom, degats, caracteristiques, protections, prix_info, craft, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                self.category,
+                self.ref_id,
+                self.nom,
+                self.degats.to_string(),
+                self.caracteristiques.to_string(),
+                self.protections.to_string(),
+                self.prix_info.to_string(),
+                self.craft.to_string(),
+                self.details.to_string()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn read_rows(base_path: &Path) -> Result<Vec<SeedRow>, String> {
+    let mut rows = Vec::new();
+    for (filename, category, since) in CATEGORIES {
         let file_path = base_path.join(filename);
         if !file_path.exists() {
             println!("Warning: Seed file not found: {:?}", file_path);
@@ -70,83 +225,214 @@ pub fn seed_reference_data(conn: &mut Connection, _app_handle: AppHandle) -> Res
 
         let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
         let items: Vec<SourceItem> = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        rows.extend(
+            items
+                .into_iter()
+                .map(|item| SeedRow::from_source(category, since, item)),
+        );
+    }
+    Ok(rows)
+}
 
-        for item in items {
-            let ref_id: i32 = item.id.trim().parse().unwrap_or(0);
-            let nom = item.nom;
-            
-            // Map legacy fields to new JSON structure
-            let degats = serde_json::json!({
-                "degats": item.degats.unwrap_or_default(),
-                "pi": item.pi.unwrap_or("0".to_string()).trim().parse::<i32>().unwrap_or(0)
-            });
-
-            // Characteristics
-            let mut caracs_map = serde_json::Map::new();
-            let char_fields = [
-                ("courage", &item.courage),
-                ("intelligence", &item.intelligence),
-                ("charisme", &item.charisme),
-                ("adresse", &item.adresse),
-                ("force", &item.force),
-                ("perception", &item.perception),
-                ("attaque", &item.attaque),
-                ("parade", &item.parade),
-            ];
-            for (key, val_opt) in char_fields {
-                if let Some(val_str) = val_opt {
-                    if let Ok(val) = val_str.trim().parse::<i32>() {
-                        if val != 0 {
-                            caracs_map.insert(key.to_string(), serde_json::Value::Number(val.into()));
-                        }
-                    }
-                }
-            }
-             if let Some(serde_json::Value::Object(obj)) = &item.caracteristiques {
-                for (k, v) in obj {
-                    caracs_map.insert(k.clone(), v.clone());
-                }
-            }
-            let caracteristiques = serde_json::Value::Object(caracs_map);
-
-            // Protections
-            let protections = serde_json::json!({
-                "pr_sol": item.pr.unwrap_or("0".to_string()), // item.pr maps to pr_sol
-                "pr_mag": item.pr_mag.unwrap_or("0".to_string()),
-                "pr_spe": item.pr_spe.unwrap_or("0".to_string())
-            });
-
-            // Details (poids, aura, effet/description, rupture, esquive)
-            let details = serde_json::json!({
-                "poids": item.poids.unwrap_or("0".to_string()),
-                "aura": item.aura.unwrap_or_default(),
-                "effet": item.effet.unwrap_or_default(),
-                "type": item.item_type.unwrap_or_default(),
-                "rupture": item.rupture.unwrap_or_default(),
-                "esquive_bonus": item.esquive.unwrap_or("0".to_string())
-            });
-
-            let prix_info = serde_json::json!({}); // Empty for now
-            let craft = serde_json::json!({}); // Empty for now
-
-            tx.execute(
-                "INSERT INTO ref_items (category, ref_id, nom, degats, caracteristiques, protections, prix_info, craft, details)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-                params![
-                    category, 
-                    ref_id, 
-                    nom, 
-                    degats.to_string(), 
-                    caracteristiques.to_string(), 
-                    protections.to_string(), 
-                    prix_info.to_string(), 
-                    craft.to_string(), 
-                    details.to_string()
-                ],
-            ).map_err(|e| e.to_string())?;
-        }
+pub fn seed_from_dir(conn: &mut Connection, base_path: &Path) -> Result<(), String> {
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM ref_items", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let seeded: i64 = conn
+        .query_row(
+            "SELECT value FROM db_meta WHERE key = 'seed_version'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if count > 0 && seeded >= SEED_VERSION {
+        return Ok(()); // Already seeded
     }
 
+    let rows = read_rows(base_path)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for row in &rows {
+        if count == 0 {
+            row.insert(&tx)?;
+        } else {
+            // Rows of a table the earlier build did not have are inserted; in
+            // the other tables, a missing row was deleted and stays so
+            backfill(&tx, row, seeded < row.since)?;
+        }
+    }
+    tx.execute(
+        "INSERT OR REPLACE INTO db_meta (key, value) VALUES ('seed_version', ?1)",
+        params![SEED_VERSION.to_string()],
+    )
+    .map_err(|e| e.to_string())?;
     tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
+
+/// Brings a row seeded by an earlier build up to date, matched on
+/// (category, ref_id): keys it lacks are added, values already there are kept
+/// since they may come from `sync_ref_items` or an edit. A row missing
+/// altogether is inserted only with `insert_missing`.
+fn backfill(conn: &Connection, row: &SeedRow, insert_missing: bool) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, caracteristiques, protections, craft, details FROM ref_items
+             WHERE category = ?1 AND ref_id = ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let existing = stmt
+        .query_map(params![row.category, row.ref_id], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, String>(3)?,
+                r.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if existing.is_empty() && insert_missing {
+        return row.insert(conn);
+    }
+
+    for (id, caracteristiques, protections, craft, details) in existing {
+        conn.execute(
+            "UPDATE ref_items SET caracteristiques = ?1, protections = ?2, craft = ?3, details = ?4
+             WHERE id = ?5",
+            params![
+                merge_missing(&caracteristiques, &row.caracteristiques),
+                merge_missing(&protections, &row.protections),
+                merge_missing(&craft, &row.craft),
+                merge_missing(&details, &row.details),
+                id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Adds to the stored JSON object the keys of `fresh` it does not have.
+fn merge_missing(stored: &str, fresh: &serde_json::Value) -> String {
+    let mut merged = match serde_json::from_str::<serde_json::Value>(stored) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return fresh.to_string(),
+    };
+    if let Some(fresh) = fresh.as_object() {
+        for (key, value) in fresh {
+            merged.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    serde_json::Value::Object(merged).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    fn seeded_db(dir: &Path) -> Connection {
+        let mut conn = crate::migrations::test_db();
+        seed_from_dir(&mut conn, dir).unwrap();
+        conn
+    }
+
+    fn column(conn: &Connection, name: &str, column: &str) -> serde_json::Value {
+        let value: String = conn
+            .query_row(
+                &format!("SELECT {} FROM ref_items WHERE nom = ?1", column),
+                [name],
+                |row| row.get(0),
+            )
+            .unwrap();
+        serde_json::from_str(&value).unwrap()
+    }
+
+    #[test]
+    fn test_older_seed_is_backfilled() {
+        let dir = test_dir();
+        std::fs::write(
+            dir.join("Protections.json"),
+            r#"[{ "id": "1", "nom": "Cape", "pr_sol": "1", "pluie": "2", "mvt": "-1" }]"#,
+        )
+        .unwrap();
+        let mut conn = seeded_db(&dir);
+
+        // As left by a build from before the weather and movement fields,
+        // with a value since changed by a sync
+        conn.execute_batch(
+            "UPDATE ref_items SET protections = '{\"pr_sol\":\"3\"}', caracteristiques = '{}';
+             DELETE FROM db_meta WHERE key = 'seed_version';",
+        )
+        .unwrap();
+        seed_from_dir(&mut conn, &dir).unwrap();
+
+        let protections = column(&conn, "Cape", "protections");
+        assert_eq!(protections["pr_sol"], "3");
+        assert_eq!(protections["pluie"], "2");
+        assert_eq!(column(&conn, "Cape", "caracteristiques")["mvt"], -1);
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM ref_items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Tables added since are inserted whole
+        std::fs::write(
+            dir.join("Bouffes.json"),
+            r#"[{ "id": "1", "nom": "Pain", "peremption": "3 jours" }]"#,
+        )
+        .unwrap();
+        conn.execute("DELETE FROM db_meta WHERE key = 'seed_version'", [])
+            .unwrap();
+        seed_from_dir(&mut conn, &dir).unwrap();
+        assert_eq!(column(&conn, "Pain", "details")["peremption"], "3 jours");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_craft_backfilled_and_deleted_rows_stay_deleted() {
+        let dir = test_dir();
+        std::fs::write(
+            dir.join("Armes.json"),
+            r#"[{ "id": "1", "nom": "Hache", "xp_confection": "4", "xp_reparation": "2" },
+                { "id": "2", "nom": "Gourdin" }]"#,
+        )
+        .unwrap();
+        let mut conn = seeded_db(&dir);
+
+        // Seeded by the build before the craft experience, then the user
+        // deleted a reference item
+        conn.execute_batch(
+            "UPDATE ref_items SET craft = '{}';
+             DELETE FROM ref_items WHERE nom = 'Gourdin';
+             UPDATE db_meta SET value = '1' WHERE key = 'seed_version';",
+        )
+        .unwrap();
+        seed_from_dir(&mut conn, &dir).unwrap();
+
+        let craft = column(&conn, "Hache", "craft");
+        assert_eq!(
+            (
+                craft["xp_confection"].as_i64(),
+                craft["xp_reparation"].as_i64()
+            ),
+            (Some(4), Some(2))
+        );
+        let gourdins: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM ref_items WHERE nom = 'Gourdin'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(gourdins, 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
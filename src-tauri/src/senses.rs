@@ -0,0 +1,328 @@
+use crate::alcohol::alcohol_modifiers;
+use crate::commands::origines;
+use crate::db::{load_personnage_data, AppState};
+use crate::logic::{Etats, StatComponent};
+use crate::sheet::{read_i64, read_str};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+/// Acuity of a sense, from the options of the status panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Acuite {
+    Surdeveloppee,
+    Developpee,
+    Normale,
+    Faible,
+    /// Aveugle, Sourd, Anosmique
+    Absente,
+    /// Hearing only
+    Ultrason,
+}
+
+impl Acuite {
+    fn from_label(label: &str) -> Self {
+        let label = label.trim();
+        if label.starts_with("Surdéveloppée") {
+            Acuite::Surdeveloppee
+        } else if label.starts_with("Développée") {
+            Acuite::Developpee
+        } else if label.starts_with("Faible") || label.starts_with("Mauvaise") {
+            Acuite::Faible
+        } else if label.starts_with("Aveugle") || label == "Sourd" || label == "Anosmique" {
+            Acuite::Absente
+        } else if label == "Ultrason" {
+            Acuite::Ultrason
+        } else {
+            Acuite::Normale
+        }
+    }
+
+    fn modificateur(&self) -> i32 {
+        match self {
+            Acuite::Ultrason => 3,
+            Acuite::Surdeveloppee => 2,
+            Acuite::Developpee => 1,
+            Acuite::Normale | Acuite::Absente => 0,
+            Acuite::Faible => -2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Nyctalopie {
+    Aucune,
+    Moyenne,
+    Totale,
+}
+
+impl Nyctalopie {
+    fn from_label(label: &str) -> Self {
+        if label.contains("nyctalopie totale") {
+            Nyctalopie::Totale
+        } else if label.contains("nyctalopie moyenne") {
+            Nyctalopie::Moyenne
+        } else {
+            Nyctalopie::Aucune
+        }
+    }
+}
+
+/// Night vision every member of an origin has, even if the sheet was filled without it,
+/// from `Nyctalopie` in `origines.json`. The sheet holds either name of the origin.
+fn nyctalopie_origine(origine: &str) -> Nyctalopie {
+    let origines = origines().unwrap_or_default();
+    let entree = origines
+        .iter()
+        .find(|o| o.name_m == origine || o.name_f == origine);
+    match entree.and_then(|o| o.nyctalopie.as_deref()) {
+        Some("Totale") => Nyctalopie::Totale,
+        Some("Moyenne") => Nyctalopie::Moyenne,
+        _ => Nyctalopie::Aucune,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Senses {
+    pub vue: Acuite,
+    pub nyctalopie: Nyctalopie,
+    pub ouie: Acuite,
+    pub odorat: Acuite,
+    pub humectation: String,
+    pub sentir_danger: i64,
+}
+
+impl Senses {
+    pub fn depuis_fiche(data: &Value) -> Self {
+        let vue = read_str(data, "/status/senses/vue");
+        let origine = read_str(data, "/identity/origine");
+        Senses {
+            vue: Acuite::from_label(if vue.is_empty() { "Normale" } else { vue }),
+            nyctalopie: Nyctalopie::from_label(vue).max(nyctalopie_origine(origine)),
+            ouie: Acuite::from_label(read_str(data, "/status/senses/ouie")),
+            odorat: Acuite::from_label(read_str(data, "/status/senses/odorat")),
+            humectation: read_str(data, "/status/senses/humectation").to_string(),
+            sentir_danger: read_i64(data, "/status/senses/sentir_danger"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sens {
+    Vue,
+    Ouie,
+    Odorat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lumiere {
+    #[default]
+    Jour,
+    Penombre,
+    Nuit,
+    NoirTotal,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bruit {
+    #[default]
+    Calme,
+    Bruyant,
+    Vacarme,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PerceptionSituation {
+    pub sens: Sens,
+    #[serde(default)]
+    pub lumiere: Lumiere,
+    #[serde(default)]
+    pub bruit: Bruit,
+    /// Free modifier decided by the GM (distance, camouflage...)
+    #[serde(default)]
+    pub modificateur_mj: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerceptionModifiers {
+    pub total: i32,
+    /// The sense used is missing or fully blocked: the test fails
+    pub impossible: bool,
+    pub components: Vec<StatComponent>,
+}
+
+fn malus_lumiere(lumiere: Lumiere, nyctalopie: Nyctalopie) -> Option<i32> {
+    match (lumiere, nyctalopie) {
+        (Lumiere::Jour, _) => Some(0),
+        (Lumiere::Penombre, Nyctalopie::Aucune) => Some(-2),
+        (Lumiere::Penombre, _) => Some(0),
+        (Lumiere::Nuit, Nyctalopie::Aucune) => Some(-5),
+        (Lumiere::Nuit, Nyctalopie::Moyenne) => Some(-2),
+        (Lumiere::Nuit, Nyctalopie::Totale) => Some(0),
+        (Lumiere::NoirTotal, Nyctalopie::Totale) => Some(-2),
+        (Lumiere::NoirTotal, _) => None,
+    }
+}
+
+fn malus_bruit(bruit: Bruit, ouie: Acuite) -> i32 {
+    match (bruit, ouie) {
+        (Bruit::Calme, _) => 0,
+        (Bruit::Bruyant, _) => -2,
+        // Sharp ears suffer more from a deafening noise
+        (Bruit::Vacarme, Acuite::Ultrason | Acuite::Surdeveloppee) => -7,
+        (Bruit::Vacarme, _) => -5,
+    }
+}
+
+fn malus_humectation(humectation: &str) -> i32 {
+    match humectation {
+        "Trempé" | "Desséché" => -1,
+        "Frigorifié" | "Calciné" => -2,
+        _ => 0,
+    }
+}
+
+pub fn perception_modifiers(data: &Value, situation: &PerceptionSituation) -> PerceptionModifiers {
+    let senses = Senses::depuis_fiche(data);
+    let mut components = Vec::new();
+    let mut impossible = false;
+    let mut push = |label: String, value: i32| {
+        if value != 0 {
+            components.push(StatComponent { label, value });
+        }
+    };
+
+    let acuite = match situation.sens {
+        Sens::Vue => senses.vue,
+        Sens::Ouie => senses.ouie,
+        Sens::Odorat => senses.odorat,
+    };
+    if acuite == Acuite::Absente {
+        impossible = true;
+    }
+    push(format!("Acuité ({:?})", acuite), acuite.modificateur());
+
+    match situation.sens {
+        Sens::Vue => match malus_lumiere(situation.lumiere, senses.nyctalopie) {
+            Some(malus) => push(format!("Lumière ({:?})", situation.lumiere), malus),
+            None => impossible = true,
+        },
+        Sens::Ouie => push(
+            format!("Bruit ({:?})", situation.bruit),
+            malus_bruit(situation.bruit, senses.ouie),
+        ),
+        Sens::Odorat => {}
+    }
+
+    push(
+        format!("Humectation ({})", senses.humectation),
+        malus_humectation(&senses.humectation),
+    );
+
+    let alcool = alcohol_modifiers(data);
+    push("Alcool (léger)".to_string(), alcool.leger.perception);
+    push("Alcool (fort)".to_string(), alcool.fort.perception);
+    push(
+        "Gueule de bois".to_string(),
+        alcool.gueule_de_bois.perception,
+    );
+
+    let etats = Etats::depuis_fiche(data);
+    push("Malus Tête".to_string(), -etats.blessure_tete);
+    push("Manque (Drogue)".to_string(), -etats.drogue);
+    push("Modificateur MJ".to_string(), situation.modificateur_mj);
+
+    PerceptionModifiers {
+        total: components.iter().map(|c| c.value).sum(),
+        impossible,
+        components,
+    }
+}
+
+#[tauri::command]
+pub fn get_senses(id: String, state: State<AppState>) -> Result<Senses, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    Ok(Senses::depuis_fiche(&data))
+}
+
+#[tauri::command]
+pub fn perception_test_modifiers(
+    id: String,
+    situation: PerceptionSituation,
+    state: State<AppState>,
+) -> Result<PerceptionModifiers, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let data = load_personnage_data(&db, &id)?;
+    Ok(perception_modifiers(&data, &situation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn situation(sens: Sens, lumiere: Lumiere) -> PerceptionSituation {
+        PerceptionSituation {
+            sens,
+            lumiere,
+            bruit: Bruit::Calme,
+            modificateur_mj: 0,
+        }
+    }
+
+    #[test]
+    fn test_origin_night_vision_in_darkness() {
+        let humain = serde_json::json!({
+            "identity": { "origine": "Humain" },
+            "status": { "senses": { "vue": "Normale" } }
+        });
+        let nain = serde_json::json!({
+            "identity": { "origine": "Nain" },
+            "status": { "senses": { "vue": "Normale" } }
+        });
+
+        let nuit = situation(Sens::Vue, Lumiere::Nuit);
+        assert_eq!(perception_modifiers(&humain, &nuit).total, -5);
+        assert_eq!(perception_modifiers(&nain, &nuit).total, -2);
+        assert!(
+            perception_modifiers(&humain, &situation(Sens::Vue, Lumiere::NoirTotal)).impossible
+        );
+    }
+
+    #[test]
+    fn test_feminine_origin_names() {
+        assert_eq!(nyctalopie_origine("Naine Duregar"), Nyctalopie::Totale);
+        assert_eq!(nyctalopie_origine("Profonde"), Nyctalopie::Totale);
+        assert_eq!(nyctalopie_origine("Draque"), Nyctalopie::Moyenne);
+        assert_eq!(nyctalopie_origine("Naine de la Mafia"), Nyctalopie::Moyenne);
+        assert_eq!(nyctalopie_origine("Humaine"), Nyctalopie::Aucune);
+        for origine in origines().unwrap() {
+            if let Some(valeur) = origine.nyctalopie.as_deref() {
+                assert!(matches!(valeur, "Moyenne" | "Totale"), "{}", valeur);
+            }
+        }
+    }
+
+    #[test]
+    fn test_impairments_stack() {
+        let data = serde_json::json!({
+            "general": { "malus_tete": 1 },
+            "status": {
+                "senses": { "ouie": "Développée" },
+                "alcohol": { "leger": 5, "fort": 0, "gueule_de_bois": 0 }
+            }
+        });
+        let mut noisy = situation(Sens::Ouie, Lumiere::Jour);
+        noisy.bruit = Bruit::Bruyant;
+
+        let mods = perception_modifiers(&data, &noisy);
+        // +1 acuity, -2 noise, -1 alcohol, -1 head injury
+        assert_eq!(mods.total, -3);
+        assert!(!mods.impossible);
+    }
+}
@@ -0,0 +1,80 @@
+use serde_json::Value;
+
+// Helpers to read and patch the character JSON from Rust. Numbers typed in the
+// UI may come back as floats or strings, so reads are lenient.
+
+pub fn read_i64(data: &Value, pointer: &str) -> i64 {
+    match data.pointer(pointer) {
+        Some(Value::String(s)) => s.trim().parse::<f64>().map(|f| f as i64).unwrap_or(0),
+        Some(v) => v
+            .as_i64()
+            .or_else(|| v.as_f64().map(|f| f as i64))
+            .unwrap_or(0),
+        None => 0,
+    }
+}
+
+pub fn read_str<'a>(data: &'a Value, pointer: &str) -> &'a str {
+    data.pointer(pointer).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// Sets the value at `pointer`, creating missing intermediate objects.
+pub fn set_value(data: &mut Value, pointer: &str, value: Value) {
+    let mut current = data;
+    for key in pointer.split('/').skip(1) {
+        if !current.is_object() {
+            *current = Value::Object(serde_json::Map::new());
+        }
+        current = current
+            .as_object_mut()
+            .expect("just made an object")
+            .entry(key.to_string())
+            .or_insert(Value::Null);
+    }
+    *current = value;
+}
+
+pub fn add_i64(data: &mut Value, pointer: &str, delta: i64) -> i64 {
+    let value = read_i64(data, pointer) + delta;
+    set_value(data, pointer, Value::from(value));
+    value
+}
+
+/// Typed list stored at `pointer`, empty when missing or null. Lists read this
+/// way are written back whole, so a malformed entry is an error rather than an
+/// empty list that would erase them.
+pub fn read_list<T: serde::de::DeserializeOwned>(
+    data: &Value,
+    pointer: &str,
+) -> Result<Vec<T>, serde_json::Error> {
+    match data.pointer(pointer) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(list) => serde_json::from_value(list.clone()),
+    }
+}
+
+/// Serde helper for sheet numbers that may have been saved as strings. Unlike
+/// `read_i64`, anything but a number, a numeric string, an empty field or null
+/// is an error, so that a corrupted value is not written back as 0.
+pub fn lenient_i64<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    match <Value as serde::Deserialize>::deserialize(deserializer)? {
+        Value::Null => Ok(0),
+        Value::String(s) if s.trim().is_empty() => Ok(0),
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite())
+            .map(|f| f as i64)
+            .ok_or_else(|| D::Error::custom(format!("nombre attendu, pas \"{}\"", s))),
+        Value::Number(n) => n
+            .as_i64()
+            .or_else(|| n.as_f64().map(|f| f as i64))
+            .ok_or_else(|| D::Error::custom(format!("nombre hors limites: {}", n))),
+        other => Err(D::Error::custom(format!("nombre attendu, pas {}", other))),
+    }
+}
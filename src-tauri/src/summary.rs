@@ -0,0 +1,398 @@
+use crate::combat::DegatsArme;
+use crate::commands::{get_competences, get_game_rules, GameRules};
+use crate::db::{load_personnage_data, load_ref_items, RefEquipement};
+use crate::equipement::{pieces_portees, pr_solide_totale};
+use crate::inventory::{quantite_objet, reference};
+use crate::logic::{calculer_stats_magiques, caracteristiques_equipees};
+use crate::richesse::{Monnaies, CURRENCIES, LOCATIONS};
+use crate::sheet::{read_i64, read_str};
+use crate::vitals::{read_jauge, Jauge, Pool};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// The character sheet with its computed values, as printed or shared outside the
+// app. Each export renders it its own way.
+
+pub const CARACTERISTIQUES: [(&str, &str); 10] = [
+    ("courage", "Courage"),
+    ("intelligence", "Intelligence"),
+    ("charisme", "Charisme"),
+    ("adresse", "Adresse"),
+    ("force", "Force"),
+    ("perception", "Perception"),
+    ("esquive", "Esquive"),
+    ("attaque", "Attaque"),
+    ("parade", "Parade"),
+    ("degats", "Dégâts"),
+];
+
+pub const MONNAIES: [&str; 5] = ["Béryllium", "Thritil", "Or", "Argent", "Cuivre"];
+pub const LIEUX: [&str; 4] = ["Sur soi", "Banque", "Maison", "Commun"];
+
+const EQUIPEMENT_PORTE: [&str; 4] = ["Armes", "MainsNues", "Protections", "Accessoires"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identite {
+    pub nom: String,
+    pub sexe: String,
+    pub origine: String,
+    pub metier: String,
+    pub specialisation: String,
+    pub sous_specialisation: String,
+    pub domaine: String,
+    pub description: String,
+    pub niveau: i64,
+    pub experience: i64,
+    pub points_destin: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LigneCaracteristique {
+    pub nom: String,
+    pub naturel: i64,
+    /// Value in the "equipped" column
+    pub finale: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LigneValeur {
+    pub nom: String,
+    pub valeur: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmeFiche {
+    pub nom: String,
+    /// Total damage, as on the sheet
+    pub degats: String,
+    /// Strength bonus included in `degats`
+    pub bonus_force: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionFiche {
+    pub nom: String,
+    pub pr_sol: i64,
+    pub pr_spe: i64,
+    pub pr_mag: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompetenceFiche {
+    pub nom: String,
+    /// "Spécialisation", "Sous-spécialisation" or empty for a base competence
+    pub rang: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjetSac {
+    pub nom: String,
+    pub quantite: i64,
+    /// Nesting depth, 0 for what is directly in the inventory
+    pub niveau: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LigneMonnaie {
+    pub monnaie: String,
+    /// In `LIEUX` order
+    pub montants: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fiche {
+    pub identite: Identite,
+    pub caracteristiques: Vec<LigneCaracteristique>,
+    pub defenses: Vec<LigneValeur>,
+    pub magie: Vec<LigneValeur>,
+    pub pv: Jauge,
+    pub pm: Jauge,
+    pub corruption: i64,
+    pub armes: Vec<ArmeFiche>,
+    pub protections: Vec<ProtectionFiche>,
+    pub competences: Vec<CompetenceFiche>,
+    pub sac: Vec<ObjetSac>,
+    /// Currencies the character owns somewhere
+    pub richesse: Vec<LigneMonnaie>,
+}
+
+fn nom_objet(item: &Value, refs: &[RefEquipement]) -> String {
+    reference(item, refs)
+        .map(|r| r.nom.clone())
+        .unwrap_or_else(|| read_str(item, "/nom").to_string())
+}
+
+fn objets_sac(items: &[Value], refs: &[RefEquipement], niveau: usize, sac: &mut Vec<ObjetSac>) {
+    for item in items {
+        sac.push(ObjetSac {
+            nom: nom_objet(item, refs),
+            quantite: quantite_objet(item),
+            niveau,
+        });
+        if let Some(contenu) = item.get("contenu").and_then(|c| c.as_array()) {
+            objets_sac(contenu, refs, niveau + 1, sac);
+        }
+    }
+}
+
+fn base_temp(data: &Value, pointer: &str) -> i64 {
+    read_i64(data, &format!("{}/base", pointer)) + read_i64(data, &format!("{}/temp", pointer))
+}
+
+pub fn fiche_calculee(data: &Value, refs: &[RefEquipement], rules: &GameRules) -> Fiche {
+    let pieces = pieces_portees(data, refs);
+    let caracs = caracteristiques_equipees(data, refs, &rules.metiers);
+    let carac = |key: &str| caracs.get(key) as i64;
+    let texte = |pointer: &str| read_str(data, pointer).to_string();
+
+    let identite = Identite {
+        nom: texte("/identity/nom"),
+        sexe: texte("/identity/sexe"),
+        origine: texte("/identity/origine"),
+        metier: texte("/identity/metier"),
+        specialisation: texte("/identity/specialisation"),
+        sous_specialisation: texte("/identity/sous_specialisation"),
+        domaine: texte("/identity/domaine"),
+        description: texte("/identity/description"),
+        niveau: read_i64(data, "/general/niveau"),
+        experience: read_i64(data, "/general/experience"),
+        points_destin: read_i64(data, "/general/points_destin"),
+    };
+
+    let caracteristiques = CARACTERISTIQUES
+        .iter()
+        .map(|(key, nom)| LigneCaracteristique {
+            nom: nom.to_string(),
+            naturel: read_i64(data, &format!("/characteristics/{}/naturel", key)),
+            finale: carac(key),
+        })
+        .collect();
+
+    let protection = |key: &str| -> i64 {
+        pieces
+            .iter()
+            .map(|p| p.protection(key) + read_i64(p.item, &format!("/modif_{}", key)))
+            .sum()
+    };
+    let defenses = vec![
+        LigneValeur {
+            nom: "PR naturelle".to_string(),
+            valeur: base_temp(data, "/defenses/naturelle"),
+        },
+        LigneValeur {
+            nom: "PR solide".to_string(),
+            valeur: read_i64(data, "/defenses/solide/base") + pr_solide_totale(data, &pieces),
+        },
+        LigneValeur {
+            nom: "PR spéciale".to_string(),
+            valeur: base_temp(data, "/defenses/speciale") + protection("pr_spe"),
+        },
+        LigneValeur {
+            nom: "PR magique".to_string(),
+            valeur: base_temp(data, "/defenses/magique") + protection("pr_mag"),
+        },
+    ];
+
+    let stats = calculer_stats_magiques(data, refs, rules);
+    let magie = [
+        ("Magie physique", &stats.magie_physique),
+        ("Magie psychique", &stats.magie_psychique),
+        ("Résistance magique", &stats.resistance_magique),
+        ("Discrétion", &stats.discretion),
+    ]
+    .into_iter()
+    .map(|(nom, stat)| LigneValeur {
+        nom: nom.to_string(),
+        valeur: stat.total as i64,
+    })
+    .collect();
+
+    let inventaire = data
+        .get("inventory")
+        .and_then(|i| i.as_array())
+        .map(|i| i.as_slice())
+        .unwrap_or_default();
+    let armes = inventaire
+        .iter()
+        .filter(|item| matches!(read_str(item, "/equipement_type"), "Armes" | "MainsNues"))
+        .map(|item| {
+            let degats =
+                DegatsArme::calculer(carac("force"), reference(item, refs).map(|r| (item, r)));
+            ArmeFiche {
+                nom: nom_objet(item, refs),
+                degats: degats.formule(),
+                bonus_force: degats.bonus_force.unwrap_or(0),
+            }
+        })
+        .collect();
+    let protections = pieces
+        .iter()
+        .map(|p| ProtectionFiche {
+            nom: p.nom(),
+            pr_sol: p.pr_solide(),
+            pr_spe: p.protection("pr_spe") + read_i64(p.item, "/modif_pr_spe"),
+            pr_mag: p.protection("pr_mag") + read_i64(p.item, "/modif_pr_mag"),
+        })
+        .collect();
+
+    let mut competences = Vec::new();
+    for (key, rang) in [
+        ("competences", ""),
+        ("competences_specialisation", "Spécialisation"),
+        ("competences_sous_specialisation", "Sous-spécialisation"),
+    ] {
+        for competence in data
+            .get(key)
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            competences.push(CompetenceFiche {
+                nom: read_str(competence, "/nom").to_string(),
+                rang: rang.to_string(),
+                description: read_str(competence, "/description").to_string(),
+            });
+        }
+    }
+
+    let mut sac = Vec::new();
+    let transportes: Vec<Value> = inventaire
+        .iter()
+        .filter(|item| !EQUIPEMENT_PORTE.contains(&read_str(item, "/equipement_type")))
+        .cloned()
+        .collect();
+    objets_sac(&transportes, refs, 0, &mut sac);
+    if let Some(custom) = data.get("custom_sac_items").and_then(|c| c.as_array()) {
+        objets_sac(custom, refs, 0, &mut sac);
+    }
+
+    let monnaies = Monnaies::from_sheet(data);
+    let richesse = CURRENCIES
+        .iter()
+        .zip(MONNAIES)
+        .map(|(currency, monnaie)| LigneMonnaie {
+            monnaie: monnaie.to_string(),
+            montants: LOCATIONS
+                .iter()
+                .map(|location| monnaies.slot(currency, location).unwrap_or(0))
+                .collect(),
+        })
+        .filter(|ligne| ligne.montants.iter().any(|m| *m != 0))
+        .collect();
+
+    Fiche {
+        identite,
+        caracteristiques,
+        defenses,
+        magie,
+        pv: read_jauge(data, Pool::Pv).unwrap_or_default(),
+        pm: read_jauge(data, Pool::Pm).unwrap_or_default(),
+        corruption: read_i64(data, "/vitals/corruption/current"),
+        armes,
+        protections,
+        competences,
+        sac,
+        richesse,
+    }
+}
+
+/// Fills empty competence descriptions from `competences.json`.
+pub fn completer_descriptions(fiche: &mut Fiche) -> Result<(), String> {
+    let reference = get_competences()?;
+    for competence in fiche
+        .competences
+        .iter_mut()
+        .filter(|c| c.description.is_empty())
+    {
+        if let Some(r) = reference.iter().find(|r| r.nom == competence.nom) {
+            competence.description = r.description.clone();
+        }
+    }
+    Ok(())
+}
+
+pub fn charger_fiche(conn: &Connection, id: &str) -> Result<Fiche, String> {
+    let data = load_personnage_data(conn, id)?;
+    let refs = load_ref_items(conn)?;
+    Ok(fiche_calculee(&data, &refs, &get_game_rules()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arme(id: i64) -> RefEquipement {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "Armes",
+            "ref_id": 1,
+            "nom": "Epée",
+            "degats": { "degats": "1D", "pi": 3 },
+            "caracteristiques": {},
+            "protections": {},
+            "prix_info": {},
+            "craft": {},
+            "details": {}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fiche_calculee() {
+        let data = serde_json::json!({
+            "identity": { "nom": "Gurdil", "metier": "Guerrier" },
+            "characteristics": {
+                "force": { "naturel": 14, "t1": 0, "t2": 0, "t3": 0 },
+                "courage": { "naturel": 11, "t1": 1, "t2": 0, "t3": 0 }
+            },
+            "vitals": { "pv": { "current": 20, "max": 30, "temp": 0 } },
+            "inventory": [
+                { "uid": "a", "refId": 7, "equipement_type": "Armes", "modif_pi": 1 },
+                { "uid": "b", "nom": "Sac", "equipement_type": "Sacs",
+                  "contenu": [{ "uid": "c", "nom": "Corde", "quantite": 2 }] }
+            ],
+            "competences": [{ "id": "x", "nom": "Bourrin", "description": "" }],
+            "richesse": { "monnaies": { "or": { "sur_soi": 12 } } }
+        });
+
+        let fiche = fiche_calculee(&data, &[arme(7)], &get_game_rules().unwrap());
+        assert_eq!(fiche.identite.nom, "Gurdil");
+        assert_eq!(fiche.caracteristiques[0].finale, 12);
+        assert_eq!(fiche.pv.current, 20);
+        assert_eq!(fiche.armes[0].degats, "1D + 6");
+        assert_eq!(fiche.armes[0].bonus_force, 2);
+        assert_eq!(fiche.sac.len(), 2);
+        assert_eq!(fiche.sac[1].niveau, 1);
+        assert_eq!(fiche.richesse.len(), 1);
+        assert_eq!(fiche.richesse[0].montants, vec![12, 0, 0, 0]);
+        assert_eq!(fiche.competences[0].nom, "Bourrin");
+    }
+
+    #[test]
+    fn test_fiche_matches_sheet_engine() {
+        // The printed values are the ones the sheet shows, spec bonuses,
+        // fatigue, mount and temporary effects included
+        let fixture: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/stats_parity.json")).unwrap();
+        let refs: Vec<RefEquipement> = serde_json::from_value(fixture["refs"].clone()).unwrap();
+        let expected = &fixture["expected"];
+
+        let fiche = fiche_calculee(&fixture["sheet"], &refs, &get_game_rules().unwrap());
+        for ((key, _), ligne) in CARACTERISTIQUES.iter().zip(&fiche.caracteristiques) {
+            assert_eq!(ligne.finale, expected[key].as_i64().unwrap(), "{}", key);
+        }
+        let magie: Vec<i64> = fiche.magie.iter().map(|l| l.valeur).collect();
+        assert_eq!(
+            magie,
+            [
+                "magie_physique",
+                "magie_psychique",
+                "resistance_magique",
+                "discretion"
+            ]
+            .map(|key| expected[key].as_i64().unwrap())
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::db::{AppState, Personnage};
 use reqwest::blocking::Client;
+use std::collections::HashMap;
 use tauri::State;
 
 #[tauri::command]
@@ -14,7 +15,7 @@ pub fn sync_personnages(
 
     // 1. Fetch Local Data
     let mut stmt = db
-        .prepare("SELECT id, name, data, updated_at FROM personnages")
+        .prepare("SELECT id, name, data, updated_at, deleted_at FROM personnages")
         .map_err(|e| e.to_string())?;
 
     let local_personnages = stmt
@@ -28,12 +29,29 @@ pub fn sync_personnages(
                 name: row.get(1)?,
                 data,
                 updated_at: row.get(3)?,
+                deleted_at: row.get(4)?,
             })
         })
         .map_err(|e| e.to_string())?
         .collect::<std::result::Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
+    // Purged characters go up once as empty tombstones so other devices trash
+    // them too; `import_personnage` never turns a tombstone into a row
+    let tombstones = crate::trash::unsynced_tombstones(&db)?;
+    let ids: Vec<String> = tombstones.iter().map(|t| t.id.clone()).collect();
+    let remote = remote_updated_at(&client, &supabase_url, &supabase_key, &token, &ids)?;
+    let mut local_personnages = local_personnages;
+    for tombstone in crate::trash::tombstones_to_push(tombstones, &remote)? {
+        local_personnages.push(Personnage {
+            id: tombstone.id,
+            name: tombstone.name,
+            data: serde_json::json!({}),
+            updated_at: tombstone.deleted_at.clone(),
+            deleted_at: Some(tombstone.deleted_at),
+        });
+    }
+
     // 2. Push to Supabase (Upsert)
     // Using Supabase REST API: POST /personnages with Prefer: resolution=merge-duplicates
     let url = format!("{}/rest/v1/personnages", supabase_url);
@@ -54,6 +72,7 @@ pub fn sync_personnages(
             response.text().unwrap_or_default()
         ));
     }
+    crate::trash::mark_tombstones_synced(&db, &ids)?;
 
     Ok(format!(
         "Synced {} characters to cloud.",
@@ -61,6 +80,47 @@ pub fn sync_personnages(
     ))
 }
 
+/// `updated_at` of the cloud rows among `ids`.
+fn remote_updated_at(
+    client: &Client,
+    supabase_url: &str,
+    supabase_key: &str,
+    token: &str,
+    ids: &[String],
+) -> Result<HashMap<String, String>, String> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let url = format!(
+        "{}/rest/v1/personnages?select=id,updated_at&id=in.({})",
+        supabase_url,
+        ids.join(",")
+    );
+    let response = client
+        .get(&url)
+        .header("apikey", supabase_key)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Fetch failed: {}",
+            response.text().unwrap_or_default()
+        ));
+    }
+
+    let rows: Vec<serde_json::Value> = response.json().map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some((
+                row.get("id")?.as_str()?.to_string(),
+                row.get("updated_at")?.as_str()?.to_string(),
+            ))
+        })
+        .collect())
+}
+
 #[tauri::command]
 pub fn publish_ref_items(
     token: String,
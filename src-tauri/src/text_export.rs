@@ -0,0 +1,366 @@
+use crate::db::AppState;
+use crate::summary::{charger_fiche, completer_descriptions, Fiche, LIEUX};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+// Sheet summary to paste in a chat or a forum post. Both formats share the same
+// layout and differ only by their template; Markdown stays to lists and bold
+// text, which Discord renders (it has no tables).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatTexte {
+    Markdown,
+    #[serde(alias = "text")]
+    Texte,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionTexte {
+    Identite,
+    Caracteristiques,
+    Defenses,
+    Vitalite,
+    Armes,
+    Protections,
+    Competences,
+    Sac,
+    Richesse,
+}
+
+pub const SECTIONS_PAR_DEFAUT: [SectionTexte; 9] = [
+    SectionTexte::Identite,
+    SectionTexte::Caracteristiques,
+    SectionTexte::Defenses,
+    SectionTexte::Vitalite,
+    SectionTexte::Armes,
+    SectionTexte::Protections,
+    SectionTexte::Competences,
+    SectionTexte::Sac,
+    SectionTexte::Richesse,
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OptionsTexte {
+    /// Sections to render, in this order. All of them when missing.
+    #[serde(default)]
+    pub sections: Option<Vec<SectionTexte>>,
+    /// Adds the competence descriptions, from `competences.json` when the sheet has none
+    #[serde(default)]
+    pub descriptions: bool,
+}
+
+/// `{texte}`, `{nom}` and `{valeur}` are replaced when rendering.
+struct Gabarit {
+    titre: &'static str,
+    soulignement_titre: Option<char>,
+    section: &'static str,
+    soulignement_section: Option<char>,
+    champ: &'static str,
+    element: &'static str,
+    description: &'static str,
+    retrait: &'static str,
+    echapper: fn(&str) -> String,
+}
+
+const MARKDOWN: Gabarit = Gabarit {
+    titre: "# {texte}",
+    soulignement_titre: None,
+    section: "## {texte}",
+    soulignement_section: None,
+    champ: "- **{nom}** : {valeur}",
+    element: "- {texte}",
+    description: "  > {texte}",
+    retrait: "  ",
+    echapper: echapper_markdown,
+};
+
+const TEXTE: Gabarit = Gabarit {
+    titre: "{texte}",
+    soulignement_titre: Some('='),
+    section: "{texte}",
+    soulignement_section: Some('-'),
+    champ: "{nom} : {valeur}",
+    element: "* {texte}",
+    description: "    {texte}",
+    retrait: "  ",
+    echapper: |texte| texte.to_string(),
+};
+
+fn echapper_markdown(texte: &str) -> String {
+    let mut sortie = String::with_capacity(texte.len());
+    for c in texte.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#') {
+            sortie.push('\\');
+        }
+        sortie.push(c);
+    }
+    sortie
+}
+
+struct Rendu<'a> {
+    gabarit: &'a Gabarit,
+    lignes: Vec<String>,
+}
+
+impl Rendu<'_> {
+    fn entete(&mut self, modele: &str, soulignement: Option<char>, texte: &str) {
+        let ligne = modele.replace("{texte}", &(self.gabarit.echapper)(texte));
+        if let Some(trait_) = soulignement {
+            let longueur = ligne.chars().count();
+            self.lignes.push(ligne);
+            self.lignes.push(trait_.to_string().repeat(longueur));
+        } else {
+            self.lignes.push(ligne);
+        }
+    }
+
+    fn section(&mut self, texte: &str) {
+        if !self.lignes.is_empty() {
+            self.lignes.push(String::new());
+        }
+        self.entete(
+            self.gabarit.section,
+            self.gabarit.soulignement_section,
+            texte,
+        );
+    }
+
+    fn champ(&mut self, nom: &str, valeur: &str) {
+        let echapper = self.gabarit.echapper;
+        self.lignes.push(
+            self.gabarit
+                .champ
+                .replace("{nom}", &echapper(nom))
+                .replace("{valeur}", &echapper(valeur)),
+        );
+    }
+
+    fn element(&mut self, texte: &str, niveau: usize) {
+        let ligne = self
+            .gabarit
+            .element
+            .replace("{texte}", &(self.gabarit.echapper)(texte));
+        self.lignes
+            .push(format!("{}{}", self.gabarit.retrait.repeat(niveau), ligne));
+    }
+
+    fn description(&mut self, texte: &str) {
+        for ligne in texte.lines().filter(|l| !l.trim().is_empty()) {
+            self.lignes.push(
+                self.gabarit
+                    .description
+                    .replace("{texte}", &(self.gabarit.echapper)(ligne.trim())),
+            );
+        }
+    }
+}
+
+fn jauge(j: &crate::vitals::Jauge) -> String {
+    match j.temp {
+        0 => format!("{} / {}", j.current, j.max),
+        temp => format!("{} / {} ({:+} temporaire)", j.current, j.max, temp),
+    }
+}
+
+pub fn rendre_texte(
+    fiche: &Fiche,
+    format: FormatTexte,
+    sections: &[SectionTexte],
+    descriptions: bool,
+) -> String {
+    let mut rendu = Rendu {
+        gabarit: match format {
+            FormatTexte::Markdown => &MARKDOWN,
+            FormatTexte::Texte => &TEXTE,
+        },
+        lignes: Vec::new(),
+    };
+    let identite = &fiche.identite;
+    rendu.entete(
+        rendu.gabarit.titre,
+        rendu.gabarit.soulignement_titre,
+        &identite.nom,
+    );
+    let sous_titre: Vec<String> = [identite.origine.clone(), identite.metier.clone()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .chain([format!("niveau {}", identite.niveau)])
+        .collect();
+    rendu
+        .lignes
+        .push((rendu.gabarit.echapper)(&sous_titre.join(", ")));
+
+    for section in sections {
+        match section {
+            SectionTexte::Identite => {
+                rendu.section("Identité");
+                for (nom, valeur) in [
+                    ("Sexe", &identite.sexe),
+                    ("Spécialisation", &identite.specialisation),
+                    ("Sous-spécialisation", &identite.sous_specialisation),
+                    ("Domaine", &identite.domaine),
+                ] {
+                    if !valeur.is_empty() {
+                        rendu.champ(nom, valeur);
+                    }
+                }
+                rendu.champ("Expérience", &identite.experience.to_string());
+                rendu.champ("Points de destin", &identite.points_destin.to_string());
+                if !identite.description.is_empty() {
+                    rendu.description(&identite.description);
+                }
+            }
+            SectionTexte::Caracteristiques => {
+                rendu.section("Caractéristiques");
+                for c in &fiche.caracteristiques {
+                    let valeur = if c.finale == c.naturel {
+                        c.finale.to_string()
+                    } else {
+                        format!("{} (naturel {})", c.finale, c.naturel)
+                    };
+                    rendu.champ(&c.nom, &valeur);
+                }
+            }
+            SectionTexte::Defenses => {
+                rendu.section("Défenses et magie");
+                for ligne in fiche.defenses.iter().chain(&fiche.magie) {
+                    rendu.champ(&ligne.nom, &ligne.valeur.to_string());
+                }
+            }
+            SectionTexte::Vitalite => {
+                rendu.section("Vitalité");
+                rendu.champ("Points de vie", &jauge(&fiche.pv));
+                rendu.champ("Points de mana", &jauge(&fiche.pm));
+                rendu.champ("Corruption", &fiche.corruption.to_string());
+            }
+            SectionTexte::Armes if !fiche.armes.is_empty() => {
+                rendu.section("Armes");
+                for arme in &fiche.armes {
+                    let degats = match arme.bonus_force {
+                        0 => arme.degats.clone(),
+                        bonus => format!("{} (dont FO {:+})", arme.degats, bonus),
+                    };
+                    rendu.champ(&arme.nom, &degats);
+                }
+            }
+            SectionTexte::Protections if !fiche.protections.is_empty() => {
+                rendu.section("Protections");
+                for p in &fiche.protections {
+                    rendu.champ(
+                        &p.nom,
+                        &format!("PR sol. {}, spé. {}, mag. {}", p.pr_sol, p.pr_spe, p.pr_mag),
+                    );
+                }
+            }
+            SectionTexte::Competences if !fiche.competences.is_empty() => {
+                rendu.section("Compétences");
+                for competence in &fiche.competences {
+                    let nom = match competence.rang.as_str() {
+                        "" => competence.nom.clone(),
+                        rang => format!("{} ({})", competence.nom, rang),
+                    };
+                    rendu.element(&nom, 0);
+                    if descriptions {
+                        rendu.description(&competence.description);
+                    }
+                }
+            }
+            SectionTexte::Sac if !fiche.sac.is_empty() => {
+                rendu.section("Sac");
+                for objet in &fiche.sac {
+                    rendu.element(&format!("{} × {}", objet.quantite, objet.nom), objet.niveau);
+                }
+            }
+            SectionTexte::Richesse if !fiche.richesse.is_empty() => {
+                rendu.section("Richesse");
+                for ligne in &fiche.richesse {
+                    let montants: Vec<String> = LIEUX
+                        .iter()
+                        .zip(&ligne.montants)
+                        .filter(|(_, m)| **m != 0)
+                        .map(|(lieu, m)| format!("{} {}", m, lieu.to_lowercase()))
+                        .collect();
+                    rendu.champ(&ligne.monnaie, &montants.join(", "));
+                }
+            }
+            // Empty lists are left out
+            _ => {}
+        }
+    }
+
+    let mut texte = rendu.lignes.join("\n");
+    texte.push('\n');
+    texte
+}
+
+/// Sheet summary as Markdown or plain text, ready to paste.
+#[tauri::command]
+pub fn export_personnage_text(
+    id: String,
+    format: FormatTexte,
+    options: Option<OptionsTexte>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut fiche = charger_fiche(&db, &id)?;
+    drop(db);
+
+    if options.descriptions {
+        completer_descriptions(&mut fiche)?;
+    }
+    let sections = options
+        .sections
+        .unwrap_or_else(|| SECTIONS_PAR_DEFAUT.to_vec());
+    Ok(rendre_texte(
+        &fiche,
+        format,
+        &sections,
+        options.descriptions,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::fiche_calculee;
+
+    fn fiche() -> Fiche {
+        let data = serde_json::json!({
+            "identity": { "nom": "Gurdil *le Bourrin*", "metier": "Guerrier" },
+            "general": { "niveau": 3 },
+            "characteristics": { "courage": { "naturel": 11, "t1": 1 } },
+            "competences": [{ "id": "x", "nom": "Bourrin", "description": "Tape fort." }]
+        });
+        fiche_calculee(&data, &[], &crate::commands::get_game_rules().unwrap())
+    }
+
+    #[test]
+    fn test_markdown_sections() {
+        let texte = rendre_texte(
+            &fiche(),
+            FormatTexte::Markdown,
+            &[SectionTexte::Caracteristiques, SectionTexte::Competences],
+            false,
+        );
+        assert!(texte.starts_with("# Gurdil \\*le Bourrin\\*\nGuerrier, niveau 3\n"));
+        assert!(texte.contains("- **Courage** : 12 (naturel 11)"));
+        assert!(texte.contains("- Bourrin"));
+        assert!(!texte.contains("Tape fort."));
+        assert!(!texte.contains("Vitalité"));
+    }
+
+    #[test]
+    fn test_plain_text_with_descriptions() {
+        let texte = rendre_texte(
+            &fiche(),
+            FormatTexte::Texte,
+            &[SectionTexte::Competences],
+            true,
+        );
+        assert!(texte.starts_with("Gurdil *le Bourrin*\n===================\n"));
+        assert!(texte.contains("Compétences\n-----------\n* Bourrin\n    Tape fort.\n"));
+    }
+}
@@ -0,0 +1,376 @@
+use crate::db::{log_personnage_event, AppState};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::State;
+
+// Deleting a character only sets `deleted_at`: it leaves the list but keeps its
+// history and can be restored. Purging the trash removes the rows for good and
+// leaves a tombstone, so a sync does not bring the character back.
+//
+// Dates from the cloud and from the frontend do not share one RFC 3339 layout
+// ("Z" or "+00:00", fractional seconds or not), so they are compared parsed.
+
+/// Tables holding per-character rows, cleaned when a character is purged.
+const TABLES_PERSONNAGE: &[&str] = &[
+    "personnages_versions",
+    "personnages_journal",
+    "personnages_events",
+    "personnages_level_ups",
+    "richesse_ledger",
+    "game_clock_members",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedSummary {
+    pub id: String,
+    pub name: String,
+    pub deleted_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub id: String,
+    pub name: String,
+    pub deleted_at: String,
+}
+
+pub fn instant(date: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| format!("Date invalide \"{}\": {}", date, e))
+}
+
+pub fn soft_delete(conn: &Connection, id: &str) -> Result<String, String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let updated = conn
+        .execute(
+            "UPDATE personnages SET deleted_at = ?1, updated_at = ?1
+             WHERE id = ?2 AND deleted_at IS NULL",
+            params![now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Personnage introuvable: {}", id));
+    }
+    Ok(now)
+}
+
+pub fn restore(conn: &Connection, id: &str) -> Result<String, String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let updated = conn
+        .execute(
+            "UPDATE personnages SET deleted_at = NULL, updated_at = ?1
+             WHERE id = ?2 AND deleted_at IS NOT NULL",
+            params![now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Personnage absent de la corbeille: {}", id));
+    }
+    Ok(now)
+}
+
+/// Removes for good what was deleted before `cutoff` (RFC 3339). Returns the ids.
+pub fn purge(conn: &Connection, cutoff: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, deleted_at FROM personnages
+             WHERE deleted_at IS NOT NULL AND deleted_at <= ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let purged = stmt
+        .query_map([cutoff], |row| {
+            Ok(Tombstone {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                deleted_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    for tombstone in &purged {
+        conn.execute(
+            "INSERT OR REPLACE INTO personnages_tombstones (id, name, deleted_at) VALUES (?1, ?2, ?3)",
+            params![tombstone.id, tombstone.name, tombstone.deleted_at],
+        )
+        .map_err(|e| e.to_string())?;
+        for table in TABLES_PERSONNAGE {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE personnage_id = ?1", table),
+                [&tombstone.id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        // Its own clock, unless other characters joined it
+        conn.execute(
+            "DELETE FROM game_clocks WHERE clock_id = ?1
+             AND NOT EXISTS (SELECT 1 FROM game_clock_members WHERE clock_id = ?1)",
+            [&tombstone.id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM personnages WHERE id = ?1", [&tombstone.id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(purged.into_iter().map(|t| t.id).collect())
+}
+
+/// When the local copy was deleted after `updated_at`, an incoming copy is stale
+/// and must not bring the character back.
+pub fn deleted_since(conn: &Connection, id: &str, updated_at: &str) -> Result<bool, String> {
+    let deleted_at: Option<String> = conn
+        .query_row(
+            "SELECT deleted_at FROM personnages WHERE id = ?1 AND deleted_at IS NOT NULL
+             UNION ALL
+             SELECT deleted_at FROM personnages_tombstones WHERE id = ?1",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match deleted_at {
+        Some(deleted_at) => Ok(instant(&deleted_at)? >= instant(updated_at)?),
+        None => Ok(false),
+    }
+}
+
+/// Applies a deletion received from the cloud: the local copy goes to the trash,
+/// unless it was edited after `deleted_at`. Returns whether it was trashed.
+pub fn apply_remote_deletion(
+    conn: &Connection,
+    id: &str,
+    deleted_at: &str,
+) -> Result<bool, String> {
+    let updated_at: Option<String> = conn
+        .query_row(
+            "SELECT updated_at FROM personnages WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let deleted_at = instant(deleted_at)?;
+    match updated_at {
+        Some(updated_at) if instant(&updated_at)? <= deleted_at => {
+            // Stored in the same layout as local deletions, which `purge` compares
+            conn.execute(
+                "UPDATE personnages SET deleted_at = ?1 WHERE id = ?2",
+                params![deleted_at.to_rfc3339(), id],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Tombstones never reconciled with the cloud.
+pub fn unsynced_tombstones(conn: &Connection) -> Result<Vec<Tombstone>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, deleted_at FROM personnages_tombstones WHERE synced_at IS NULL")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Tombstone {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                deleted_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Tombstones to push, given the `updated_at` of the cloud rows by id: a row
+/// edited on another device after the purge is left alone.
+pub fn tombstones_to_push(
+    tombstones: Vec<Tombstone>,
+    remote: &HashMap<String, String>,
+) -> Result<Vec<Tombstone>, String> {
+    let mut push = Vec::new();
+    for tombstone in tombstones {
+        let newer = match remote.get(&tombstone.id) {
+            Some(updated_at) => instant(&tombstone.deleted_at)? > instant(updated_at)?,
+            None => true,
+        };
+        if newer {
+            push.push(tombstone);
+        }
+    }
+    Ok(push)
+}
+
+/// Tombstones are pushed once: skipped or sent, they are settled.
+pub fn mark_tombstones_synced(conn: &Connection, ids: &[String]) -> Result<(), String> {
+    let now = Utc::now().to_rfc3339();
+    for id in ids {
+        conn.execute(
+            "UPDATE personnages_tombstones SET synced_at = ?1 WHERE id = ?2",
+            params![now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_deleted_personnages(state: State<AppState>) -> Result<Vec<DeletedSummary>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare(
+            "SELECT id, name, deleted_at FROM personnages
+             WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(DeletedSummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                deleted_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn restore_deleted_personnage(id: String, state: State<AppState>) -> Result<(), String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let now = restore(&tx, &id)?;
+    log_personnage_event(&tx, &id, "restore", &serde_json::json!({}), &now)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Empties the trash of characters deleted more than `older_than` days ago
+/// (all of them with 0). Returns the purged ids.
+#[tauri::command]
+pub fn purge_trash(older_than: i64, state: State<AppState>) -> Result<Vec<String>, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(older_than.max(0));
+    let purged = purge(&tx, &cutoff.to_rfc3339())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db() -> Connection {
+        let conn = crate::migrations::test_db_with(&serde_json::json!({}));
+        crate::versions::snapshot_version(&conn, "p1").unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_trash_round_trip() {
+        let conn = db();
+        soft_delete(&conn, "p1").unwrap();
+        assert!(soft_delete(&conn, "p1").is_err());
+        // An older copy from the cloud does not resurrect it
+        assert!(deleted_since(&conn, "p1", "2024-01-01T00:00:00+00:00").unwrap());
+
+        restore(&conn, "p1").unwrap();
+        assert!(!deleted_since(&conn, "p1", "2024-01-01T00:00:00+00:00").unwrap());
+
+        // A remote deletion older than the local edit is ignored
+        assert!(!apply_remote_deletion(&conn, "p1", "2023-01-01T00:00:00+00:00").unwrap());
+        assert!(apply_remote_deletion(&conn, "p1", "2099-01-01T00:00:00+00:00").unwrap());
+        assert!(crate::db::load_personnage_data(&conn, "p1").is_err());
+        restore(&conn, "p1").unwrap();
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM personnages WHERE deleted_at IS NULL"
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn test_dates_compare_whatever_their_layout() {
+        let conn = db();
+        soft_delete(&conn, "p1").unwrap();
+        conn.execute(
+            "UPDATE personnages SET deleted_at = '2024-06-01T12:00:00+00:00'",
+            [],
+        )
+        .unwrap();
+        // "Z" sorts after "+" as text, yet both are the same instant
+        assert!(deleted_since(&conn, "p1", "2024-06-01T12:00:00Z").unwrap());
+        assert!(!deleted_since(&conn, "p1", "2024-06-01T12:00:00.5Z").unwrap());
+        assert!(deleted_since(&conn, "p1", "demain").is_err());
+
+        restore(&conn, "p1").unwrap();
+        conn.execute(
+            "UPDATE personnages SET updated_at = '2024-06-01T12:00:00.250+00:00'",
+            [],
+        )
+        .unwrap();
+        assert!(!apply_remote_deletion(&conn, "p1", "2024-06-01T14:00:00+02:00").unwrap());
+        assert!(apply_remote_deletion(&conn, "p1", "2024-06-01T13:00:00Z").unwrap());
+    }
+
+    #[test]
+    fn test_tombstones_are_pushed_once_when_newer() {
+        let tombstone = |id: &str| Tombstone {
+            id: id.to_string(),
+            name: id.to_string(),
+            deleted_at: "2024-06-01T12:00:00+00:00".to_string(),
+        };
+        let remote = HashMap::from([
+            ("edite".to_string(), "2024-06-02T08:00:00.123Z".to_string()),
+            ("ancien".to_string(), "2024-05-01T08:00:00Z".to_string()),
+        ]);
+        let push = tombstones_to_push(
+            vec![tombstone("edite"), tombstone("ancien"), tombstone("absent")],
+            &remote,
+        )
+        .unwrap();
+        let ids: Vec<&str> = push.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["ancien", "absent"]);
+
+        let conn = db();
+        soft_delete(&conn, "p1").unwrap();
+        purge(&conn, &chrono::Utc::now().to_rfc3339()).unwrap();
+        mark_tombstones_synced(&conn, &["p1".to_string()]).unwrap();
+        assert!(unsynced_tombstones(&conn).unwrap().is_empty());
+        // Still keeps a stale cloud copy out
+        assert!(deleted_since(&conn, "p1", "2024-06-01T00:00:00+00:00").unwrap());
+    }
+
+    #[test]
+    fn test_purge_leaves_a_tombstone() {
+        let conn = db();
+        conn.execute(
+            "INSERT INTO game_clocks (clock_id, minutes, updated_at) VALUES ('p1', 60, 'now')",
+            [],
+        )
+        .unwrap();
+        soft_delete(&conn, "p1").unwrap();
+        assert!(purge(&conn, "2000-01-01T00:00:00+00:00")
+            .unwrap()
+            .is_empty());
+
+        let purged = purge(&conn, &chrono::Utc::now().to_rfc3339()).unwrap();
+        assert_eq!(purged, vec!["p1".to_string()]);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM personnages"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM personnages_versions"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM game_clocks"), 0);
+        assert_eq!(unsynced_tombstones(&conn).unwrap()[0].name, "Gurdil");
+        assert!(deleted_since(&conn, "p1", "2024-06-01T00:00:00+00:00").unwrap());
+    }
+}
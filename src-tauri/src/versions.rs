@@ -0,0 +1,515 @@
+use crate::db::{load_personnage_data, AppState};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use tauri::State;
+
+// Version history of the character sheets. Every save snapshots the previous
+// sheet; pruning then keeps the N most recent snapshots, the latest one of each
+// of the last days and weeks, and anything pinned by the user.
+//
+// The label and client of a save describe the sheet it writes: they are kept on
+// the `personnages` row (`version_label`, `version_client`) and move into the
+// history with that sheet when the next save snapshots it.
+
+const RETENTION_KEY: &str = "version_retention";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub keep_recent: usize,
+    /// One version per day, for this many days that have versions
+    pub keep_daily: usize,
+    /// One version per ISO week, for this many weeks that have versions
+    pub keep_weekly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_recent: 3,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionSummary {
+    pub version_id: i64,
+    pub saved_at: String,
+    pub label: Option<String>,
+    /// Which client saved it ("desktop", "sync"…)
+    pub client: Option<String>,
+    pub pinned: bool,
+}
+
+pub fn load_retention(conn: &Connection) -> Result<RetentionPolicy, String> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM db_meta WHERE key = ?1",
+            [RETENTION_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(value
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Copies the current sheet into the history with its label and client.
+/// `saved_at` is the `updated_at` of the row, when that sheet was saved, so
+/// that daily and weekly retention go by the age of the sheet rather than by
+/// the time it was replaced. Returns `None` for an unknown character.
+pub fn snapshot_version(conn: &Connection, id: &str) -> Result<Option<i64>, String> {
+    let inserted = conn
+        .execute(
+            "INSERT INTO personnages_versions (personnage_id, data, saved_at, label, client)
+             SELECT id, data, updated_at, version_label, version_client
+             FROM personnages WHERE id = ?1",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+}
+
+/// Sets the label and client of the sheet currently stored.
+pub fn tag_current(
+    conn: &Connection,
+    id: &str,
+    label: Option<&str>,
+    client: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE personnages SET version_label = ?2, version_client = ?3 WHERE id = ?1",
+        params![id, label, client],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Stores an arbitrary state of the sheet as a version.
+pub fn insert_version(
+    conn: &Connection,
+    id: &str,
+    data: &Value,
+    saved_at: &str,
+    label: Option<&str>,
+    client: Option<&str>,
+) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO personnages_versions (personnage_id, data, saved_at, label, client)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, data.to_string(), saved_at, label, client],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn day_of(saved_at: &str) -> Option<NaiveDate> {
+    saved_at
+        .get(..10)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+}
+
+/// Versions to keep, given versions sorted from newest to oldest.
+pub fn versions_to_keep(versions: &[VersionSummary], policy: &RetentionPolicy) -> HashSet<i64> {
+    let mut keep: HashSet<i64> = versions
+        .iter()
+        .filter(|v| v.pinned)
+        .map(|v| v.version_id)
+        .collect();
+    keep.extend(
+        versions
+            .iter()
+            .take(policy.keep_recent)
+            .map(|v| v.version_id),
+    );
+
+    // The newest version of each period comes first in the list
+    let mut days = Vec::new();
+    let mut weeks = Vec::new();
+    for version in versions {
+        let Some(day) = day_of(&version.saved_at) else {
+            continue;
+        };
+        if !days.contains(&day) && days.len() < policy.keep_daily {
+            days.push(day);
+            keep.insert(version.version_id);
+        }
+        let week = (day.iso_week().year(), day.iso_week().week());
+        if !weeks.contains(&week) && weeks.len() < policy.keep_weekly {
+            weeks.push(week);
+            keep.insert(version.version_id);
+        }
+    }
+    keep
+}
+
+pub fn list_versions(conn: &Connection, id: &str) -> Result<Vec<VersionSummary>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT version_id, saved_at, label, client, pinned FROM personnages_versions
+             WHERE personnage_id = ?1 ORDER BY saved_at DESC, version_id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([id], |row| {
+            Ok(VersionSummary {
+                version_id: row.get(0)?,
+                saved_at: row.get(1)?,
+                label: row.get(2)?,
+                client: row.get(3)?,
+                pinned: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Applies the retention policy to one character's history.
+pub fn prune_versions(conn: &Connection, id: &str) -> Result<usize, String> {
+    let policy = load_retention(conn)?;
+    let versions = list_versions(conn, id)?;
+    let keep = versions_to_keep(&versions, &policy);
+
+    let mut pruned = 0;
+    for version in versions.iter().filter(|v| !keep.contains(&v.version_id)) {
+        pruned += conn
+            .execute(
+                "DELETE FROM personnages_versions WHERE version_id = ?1",
+                [version.version_id],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(pruned)
+}
+
+#[tauri::command]
+pub fn get_version_retention(state: State<AppState>) -> Result<RetentionPolicy, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    load_retention(&db)
+}
+
+/// Takes effect on the next save of each character.
+#[tauri::command]
+pub fn set_version_retention(
+    policy: RetentionPolicy,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let value = serde_json::to_string(&policy).map_err(|e| e.to_string())?;
+    db.execute(
+        "INSERT OR REPLACE INTO db_meta (key, value) VALUES (?1, ?2)",
+        params![RETENTION_KEY, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// A pinned version is never pruned.
+#[tauri::command]
+pub fn pin_personnage_version(
+    id: String,
+    version_id: i64,
+    pinned: bool,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let updated = db
+        .execute(
+            "UPDATE personnages_versions SET pinned = ?1 WHERE version_id = ?2 AND personnage_id = ?3",
+            params![pinned, version_id, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Version introuvable: {}", version_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn label_personnage_version(
+    id: String,
+    version_id: i64,
+    label: Option<String>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let label = label.filter(|l| !l.trim().is_empty());
+    let updated = db
+        .execute(
+            "UPDATE personnages_versions SET label = ?1 WHERE version_id = ?2 AND personnage_id = ?3",
+            params![label, version_id, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Version introuvable: {}", version_id));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// JSON pointer, into the newer sheet for items matched by uid
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemChange {
+    pub uid: String,
+    pub path: String,
+    pub item: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SectionDiff {
+    /// Top-level key of the sheet: "vitals", "inventory", "richesse"…
+    pub section: String,
+    pub changes: Vec<FieldChange>,
+    pub items_added: Vec<ItemChange>,
+    pub items_removed: Vec<ItemChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionDiff {
+    pub from: i64,
+    /// `None` for the current sheet
+    pub to: Option<i64>,
+    pub sections: Vec<SectionDiff>,
+}
+
+fn uid_of(value: &Value) -> Option<&str> {
+    value.get("uid").and_then(|u| u.as_str())
+}
+
+fn section<'a>(sections: &'a mut Vec<SectionDiff>, path: &str) -> &'a mut SectionDiff {
+    let name = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    let index = match sections.iter().position(|s| s.section == name) {
+        Some(index) => index,
+        None => {
+            sections.push(SectionDiff {
+                section: name.to_string(),
+                ..Default::default()
+            });
+            sections.len() - 1
+        }
+    };
+    &mut sections[index]
+}
+
+fn diff_values(old: &Value, new: &Value, path: &str, sections: &mut Vec<SectionDiff>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                match b.get(key) {
+                    Some(other) => diff_values(value, other, &child, sections),
+                    None => section(sections, &child).changes.push(FieldChange {
+                        path: child,
+                        old: Some(value.clone()),
+                        new: None,
+                    }),
+                }
+            }
+            for (key, value) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                section(sections, &child).changes.push(FieldChange {
+                    path: child,
+                    old: None,
+                    new: Some(value.clone()),
+                });
+            }
+        }
+        // Lists of items: matched by uid so a reordering is not a change
+        (Value::Array(a), Value::Array(b))
+            if !path.is_empty() && a.iter().chain(b).all(|v| uid_of(v).is_some()) =>
+        {
+            for (index, item) in b.iter().enumerate() {
+                let child = format!("{}/{}", path, index);
+                match a.iter().find(|o| uid_of(o) == uid_of(item)) {
+                    Some(before) => diff_values(before, item, &child, sections),
+                    None => section(sections, &child).items_added.push(ItemChange {
+                        uid: uid_of(item).unwrap_or_default().to_string(),
+                        path: child,
+                        item: item.clone(),
+                    }),
+                }
+            }
+            for (index, item) in a.iter().enumerate() {
+                if !b.iter().any(|n| uid_of(n) == uid_of(item)) {
+                    let child = format!("{}/{}", path, index);
+                    section(sections, &child).items_removed.push(ItemChange {
+                        uid: uid_of(item).unwrap_or_default().to_string(),
+                        path: child,
+                        item: item.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for index in 0..a.len().max(b.len()) {
+                let child = format!("{}/{}", path, index);
+                match (a.get(index), b.get(index)) {
+                    (Some(x), Some(y)) => diff_values(x, y, &child, sections),
+                    (x, y) => section(sections, &child).changes.push(FieldChange {
+                        path: child,
+                        old: x.cloned(),
+                        new: y.cloned(),
+                    }),
+                }
+            }
+        }
+        _ if old != new => section(sections, path).changes.push(FieldChange {
+            path: path.to_string(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Changes from `old` to `new`, grouped by sheet section.
+pub fn diff_sheets(old: &Value, new: &Value) -> Vec<SectionDiff> {
+    let mut sections = Vec::new();
+    diff_values(old, new, "", &mut sections);
+    sections
+}
+
+pub fn load_version_data(conn: &Connection, id: &str, version_id: i64) -> Result<Value, String> {
+    let data: String = conn
+        .query_row(
+            "SELECT data FROM personnages_versions WHERE version_id = ?1 AND personnage_id = ?2",
+            params![version_id, id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Version introuvable: {}", e))?;
+    serde_json::from_str(&data).map_err(|e| e.to_string())
+}
+
+/// What changed between version `a` and version `b`, or the current sheet when
+/// `b` is omitted.
+#[tauri::command]
+pub fn diff_personnage_versions(
+    id: String,
+    a: i64,
+    b: Option<i64>,
+    state: State<AppState>,
+) -> Result<VersionDiff, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let old = load_version_data(&db, &id, a)?;
+    let new = match b {
+        Some(b) => load_version_data(&db, &id, b)?,
+        None => load_personnage_data(&db, &id)?,
+    };
+    Ok(VersionDiff {
+        from: a,
+        to: b,
+        sections: diff_sheets(&old, &new),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version_id: i64, saved_at: &str, pinned: bool) -> VersionSummary {
+        VersionSummary {
+            version_id,
+            saved_at: saved_at.to_string(),
+            label: None,
+            client: None,
+            pinned,
+        }
+    }
+
+    #[test]
+    fn test_retention_keeps_recent_daily_weekly_and_pinned() {
+        let versions = vec![
+            version(9, "2024-03-20T18:00:00+00:00", false),
+            version(8, "2024-03-20T12:00:00+00:00", false),
+            version(7, "2024-03-20T08:00:00+00:00", false),
+            version(6, "2024-03-19T20:00:00+00:00", false),
+            version(5, "2024-03-19T10:00:00+00:00", false),
+            version(4, "2024-03-12T10:00:00+00:00", false),
+            version(3, "2024-03-11T10:00:00+00:00", false),
+            version(2, "2024-01-02T10:00:00+00:00", true),
+            version(1, "2023-12-01T10:00:00+00:00", false),
+        ];
+        let policy = RetentionPolicy {
+            keep_recent: 2,
+            keep_daily: 2,
+            keep_weekly: 2,
+        };
+
+        let mut keep: Vec<i64> = versions_to_keep(&versions, &policy).into_iter().collect();
+        keep.sort();
+        // 9, 8 recent; 9, 6 latest of their day; 9, 4 latest of their week; 2 pinned
+        assert_eq!(keep, vec![2, 4, 6, 8, 9]);
+    }
+
+    #[test]
+    fn test_label_follows_the_sheet_it_describes() {
+        let conn = crate::migrations::test_db_with(&serde_json::json!({ "v": 1 }));
+        tag_current(&conn, "p1", Some("Avant le donjon"), Some("desktop")).unwrap();
+
+        // The next save moves the labelled sheet into the history
+        snapshot_version(&conn, "p1").unwrap();
+        conn.execute(
+            "UPDATE personnages SET data = '{\"v\":2}', updated_at = '2024-01-02T00:00:00+00:00'",
+            [],
+        )
+        .unwrap();
+        tag_current(&conn, "p1", None, Some("sync")).unwrap();
+        snapshot_version(&conn, "p1").unwrap();
+
+        let versions = list_versions(&conn, "p1").unwrap();
+        let labelled = &versions[1];
+        assert_eq!(labelled.label.as_deref(), Some("Avant le donjon"));
+        assert_eq!(labelled.client.as_deref(), Some("desktop"));
+        assert_eq!(labelled.saved_at, "2024-01-01T00:00:00+00:00");
+        assert_eq!(
+            load_version_data(&conn, "p1", labelled.version_id).unwrap()["v"],
+            1
+        );
+        assert_eq!(versions[0].label, None);
+        assert_eq!(versions[0].client.as_deref(), Some("sync"));
+    }
+
+    #[test]
+    fn test_diff_groups_changes_by_section() {
+        let old = serde_json::json!({
+            "vitals": { "pv": { "current": 20, "max": 20 } },
+            "inventory": [
+                { "uid": "a", "nom": "Epée", "quantite": 1 },
+                { "uid": "b", "nom": "Corde" }
+            ],
+            "richesse": { "po": 10 }
+        });
+        let new = serde_json::json!({
+            "vitals": { "pv": { "current": 12, "max": 20 } },
+            "inventory": [
+                { "uid": "c", "nom": "Torche" },
+                { "uid": "a", "nom": "Epée", "quantite": 2 }
+            ],
+            "richesse": { "po": 10, "pa": 3 }
+        });
+
+        let sections = diff_sheets(&old, &new);
+        let names: Vec<&str> = sections.iter().map(|s| s.section.as_str()).collect();
+        assert_eq!(names, vec!["inventory", "richesse", "vitals"]);
+
+        let inventory = &sections[0];
+        assert_eq!(inventory.changes[0].path, "/inventory/1/quantite");
+        assert_eq!(inventory.items_added[0].uid, "c");
+        assert_eq!(inventory.items_removed[0].uid, "b");
+        assert_eq!(sections[1].changes[0].new, Some(Value::from(3)));
+        assert_eq!(sections[2].changes[0].path, "/vitals/pv/current");
+    }
+}
@@ -0,0 +1,403 @@
+use crate::db::{load_personnage_data, log_personnage_event, AppState};
+use crate::sheet::lenient_i64;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+// Below 0 PV the character is dying, and dies once this threshold is reached
+pub const SEUIL_MORT: i64 = -5;
+
+/// `{ current, max, temp }` as stored in `vitals.pv` / `vitals.pm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jauge {
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub current: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub max: i64,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub temp: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pool {
+    Pv,
+    Pm,
+}
+
+impl Pool {
+    fn key(&self) -> &'static str {
+        match self {
+            Pool::Pv => "pv",
+            Pool::Pm => "pm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EtatVital {
+    Indemne,
+    Blesse,
+    Inconscient,
+    Agonisant,
+    Mort,
+}
+
+pub fn etat_vital(pv: &Jauge) -> EtatVital {
+    if pv.current <= SEUIL_MORT {
+        EtatVital::Mort
+    } else if pv.current < 0 {
+        EtatVital::Agonisant
+    } else if pv.current == 0 {
+        EtatVital::Inconscient
+    } else if pv.current < pv.max {
+        EtatVital::Blesse
+    } else {
+        EtatVital::Indemne
+    }
+}
+
+/// What a single operation did to a gauge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variation {
+    /// Points taken from (or given to) the temporary pool
+    pub temp: i64,
+    /// Points taken from (or given to) the current value
+    pub current: i64,
+}
+
+impl Jauge {
+    /// Temporary points absorb the loss first. PV may drop below zero, down to the death threshold.
+    pub fn subir(&mut self, amount: i64, plancher: i64) -> Variation {
+        let amount = amount.max(0);
+        let absorbed = amount.min(self.temp.max(0));
+        self.temp -= absorbed;
+        let before = self.current;
+        self.current = (self.current - (amount - absorbed)).max(plancher);
+        Variation {
+            temp: -absorbed,
+            current: self.current - before,
+        }
+    }
+
+    /// Spends points without going below zero; temporary points are used first.
+    pub fn depenser(&mut self, amount: i64) -> Result<Variation, String> {
+        let amount = amount.max(0);
+        if amount > self.temp.max(0) + self.current.max(0) {
+            return Err(format!(
+                "Pas assez de points: {} demandés, {} disponibles",
+                amount,
+                self.temp.max(0) + self.current.max(0)
+            ));
+        }
+        Ok(self.subir(amount, 0))
+    }
+
+    /// Restores points, clamped to max.
+    pub fn recuperer(&mut self, amount: i64) -> Variation {
+        let before = self.current;
+        self.current = (self.current + amount.max(0)).min(self.max).max(before);
+        Variation {
+            temp: 0,
+            current: self.current - before,
+        }
+    }
+
+    pub fn definir_temp(&mut self, value: i64) -> Variation {
+        let before = self.temp;
+        self.temp = value.max(0);
+        Variation {
+            temp: self.temp - before,
+            current: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VitalsReport {
+    pub pv: Jauge,
+    pub pm: Jauge,
+    pub etat: EtatVital,
+    pub event: VitalsEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VitalsEvent {
+    pub event_id: i64,
+    pub kind: String,
+    pub pool: Pool,
+    pub amount: i64,
+    pub variation: Variation,
+    pub etat: EtatVital,
+    pub note: String,
+    pub created_at: String,
+}
+
+/// A missing gauge reads as 0/0; one that cannot be read is an error, so it is
+/// never written back as zeros.
+pub fn read_jauge(data: &Value, pool: Pool) -> Result<Jauge, String> {
+    match data.get("vitals").and_then(|v| v.get(pool.key())) {
+        None | Some(Value::Null) => Ok(Jauge::default()),
+        Some(jauge) => serde_json::from_value(jauge.clone())
+            .map_err(|e| format!("Jauge {} illisible: {}", pool.key(), e)),
+    }
+}
+
+/// Runs one operation on a gauge and persists it as a patch of `vitals` plus an event
+/// row, instead of a full `save_personnage_local` round-trip.
+fn run_operation(
+    conn: &mut Connection,
+    id: &str,
+    pool: Pool,
+    kind: &str,
+    amount: i64,
+    note: Option<String>,
+    operation: impl FnOnce(&mut Jauge) -> Result<Variation, String>,
+) -> Result<VitalsReport, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let data = load_personnage_data(&tx, id)?;
+    let mut vitals = data
+        .get("vitals")
+        .cloned()
+        .filter(|v| v.is_object())
+        .unwrap_or_else(|| serde_json::json!({}));
+    let mut pv = read_jauge(&data, Pool::Pv)?;
+    let mut pm = read_jauge(&data, Pool::Pm)?;
+
+    let variation = match pool {
+        Pool::Pv => operation(&mut pv)?,
+        Pool::Pm => operation(&mut pm)?,
+    };
+    let etat = etat_vital(&pv);
+
+    vitals["pv"] = serde_json::to_value(pv).map_err(|e| e.to_string())?;
+    vitals["pm"] = serde_json::to_value(pm).map_err(|e| e.to_string())?;
+
+    let mut patched = data.clone();
+    patched["vitals"] = vitals.clone();
+    crate::journal::record_change(&tx, id, &data, &patched)?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    tx.execute(
+        "UPDATE personnages SET data = json_set(data, '$.vitals', json(?1)), updated_at = ?2, version_label = NULL
+         WHERE id = ?3",
+        params![vitals.to_string(), now, id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let note = note.unwrap_or_default();
+    let payload = serde_json::json!({
+        "pool": pool,
+        "amount": amount,
+        "variation": variation,
+        "etat": etat,
+        "note": note,
+    });
+    let event_id = log_personnage_event(&tx, id, kind, &payload, &now)?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(VitalsReport {
+        pv,
+        pm,
+        etat,
+        event: VitalsEvent {
+            event_id,
+            kind: kind.to_string(),
+            pool,
+            amount,
+            variation,
+            etat,
+            note,
+            created_at: now,
+        },
+    })
+}
+
+#[tauri::command]
+pub fn apply_damage(
+    id: String,
+    amount: i64,
+    note: Option<String>,
+    state: State<AppState>,
+) -> Result<VitalsReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    run_operation(&mut db, &id, Pool::Pv, "damage", amount, note, |pv| {
+        Ok(pv.subir(amount, SEUIL_MORT))
+    })
+}
+
+#[tauri::command]
+pub fn heal(
+    id: String,
+    amount: i64,
+    note: Option<String>,
+    state: State<AppState>,
+) -> Result<VitalsReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    run_operation(&mut db, &id, Pool::Pv, "heal", amount, note, |pv| {
+        if etat_vital(pv) == EtatVital::Mort {
+            return Err("Le personnage est mort".to_string());
+        }
+        Ok(pv.recuperer(amount))
+    })
+}
+
+#[tauri::command]
+pub fn spend_pm(
+    id: String,
+    amount: i64,
+    note: Option<String>,
+    state: State<AppState>,
+) -> Result<VitalsReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    run_operation(&mut db, &id, Pool::Pm, "spend_pm", amount, note, |pm| {
+        pm.depenser(amount)
+    })
+}
+
+#[tauri::command]
+pub fn restore_pm(
+    id: String,
+    amount: i64,
+    note: Option<String>,
+    state: State<AppState>,
+) -> Result<VitalsReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    run_operation(&mut db, &id, Pool::Pm, "restore_pm", amount, note, |pm| {
+        Ok(pm.recuperer(amount))
+    })
+}
+
+/// Sets the temporary PV or PM (potions, spells...). Replaces any previous value.
+#[tauri::command]
+pub fn set_temp(
+    id: String,
+    pool: Pool,
+    value: i64,
+    note: Option<String>,
+    state: State<AppState>,
+) -> Result<VitalsReport, String> {
+    let mut db = state.db.lock().map_err(|e| e.to_string())?;
+    run_operation(&mut db, &id, pool, "set_temp", value, note, |jauge| {
+        Ok(jauge.definir_temp(value))
+    })
+}
+
+#[tauri::command]
+pub fn get_vitals_events(
+    id: String,
+    limit: Option<i64>,
+    state: State<AppState>,
+) -> Result<Vec<VitalsEvent>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare(
+            "SELECT event_id, kind, payload, created_at FROM personnages_events
+             WHERE personnage_id = ?1 AND kind IN ('damage', 'heal', 'spend_pm', 'restore_pm', 'set_temp')
+             ORDER BY event_id DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![id, limit.unwrap_or(-1)], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut events = Vec::new();
+    for row in rows {
+        let (event_id, kind, payload, created_at) = row.map_err(|e| e.to_string())?;
+        let payload: Value = serde_json::from_str(&payload).map_err(|e| e.to_string())?;
+        events.push(VitalsEvent {
+            event_id,
+            kind,
+            pool: serde_json::from_value(payload["pool"].clone()).map_err(|e| e.to_string())?,
+            amount: payload["amount"].as_i64().unwrap_or(0),
+            variation: serde_json::from_value(payload["variation"].clone()).unwrap_or_default(),
+            etat: serde_json::from_value(payload["etat"].clone()).map_err(|e| e.to_string())?,
+            note: payload["note"].as_str().unwrap_or_default().to_string(),
+            created_at,
+        });
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jauge(current: i64, max: i64, temp: i64) -> Jauge {
+        Jauge { current, max, temp }
+    }
+
+    #[test]
+    fn test_gauge_reads_numeric_strings_only() {
+        let data =
+            serde_json::json!({ "vitals": { "pv": { "current": "12", "max": 20.0, "temp": "" } } });
+        assert_eq!(read_jauge(&data, Pool::Pv).unwrap(), jauge(12, 20, 0));
+
+        let corrompu = serde_json::json!({ "vitals": { "pv": { "current": "abc", "max": 20 } } });
+        let erreur = read_jauge(&corrompu, Pool::Pv).unwrap_err();
+        assert!(erreur.contains("abc"), "{}", erreur);
+    }
+
+    #[test]
+    fn test_damage_absorbed_by_temp_first() {
+        let mut pv = jauge(20, 30, 5);
+        let variation = pv.subir(8, SEUIL_MORT);
+        assert_eq!(
+            variation,
+            Variation {
+                temp: -5,
+                current: -3
+            }
+        );
+        assert_eq!(pv, jauge(17, 30, 0));
+    }
+
+    #[test]
+    fn test_gauge_read_leniently_or_refused() {
+        let data = serde_json::json!({ "vitals": { "pv": { "current": "12", "max": 20.0 } } });
+        assert_eq!(read_jauge(&data, Pool::Pv).unwrap(), jauge(12, 20, 0));
+        assert_eq!(read_jauge(&data, Pool::Pm).unwrap(), Jauge::default());
+
+        let data = serde_json::json!({ "vitals": { "pv": "douze" } });
+        assert!(read_jauge(&data, Pool::Pv).is_err());
+    }
+
+    #[test]
+    fn test_damage_states_and_death_threshold() {
+        let mut pv = jauge(3, 30, 0);
+        pv.subir(3, SEUIL_MORT);
+        assert_eq!(etat_vital(&pv), EtatVital::Inconscient);
+        pv.subir(2, SEUIL_MORT);
+        assert_eq!(etat_vital(&pv), EtatVital::Agonisant);
+        pv.subir(50, SEUIL_MORT);
+        assert_eq!(pv.current, SEUIL_MORT);
+        assert_eq!(etat_vital(&pv), EtatVital::Mort);
+    }
+
+    #[test]
+    fn test_heal_clamped_to_max() {
+        let mut pv = jauge(25, 30, 0);
+        assert_eq!(pv.recuperer(10).current, 5);
+        assert_eq!(pv.current, 30);
+    }
+
+    #[test]
+    fn test_spend_pm_requires_enough_points() {
+        let mut pm = jauge(4, 20, 2);
+        assert!(pm.depenser(7).is_err());
+        pm.depenser(5).unwrap();
+        assert_eq!(pm, jauge(1, 20, 0));
+    }
+}
@@ -1,7 +1,7 @@
 import { useEffect, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { ask } from "@tauri-apps/plugin-dialog";
-import { CharacterSummary } from '../../types';
+import { CharacterSummary, VersionSummary } from '../../types';
 import { supabase } from "../../lib/supabase";
 
 interface CharacterSelectionProps {
@@ -10,7 +10,7 @@ interface CharacterSelectionProps {
 }
 
 export function CharacterSelection({ onSelect, isAuthenticated }: CharacterSelectionProps) {
-    const [historyData, setHistoryData] = useState<{ id: string, name: string, versions: { version_id: number, saved_at: string }[] } | null>(null);
+    const [historyData, setHistoryData] = useState<{ id: string, name: string, versions: VersionSummary[] } | null>(null);
     const [characters, setCharacters] = useState<CharacterSummary[]>([]);
     const [loading, setLoading] = useState(true);
     const [error, setError] = useState<string | null>(null);
@@ -76,7 +76,7 @@ export function CharacterSelection({ onSelect, isAuthenticated }: CharacterSelec
 
     const handleViewHistory = async (id: string, name: string) => {
         try {
-            const versions = await invoke<{ version_id: number, saved_at: string }[]>("get_personnage_versions", { id });
+            const versions = await invoke<VersionSummary[]>("get_personnage_versions", { id });
             setHistoryData({ id, name, versions });
         } catch (err) {
             showToast("Impossible de charger l'historique : " + String(err), 'error');
@@ -140,7 +140,11 @@ export function CharacterSelection({ onSelect, isAuthenticated }: CharacterSelec
                                             </span>
                                             <span className="text-xs text-leather/70">
                                                 {new Date(v.saved_at).toLocaleTimeString()}
+                                                {v.client && ` · ${v.client}`}
                                             </span>
+                                            {v.label && (
+                                                <span className="text-xs italic text-leather">{v.label}</span>
+                                            )}
                                         </div>
                                         <button
                                             onClick={() => handleRestore(historyData.id, v.version_id)}
@@ -193,7 +197,7 @@ export function CharacterSelection({ onSelect, isAuthenticated }: CharacterSelec
                             <button
                                 onClick={async (e) => {
                                     e.stopPropagation();
-                                    const yes = await ask("Êtes-vous sûr de vouloir supprimer ce personnage ? Cette action est irréversible.", {
+                                    const yes = await ask("Envoyer ce personnage à la corbeille ? Il pourra être restauré tant que la corbeille n'est pas vidée.", {
                                         title: 'Confirmer la suppression',
                                         kind: 'warning'
                                     });
@@ -295,7 +299,8 @@ export function CharacterSelection({ onSelect, isAuthenticated }: CharacterSelec
                                                 id: char.id,
                                                 name: char.nom,
                                                 data: JSON.stringify(char.data),
-                                                updatedAt: char.updated_at
+                                                updatedAt: char.updated_at,
+                                                deletedAt: char.deleted_at ?? null
                                             });
                                         }
                                         // 3. Refresh list
@@ -2,6 +2,9 @@ import { SmartInput } from '../../Shared/SmartInput';
 import { v4 as uuidv4 } from 'uuid';
 import { useState } from 'react';
 import { Mount } from '../../../types';
+import { useRefContext } from '../../../context/RefContext';
+import { getContenuWeight } from '../../../utils/sacUtils';
+import { ContenuList } from '../Sac/ContenuList';
 
 interface MonturePanelProps {
     mounts: Mount[];
@@ -37,6 +40,7 @@ const emptyMount: Omit<Mount, 'uid'> = {
 
 export const MonturePanel: React.FC<MonturePanelProps> = ({ mounts = [], onMountsChange }) => {
 
+    const { refs } = useRefContext();
     const [mountToDelete, setMountToDelete] = useState<string | null>(null);
 
     const handleAddMount = () => {
@@ -219,6 +223,17 @@ export const MonturePanel: React.FC<MonturePanelProps> = ({ mounts = [], onMount
                                         </div>
                                     </div>
 
+                                    {(mount.inventaire?.length ?? 0) > 0 && (
+                                        <div className="p-3 bg-parchment/30 rounded border border-leather/20">
+                                            <div className="flex justify-between items-baseline mb-2 border-b border-leather/20 pb-1">
+                                                <span className="text-xs font-bold uppercase text-leather/70">Chargement</span>
+                                                <span className="text-xs text-ink-light">
+                                                    {getContenuWeight(mount.inventaire, refs)} g{mount.charge_max && ` / ${mount.charge_max}`}
+                                                </span>
+                                            </div>
+                                            <ContenuList items={mount.inventaire || []} referenceOptions={refs} />
+                                        </div>
+                                    )}
                                 </div>
 
                                 {/* Colonne Droite: Caractéristiques & Textes */}
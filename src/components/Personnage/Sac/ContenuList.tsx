@@ -0,0 +1,37 @@
+import React from 'react';
+import { ObjetContenu, RefEquipement } from '../../../types';
+import { getStoredItemWeight } from '../../../utils/sacUtils';
+
+interface ContenuListProps {
+    items: ObjetContenu[];
+    referenceOptions: RefEquipement[];
+}
+
+// Read-only view of what a bag or pouch holds (`contenu`), nested containers included.
+// Items are moved in and out through the inventory commands, not edited here.
+export const ContenuList: React.FC<ContenuListProps> = ({ items, referenceOptions }) => {
+    if (items.length === 0) return null;
+
+    return (
+        <ul className="pl-4 border-l border-leather/20 space-y-0.5">
+            {items.map(item => {
+                const refItem = referenceOptions.find(r => r.id === item.refId);
+                const weight = getStoredItemWeight(item, referenceOptions);
+                return (
+                    <li key={item.uid} className="text-xs text-ink">
+                        <div className="flex justify-between gap-4">
+                            <span>
+                                <span className="font-medium">{item.quantite ?? 1} ×</span>{' '}
+                                {refItem?.nom || item.nom || 'Objet inconnu'}
+                            </span>
+                            <span className="text-ink-light">{weight > 0 ? `${weight} g` : '-'}</span>
+                        </div>
+                        {item.contenu && item.contenu.length > 0 && (
+                            <ContenuList items={item.contenu} referenceOptions={referenceOptions} />
+                        )}
+                    </li>
+                );
+            })}
+        </ul>
+    );
+};
@@ -34,7 +34,9 @@ export const SacDetails: React.FC<SacDetailsProps> = ({
                 refId: refItem.id,
                 equipement_type: 'Sacs',
                 etat: 'Intact',
-                modif_rupture: 0
+                modif_rupture: 0,
+                // Changing bag keeps what was stored in it
+                contenu: sac?.contenu
             };
             onSacChange(newItem);
         }
@@ -6,7 +6,8 @@ import { SacDetails } from './SacDetails';
 import { SacItemSelector } from './SacItemSelector';
 import { SacCustomTable } from './SacCustomTable';
 import { v4 as uuidv4 } from 'uuid';
-import { getItemWeight } from '../../../utils/sacUtils';
+import { getContenuWeight, getStoredItemWeight } from '../../../utils/sacUtils';
+import { ContenuList } from './ContenuList';
 
 interface SacPanelProps {
     inventory: Sac[];
@@ -101,19 +102,21 @@ export const SacPanel: React.FC<SacPanelProps> = ({ inventory, onInventoryChange
         handleContentChange(newSacList as Sac[]);
     };
 
-    // Calculate Standard Content Weight
+    // Calculate Standard Content Weight (nested bags and pouches with what they hold)
     const standardContentWeight = sacContentItems.reduce((acc, item) => {
-        const refItem = refs.find(r => r.id === item.refId);
-        const unitWeight = getItemWeight(refItem);
-        return acc + (unitWeight * (item.quantite ?? 1));
+        return acc + getStoredItemWeight(item, refs);
     }, 0);
 
+    // Items stored in the backpack itself (`contenu`)
+    const backpackContenu = backpack?.contenu || [];
+    const backpackContenuWeight = getContenuWeight(backpackContenu, refs);
+
     // Calculate Custom Content Weight
     const customContentWeight = (customItems || []).reduce((acc, item) => {
         return acc + (item.poids * item.quantite);
     }, 0);
 
-    const totalWeight = standardContentWeight + customContentWeight;
+    const totalWeight = standardContentWeight + customContentWeight + backpackContenuWeight;
 
     // Optimize: Memoize filtered references
     const backpackRefOptions = React.useMemo(() => refs.filter(r => r.category === 'Sacs'), [refs]);
@@ -135,6 +138,13 @@ export const SacPanel: React.FC<SacPanelProps> = ({ inventory, onInventoryChange
                     currentTotalWeight={totalWeight}
                 />
 
+                {backpackContenu.length > 0 && (
+                    <div className="mb-6 p-4 bg-parchment/30 rounded border border-leather/20">
+                        <h3 className="font-bold text-leather uppercase text-lg mb-2 border-b border-leather/20 pb-2">Rangé dans le sac</h3>
+                        <ContenuList items={backpackContenu} referenceOptions={refs} />
+                    </div>
+                )}
+
                 <SacItemSelector
                     referenceOptions={contentRefOptions}
                     onAddItem={handleAddItem}
@@ -1,8 +1,9 @@
 import React from 'react';
 import { Sac, RefEquipement } from '../../../types';
-import { getItemWeight, getMaxRuptureOptions } from '../../../utils/sacUtils';
+import { getItemWeight, getMaxRuptureOptions, getStoredItemWeight } from '../../../utils/sacUtils';
 import { SmartInput } from '../../Shared/SmartInput';
 import { Tooltip } from '../../Shared/Tooltip';
+import { ContenuList } from './ContenuList';
 
 interface SacTableProps {
     items: Sac[];
@@ -24,7 +25,8 @@ const SacRow = React.memo(({ item, referenceOptions, onUpdateQuantity, onRemove,
     const refItem = referenceOptions.find(r => r.id === item.refId);
     const effect = refItem?.effet || (refItem as any)?.details?.effet;
     const unitWeight = getItemWeight(refItem);
-    const totalItemWeight = unitWeight * (item.quantite ?? 1);
+    // Contents of a nested bag or pouch included
+    const totalItemWeight = getStoredItemWeight(item, referenceOptions);
     const displayRefId = refItem?.ref_id || '-';
 
     // Determine category from RefItem (or fallback to 'Sacs' if mismatch)
@@ -295,17 +297,27 @@ export const SacTable: React.FC<SacTableProps & { onUpdateNotes?: (uid: string,
                                     </tr>
                                     {/* Items in Category */}
                                     {categoryItems.map(item => (
-                                        <SacRow
-                                            key={item.uid}
-                                            item={item}
-                                            referenceOptions={referenceOptions}
-                                            onUpdateQuantity={(uid, qty) => handleUpdateField(uid, 'quantite', qty)}
-                                            onRemove={handleRemoveRow}
-                                            onUpdateNotes={onUpdateNotes}
-                                            onUpdateField={handleUpdateField}
-                                            onHover={handleItemHover}
-                                            onLeave={handleItemLeave}
-                                        />
+                                        <React.Fragment key={item.uid}>
+                                            <SacRow
+                                                item={item}
+                                                referenceOptions={referenceOptions}
+                                                onUpdateQuantity={(uid, qty) => handleUpdateField(uid, 'quantite', qty)}
+                                                onRemove={handleRemoveRow}
+                                                onUpdateNotes={onUpdateNotes}
+                                                onUpdateField={handleUpdateField}
+                                                onHover={handleItemHover}
+                                                onLeave={handleItemLeave}
+                                            />
+                                            {/* Contents of a bag or pouch stored in the bag */}
+                                            {item.contenu && item.contenu.length > 0 && (
+                                                <tr className="border-b border-leather/10">
+                                                    <td></td>
+                                                    <td colSpan={9} className="p-2 pt-0">
+                                                        <ContenuList items={item.contenu} referenceOptions={referenceOptions} />
+                                                    </td>
+                                                </tr>
+                                            )}
+                                        </React.Fragment>
                                     ))}
                                 </tbody>
                             );
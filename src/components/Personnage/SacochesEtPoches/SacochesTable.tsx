@@ -3,6 +3,8 @@ import { Equipement, RefEquipement } from '../../../types';
 import { v4 as uuidv4 } from 'uuid';
 import { SearchableSelect } from '../../Shared/SearchableSelect';
 import { calculateFinalRupture, getMaxRuptureOptions } from '../../../utils/sacUtils';
+import { ContenuList } from '../Sac/ContenuList';
+import { useRefContext } from '../../../context/RefContext';
 
 interface SacochesTableProps {
     items: Equipement[];
@@ -11,6 +13,8 @@ interface SacochesTableProps {
 }
 
 export const SacochesTable: React.FC<SacochesTableProps> = ({ items, onItemsChange, referenceOptions }) => {
+    // A pouch may hold anything, not only the Sacoches listed in referenceOptions
+    const { refs } = useRefContext();
 
     const handleAddRow = () => {
         const newItem: Equipement = {
@@ -84,50 +88,60 @@ export const SacochesTable: React.FC<SacochesTableProps> = ({ items, onItemsChan
                     {items.map(item => {
                         const refItem = referenceOptions.find(r => r.id === item.refId);
                         return (
-                            <tr key={item.uid} className="border-b border-leather/10 hover:bg-leather/5">
-                                <td className="p-2">
-                                    <SearchableSelect
-                                        options={referenceOptions.map(r => ({ id: r.id, label: r.nom }))}
-                                        value={item.refId}
-                                        onChange={(val) => handleSelectChange(item.uid, val)}
-                                        className="w-full"
-                                    />
-                                </td>
-                                <td className="p-2 text-center text-ink-light">
-                                    {refItem?.places || getRefValue(item.refId, 'details', 'places') || '-'}
-                                </td>
-                                <td className="p-2 italic text-ink-light truncate max-w-[200px]" title={refItem?.effet || getRefValue(item.refId, 'details', 'effet')}>
-                                    {refItem?.effet || getRefValue(item.refId, 'details', 'effet') || '-'}
-                                </td>
-                                <td className="p-2">
-                                    <select
-                                        value={item.etat || 'Intact'}
-                                        onChange={(e) => handleUpdateField(item.uid, 'etat', e.target.value)}
-                                        className="w-full p-1 bg-input-bg text-ink border-b border-leather-light focus:border-leather outline-none text-sm text-center"
-                                    >
-                                        <option value="Intact">Intact</option>
-                                        <option value="Endommagé">Endommagé</option>
-                                        <option value="Cassé">Cassé</option>
-                                    </select>
-                                </td>
-                                <td className="p-2 text-center text-ink-light">
-                                    {calculateFinalRupture(refItem?.rupture || getRefValue(item.refId, 'details', 'rupture'), item.modif_rupture)}
-                                </td>
-                                <td className="p-2">
-                                    <select
-                                        value={item.modif_rupture || 0}
-                                        onChange={(e) => handleUpdateField(item.uid, 'modif_rupture', parseInt(e.target.value) || 0)}
-                                        className="w-full bg-input-bg text-ink border-b border-leather/20 text-center focus:border-leather outline-none text-sm"
-                                    >
-                                        {getMaxRuptureOptions(refItem?.rupture || getRefValue(item.refId, 'details', 'rupture')).map(opt => (
-                                            <option key={opt} value={opt}>+{opt}</option>
-                                        ))}
-                                    </select>
-                                </td>
-                                <td className="p-2 text-center">
-                                    <button onClick={() => handleRemoveRow(item.uid)} className="text-red-500 hover:text-red-700 font-bold">&times;</button>
-                                </td>
-                            </tr>
+                            <React.Fragment key={item.uid}>
+                                <tr className="border-b border-leather/10 hover:bg-leather/5">
+                                    <td className="p-2">
+                                        <SearchableSelect
+                                            options={referenceOptions.map(r => ({ id: r.id, label: r.nom }))}
+                                            value={item.refId}
+                                            onChange={(val) => handleSelectChange(item.uid, val)}
+                                            className="w-full"
+                                        />
+                                    </td>
+                                    <td className="p-2 text-center text-ink-light">
+                                        {refItem?.places || getRefValue(item.refId, 'details', 'places') || '-'}
+                                    </td>
+                                    <td className="p-2 italic text-ink-light truncate max-w-[200px]" title={refItem?.effet || getRefValue(item.refId, 'details', 'effet')}>
+                                        {refItem?.effet || getRefValue(item.refId, 'details', 'effet') || '-'}
+                                    </td>
+                                    <td className="p-2">
+                                        <select
+                                            value={item.etat || 'Intact'}
+                                            onChange={(e) => handleUpdateField(item.uid, 'etat', e.target.value)}
+                                            className="w-full p-1 bg-input-bg text-ink border-b border-leather-light focus:border-leather outline-none text-sm text-center"
+                                        >
+                                            <option value="Intact">Intact</option>
+                                            <option value="Endommagé">Endommagé</option>
+                                            <option value="Cassé">Cassé</option>
+                                        </select>
+                                    </td>
+                                    <td className="p-2 text-center text-ink-light">
+                                        {calculateFinalRupture(refItem?.rupture || getRefValue(item.refId, 'details', 'rupture'), item.modif_rupture)}
+                                    </td>
+                                    <td className="p-2">
+                                        <select
+                                            value={item.modif_rupture || 0}
+                                            onChange={(e) => handleUpdateField(item.uid, 'modif_rupture', parseInt(e.target.value) || 0)}
+                                            className="w-full bg-input-bg text-ink border-b border-leather/20 text-center focus:border-leather outline-none text-sm"
+                                        >
+                                            {getMaxRuptureOptions(refItem?.rupture || getRefValue(item.refId, 'details', 'rupture')).map(opt => (
+                                                <option key={opt} value={opt}>+{opt}</option>
+                                            ))}
+                                        </select>
+                                    </td>
+                                    <td className="p-2 text-center">
+                                        <button onClick={() => handleRemoveRow(item.uid)} className="text-red-500 hover:text-red-700 font-bold">&times;</button>
+                                    </td>
+                                </tr>
+                                {/* What the pouch holds */}
+                                {item.contenu && item.contenu.length > 0 && (
+                                    <tr className="border-b border-leather/10">
+                                        <td colSpan={7} className="p-2 pt-0">
+                                            <ContenuList items={item.contenu} referenceOptions={refs} />
+                                        </td>
+                                    </tr>
+                                )}
+                            </React.Fragment>
                         );
                     })}
                 </tbody>
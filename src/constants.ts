@@ -18,6 +18,7 @@ export const INITIAL_DATA: CharacterData = {
     },
     general: {
         niveau: 1,
+        niveau_applique: 1,
         experience: 0,
         points_destin: 0,
         malus_tete: 0,
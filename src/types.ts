@@ -96,6 +96,18 @@ export interface Equipement {
     quantite?: number;
     etat?: string; // 'Intact', 'Endommagé', 'Cassé'
     equipement_type?: 'Armes' | 'Protections' | 'Accessoires' | 'MainsNues' | 'Sacoches' | 'Potions' | 'Objets_magiques' | 'Munitions' | 'Armes_de_jet' | 'Pieges' | 'Outils';    // Références aux catégories
+    contenu?: ObjetContenu[]; // Objets rangés dans une sacoche
+}
+
+// Objet rangé dans un sac ou une sacoche (rangement géré côté Rust) :
+// objet de référence (refId) ou objet personnalisé (nom, poids unitaire)
+export interface ObjetContenu {
+    uid: string;
+    refId?: number;
+    nom?: string;
+    poids?: number;
+    quantite?: number;
+    contenu?: ObjetContenu[]; // Un sac peut contenir une sacoche
 }
 
 export interface Sac {
@@ -111,6 +123,7 @@ export interface Sac {
     modif_pr_mag?: number;
     modif_pr_spe?: number;
     charges?: number;
+    contenu?: ObjetContenu[]; // Objets rangés dans ce sac
 }
 
 // Interface simplifiée pour les APE
@@ -157,6 +170,7 @@ export interface Vitals {
 // Interface pour le niveau, l'expérience, les points de destin et les blessures à la tête
 export interface GeneralStats {
     niveau: number;
+    niveau_applique?: number; // Dernier niveau dont les gains ont été appliqués (suit `niveau` sinon)
     experience: number;
     points_destin: number;
     malus_tete: number;
@@ -226,6 +240,17 @@ export interface TempModifiers {
     mod3: string;
 }
 
+// Effet temporaire structuré (voir src-tauri/src/modifiers.rs)
+export interface TempEffect {
+    uid: string;
+    cible: string; // Clé de la fiche: 'courage', 'magie_physique', 'marche', 'pr_sol'...
+    valeur: number;
+    source: string;
+    duree: { valeur: number; unite: 'rounds' | 'heures' | 'jours' } | null;
+    cumul?: 'cumulable' | 'remplace' | 'plus_fort';
+    restant: number | null; // Rounds, ou minutes de jeu
+}
+
 export interface CustomSacItem {
     uid: string;
     nom: string;
@@ -252,6 +277,7 @@ export interface CharacterData {
     magic: MagicStealth;
     characteristics: Characteristics;
     temp_modifiers: TempModifiers;
+    temp_effects?: TempEffect[]; // Effets temporaires structurés (gérés côté Rust)
     inventory: any[]; // Placeholder for now, will link to existing inventory structure
     custom_sac_items: CustomSacItem[]; // Nouveaux objets personnalisés du sac
     catalogue?: CatalogueItem[]; // Liste d'achats du catalogue
@@ -298,6 +324,7 @@ export interface CharacterStatus {
     fatigue: Fatigue;
     alcohol: Alcohol;
     drug: Drug;
+    monture_active?: string; // uid de la monture chevauchée (géré côté Rust)
 }
 
 // Interfaces pour la Richesse
@@ -355,6 +382,7 @@ export interface Mount {
     lieux: string;
     charge_max: string;
     at_speciales: string;
+    inventaire?: ObjetContenu[]; // Objets chargés sur la monture (rangement géré côté Rust)
 }
 
 // Interface pour le résumé du personnage
@@ -368,6 +396,9 @@ export interface CharacterSummary {
 export interface VersionSummary {
     version_id: number;
     saved_at: string;
+    label: string | null;
+    client: string | null;
+    pinned: boolean;
 }
 
 // Interface pour les composants de statistiques
@@ -402,6 +433,7 @@ export interface Origine {
     vitesse: number;
     metiers_impossibles?: string[];
     competences?: string[];
+    nyctalopie?: string | null; // "Moyenne" | "Totale"
 }
 
 export interface SousSpecialisation {
@@ -1,4 +1,4 @@
-import { RefEquipement } from '../types';
+import { ObjetContenu, RefEquipement } from '../types';
 
 /**
  * Calculates the weight of an item based on specific rules.
@@ -35,6 +35,31 @@ export const getItemWeight = (refItem: RefEquipement | undefined): number => {
     return rawWeight;
 };
 
+/**
+ * Total weight of a stored item: unit weight times quantity, plus whatever it
+ * holds in `contenu` (bags and pouches), recursively.
+ * Custom items carry their own unit weight in `poids`.
+ * Mirrors `poids_objet` in src-tauri/src/inventory.rs.
+ * @param item The item, as stored in the inventory or in a `contenu`.
+ * @param refs The reference items.
+ * @returns The weight in grams.
+ */
+export const getStoredItemWeight = (item: ObjetContenu, refs: RefEquipement[]): number => {
+    const unitWeight = item.poids !== undefined
+        ? Number(item.poids) || 0
+        : getItemWeight(refs.find(r => r.id === item.refId));
+    return unitWeight * (item.quantite ?? 1) + getContenuWeight(item.contenu, refs);
+};
+
+/**
+ * Total weight of a `contenu` list.
+ * @param items The stored items (may be undefined).
+ * @param refs The reference items.
+ * @returns The weight in grams.
+ */
+export const getContenuWeight = (items: ObjetContenu[] | undefined, refs: RefEquipement[]): number =>
+    (items || []).reduce((acc, item) => acc + getStoredItemWeight(item, refs), 0);
+
 /**
  * Normalizes a rupture string to a numeric value (taking the upper bound of a range).
  * "Non", "NON", "" -> 0
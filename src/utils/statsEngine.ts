@@ -0,0 +1,369 @@
+import { CharacterData, CharacterStatus, CharacteristicColumn, Characteristics, GameRules, Origine, RefEquipementRaw, StatComponent, StatDetail, TempEffect } from '../types';
+import { getAlcoholModifiers } from './alcohol';
+import { getContenuWeight, getStoredItemWeight } from './sacUtils';
+
+// The stats engine behind the character sheet, mirroring src-tauri/src/logic.rs.
+// Both run src-tauri/tests/fixtures/stats_parity.json (`npm run test:stats`):
+// a rule changed on one side must be changed on the other.
+
+export interface EquippedValue {
+    value: number;
+    components: (StatComponent & { displayValue?: string })[];
+    overrideDisplay?: string;
+}
+
+export type EquippedValues = Record<keyof Characteristics, EquippedValue>;
+
+export interface DerivedStat {
+    value: number;
+    details: StatDetail;
+}
+
+// Reference items as `get_ref_items` returns them
+type Ref = RefEquipementRaw;
+
+/** Characteristics of the sheet, with the abbreviation the rules files use. */
+export const CHARACTERISTIC_ABBREVIATIONS: Record<keyof Characteristics, string> = {
+    courage: 'COU',
+    intelligence: 'INT',
+    charisme: 'CHA',
+    adresse: 'AD',
+    force: 'FO',
+    perception: 'PER',
+    esquive: 'ES',
+    attaque: 'AT',
+    parade: 'PRD',
+    degats: 'DEG'
+};
+
+/** Esquive modifier standing for "Impossible", above 7 PR solide. */
+export const DODGE_IMPOSSIBLE = -999;
+
+/** Whole number from a sheet or reference value; anything else reads 0. */
+const toInt = (value: unknown): number => parseInt(String(value ?? 0), 10) || 0;
+
+export const getFatigueModifier = (etat: string | undefined) => {
+    if (etat === 'Reposé') return 1;
+    if (etat && etat.startsWith('Epuisé')) {
+        const parts = etat.split(' ');
+        if (parts.length > 1) {
+            const level = parseInt(parts[1], 10);
+            return -level;
+        }
+    }
+    return 0; // Normal, Fatigué
+};
+
+/**
+ * `attributs_automatisables` of the specialisation and sub-specialisation, keyed
+ * as in metiers.json ("AT", "RM", "MVTm"...). A sub-specialisation only counts
+ * under the specialisation it belongs to.
+ */
+export const getSpecBonuses = (data: CharacterData, gameRules: GameRules | null) => {
+    const specBonuses: { [key: string]: number } = {};
+    const subSpecBonuses: { [key: string]: number } = {};
+    const { metier, specialisation, sous_specialisation } = data.identity;
+
+    const currentMetier = gameRules?.metiers.find(m => m.name_m === metier || m.name_f === metier);
+    const spec = specialisation
+        ? currentMetier?.specialisations?.find(s => s.name_m === specialisation || s.name_f === specialisation)
+        : undefined;
+    const subSpec = sous_specialisation
+        ? spec?.sous_specialisations?.find(s => s.name_m === sous_specialisation || s.name_f === sous_specialisation)
+        : undefined;
+
+    for (const [key, value] of Object.entries(spec?.attributs_automatisables || {})) {
+        if (typeof value === 'number') specBonuses[key] = value;
+    }
+    for (const [key, value] of Object.entries(subSpec?.attributs_automatisables || {})) {
+        if (typeof value === 'number') subSpecBonuses[key] = value;
+    }
+    return { specBonuses, subSpecBonuses };
+};
+
+/** Protections and accessories worn; a shield only while it is active. */
+export const getWornItems = (data: CharacterData, refs: Ref[]) =>
+    data.inventory
+        .filter(item => ['Protections', 'Accessoires'].includes(item.equipement_type as string))
+        .map(item => ({ item, ref: refs.find(r => r.id === item.refId) }))
+        .filter(({ ref }) => ref?.details?.type !== 'Bouclier' || data.defenses.bouclier_actif);
+
+/**
+ * Temporary effects on `cible` that currently apply: `plus_fort` effects only
+ * keep the strongest bonus and the strongest malus.
+ */
+export const getAppliedEffects = (data: CharacterData, cible: string): StatComponent[] => {
+    const effects: TempEffect[] = Array.isArray(data.temp_effects) ? data.temp_effects : [];
+    const onTarget = effects.filter(e => e.cible === cible && e.valeur !== 0);
+    const strongest = (bonus: boolean) => onTarget
+        .filter(e => e.cumul === 'plus_fort' && (e.valeur > 0) === bonus)
+        .reduce<TempEffect | undefined>((best, e) => !best || Math.abs(e.valeur) >= Math.abs(best.valeur) ? e : best, undefined);
+
+    return [
+        ...onTarget.filter(e => e.cumul !== 'plus_fort'),
+        strongest(true),
+        strongest(false)
+    ]
+        .filter((e): e is TempEffect => e !== undefined)
+        .map(e => ({ label: e.source, value: e.valeur }));
+};
+
+const ABBREVIATION_KEYS: { [abbreviation: string]: keyof Characteristics } = {
+    COU: 'courage',
+    INT: 'intelligence',
+    CHA: 'charisme',
+    AD: 'adresse',
+    FO: 'force',
+    PER: 'perception',
+    ES: 'esquive',
+    AT: 'attaque',
+    PRD: 'parade'
+};
+
+/** Rider bonus of the mount the character is on, parsed from "+1 AT, -1 PRD". */
+export const getRiderBonus = (data: CharacterData) => {
+    const uid = data.status?.monture_active;
+    const mount = uid ? (data.mounts || []).find(m => m.uid === uid) : undefined;
+    if (!mount) return null;
+
+    const bonuses: { [key: string]: number } = {};
+    for (const part of String(mount.bonus_cavalier || '').split(/[,;/]/)) {
+        const [value, abbreviation] = part.trim().split(/\s+/);
+        const key = ABBREVIATION_KEYS[(abbreviation || '').toUpperCase()];
+        if (key && /^[+-]?\d+$/.test(value)) {
+            bonuses[key] = (bonuses[key] || 0) + parseInt(value, 10);
+        }
+    }
+    return { nom: mount.nom, bonuses };
+};
+
+/** PR solide of the worn equipment, plus the temporary value and effects. */
+export const getPrSolideEncumbrance = (data: CharacterData, refs: Ref[]) =>
+    getWornItems(data, refs).reduce((acc, { item, ref }) => acc + toInt(ref?.protections?.pr_sol) + toInt(item.modif_pr_sol), 0)
+    + toInt(data.defenses.solide.temp)
+    + getAppliedEffects(data, 'pr_sol').reduce((acc, c) => acc + c.value, 0);
+
+/** Esquive modifier for the PR solide worn: +1 up to 1, then down to "Impossible" above 7. */
+export const getDodgeEncumbrance = (prSolide: number) => {
+    if (prSolide >= 0 && prSolide <= 1) return 1;
+    if (prSolide >= 3 && prSolide <= 4) return -2;
+    if (prSolide === 5) return -4;
+    if (prSolide === 6) return -5;
+    if (prSolide === 7) return -6;
+    if (prSolide > 7) return DODGE_IMPOSSIBLE;
+    return 0;
+};
+
+/**
+ * -2 esquive once the backpack holds 90% of its capacity. The backpack is the
+ * first `Sacs` row whose reference is a bag; the other rows count as its load.
+ */
+export const getSacMalus = (data: CharacterData, refs: Ref[]) => {
+    const sacItems = data.inventory.filter(i => i.equipement_type === 'Sacs');
+    const backpack = sacItems.find(i => refs.find(r => r.id === i.refId)?.category === 'Sacs');
+    if (!backpack) return 0;
+
+    const capacity = toInt(refs.find(r => r.id === backpack.refId)?.details?.capacite);
+    const contentWeight = sacItems
+        .filter(i => i.uid !== backpack.uid)
+        .reduce((acc, item) => acc + getStoredItemWeight(item, refs as any), 0)
+        + getContenuWeight(backpack.contenu, refs as any);
+
+    return capacity > 0 && contentWeight >= 0.9 * capacity ? -2 : 0;
+};
+
+/**
+ * The "Equipé" column of the characteristics table: naturel and T1..T3, state
+ * modifiers (head, fatigue, alcohol, drug), the AD > 12 bonus, specialisation
+ * bonuses, encumbrance and an overloaded bag on esquive, worn items, the rider
+ * bonus and temporary effects.
+ */
+export const computeEquippedValues = (data: CharacterData, refs: Ref[], gameRules: GameRules | null): EquippedValues => {
+    const fatigueMod = getFatigueModifier(data.status?.fatigue?.etat);
+    const etatFatigue = data.status?.fatigue?.etat || 'Normal';
+    // Missing doses read 0
+    const { leger, fort, gueule_de_bois } = getAlcoholModifiers(data.status || ({} as CharacterStatus));
+    const isFlibustier = data.identity.specialisation?.toLowerCase() === 'flibustier';
+    const drug = data.status?.drug || { type: 'Aucune', jours_retard: 0 };
+    let drugMalus = 0;
+    if (drug.type === 'ADD') drugMalus = -Math.floor(toInt(drug.jours_retard) / 2);
+    else if (drug.type === 'ADD+' || drug.type === 'ADD++') drugMalus = -toInt(drug.jours_retard);
+    const malusTete = toInt(data.general.malus_tete);
+
+    const { specBonuses, subSpecBonuses } = getSpecBonuses(data, gameRules);
+    const prSolide = getPrSolideEncumbrance(data, refs);
+    const dodgeEncumbrance = getDodgeEncumbrance(prSolide);
+    const sacMalus = getSacMalus(data, refs);
+    const wornItems = getWornItems(data, refs);
+    const rider = getRiderBonus(data);
+
+    const values = {} as EquippedValues;
+    (Object.keys(CHARACTERISTIC_ABBREVIATIONS) as Array<keyof Characteristics>).forEach(key => {
+        const char: Partial<CharacteristicColumn> = data.characteristics[key] || {};
+        const entry: EquippedValue = { value: 0, components: [] };
+        const push = (label: string, value: number, displayValue?: string) => {
+            if (value === 0) return;
+            entry.value += value;
+            entry.components.push(displayValue ? { label, value, displayValue } : { label, value });
+        };
+
+        push('Naturel', toInt(char.naturel));
+        push('T1', toInt(char.t1));
+        push('T2', toInt(char.t2));
+        push('T3', toInt(char.t3));
+        push('Malus Tête', -malusTete);
+        push(`Etat de fatigue (${etatFatigue})`, fatigueMod);
+
+        if (key in leger) {
+            push('Alcool (léger)', leger[key as keyof typeof leger]);
+            const strong = fort[key as keyof typeof fort];
+            // A Flibustier never gets a malus from strong alcohol
+            push('Alcool (fort)', isFlibustier && strong < 0 ? 0 : strong);
+            push('Gueule de bois', gueule_de_bois[key as keyof typeof gueule_de_bois]);
+        }
+        push(`Manque (Drogue: ${drug.type})`, drugMalus);
+
+        if ((key === 'attaque' && data.general.bonus_ad_12 === 'AT') || (key === 'parade' && data.general.bonus_ad_12 === 'PRD')) {
+            push('Base AD > 12', 1);
+        }
+
+        const abbreviation = CHARACTERISTIC_ABBREVIATIONS[key];
+        push('Spécialisation', specBonuses[abbreviation] || 0);
+        push('Sous-spécialisation', subSpecBonuses[abbreviation] || 0);
+
+        if (key === 'esquive') {
+            const label = dodgeEncumbrance > 0 ? 'Légèreté' : 'Encombrement';
+            push(`${label} (PR Sol. ${prSolide})`, dodgeEncumbrance, prSolide > 7 ? 'Impossible' : undefined);
+            if (prSolide > 7) entry.overrideDisplay = 'Imp.';
+            push('Sac surchargé', sacMalus);
+        }
+
+        wornItems.forEach(({ item, ref }) => push(ref?.nom || item.nom, toInt(ref?.caracteristiques?.[key])));
+        if (rider) push(`Bonus cavalier (${rider.nom})`, rider.bonuses[key] || 0);
+        getAppliedEffects(data, key).forEach(c => push(c.label, c.value));
+
+        values[key] = entry;
+    });
+    return values;
+};
+
+const derived = (formula: string): DerivedStat => ({ value: 0, details: { formula, components: [], total: 0 } });
+
+const pushTo = (stat: DerivedStat, label: string, value: number) => {
+    if (value === 0) return;
+    stat.value += value;
+    stat.details.total = stat.value;
+    stat.details.components.push({ label, value });
+};
+
+/** RM modifier of the corruption tier reached, as `palier_corruption` in commands.rs. */
+const getCorruptionRm = (data: CharacterData, gameRules: GameRules | null) => {
+    const corruption = toInt(data.vitals?.corruption?.current);
+    const tier = (gameRules?.corruption_palier || [])
+        .filter(p => p.Paliers <= corruption)
+        .reduce<GameRules['corruption_palier'][number] | undefined>((best, p) => !best || p.Paliers >= best.Paliers ? p : best, undefined);
+    return tier ? tier['Résistance magique (RM)'] : 0;
+};
+
+/**
+ * Protections, magic and stealth, environment protections and movement, from
+ * the equipped characteristics. The panels add the `temp` of each value on top.
+ */
+export const computeDerivedStats = (data: CharacterData, refs: Ref[], gameRules: GameRules | null, equippedValues: EquippedValues) => {
+    const totals = {
+        solide: derived('Protections + Accessoires'),
+        speciale: derived('Protections + Accessoires'),
+        magique: derived('Protections + Accessoires'),
+
+        discretion: derived('Adresse Naturelle + Objets'),
+        magie_physique: derived('Moyenne sup. (Intelligence + Adresse) + Objets'),
+        magie_psychique: derived('Moyenne sup. (Intelligence + Charisme) + Objets'),
+        resistance_magique: derived('Moyenne sup. (Courage + Intelligence + Force) + Objets + Corruption'),
+
+        protection_pluie: derived('Protections + Accessoires'),
+        protection_froid: derived('Protections + Accessoires'),
+        protection_chaleur: derived('Protections + Accessoires'),
+
+        marche: derived('Arrondi sup. (Vitesse Origine * Encombrement PR sol) + Objets'),
+        course: derived('Arrondi sup. (Vitesse Origine * Encombrement PR sol) + Objets')
+    };
+
+    pushTo(totals.discretion, 'Adresse (Naturelle)', toInt(data.characteristics.adresse.naturel));
+
+    const int = equippedValues.intelligence.value;
+    const adr = equippedValues.adresse.value;
+    const cha = equippedValues.charisme.value;
+    const cour = equippedValues.courage.value;
+    const force = equippedValues.force.value;
+    pushTo(totals.magie_physique, `Moyenne sup. (INT ${int} + AD ${adr})`, Math.ceil((int + adr) / 2));
+    pushTo(totals.magie_psychique, `Moyenne sup. (INT ${int} + CHA ${cha})`, Math.ceil((int + cha) / 2));
+    pushTo(totals.resistance_magique, `Moyenne sup. (COU ${cour} + INT ${int} + FO ${force})`, Math.ceil((cour + int + force) / 3));
+
+    getWornItems(data, refs).forEach(({ item, ref }) => {
+        const nom = ref?.nom || item.nom;
+        const prots = ref?.protections || {};
+        const caracs = ref?.caracteristiques || {};
+
+        pushTo(totals.solide, nom, toInt(prots.pr_sol) + toInt(item.modif_pr_sol));
+        pushTo(totals.speciale, nom, toInt(prots.pr_spe) + toInt(item.modif_pr_spe));
+        pushTo(totals.magique, nom, toInt(prots.pr_mag) + toInt(item.modif_pr_mag));
+        pushTo(totals.protection_pluie, nom, toInt(prots.pluie));
+        pushTo(totals.protection_froid, nom, toInt(prots.froid));
+        pushTo(totals.protection_chaleur, nom, toInt(prots.chaleur));
+
+        pushTo(totals.discretion, nom, toInt(caracs.discretion));
+        pushTo(totals.magie_physique, nom, toInt(caracs.mag_phy));
+        pushTo(totals.magie_psychique, nom, toInt(caracs.mag_psy));
+        pushTo(totals.resistance_magique, nom, toInt(caracs.rm));
+
+        // mvt applies to both marche and course
+        pushTo(totals.marche, `${nom} (Mvt)`, toInt(caracs.mvt));
+        pushTo(totals.course, `${nom} (Mvt)`, toInt(caracs.mvt));
+        pushTo(totals.marche, nom, toInt(caracs.marche));
+        pushTo(totals.course, nom, toInt(caracs.course));
+    });
+    getAppliedEffects(data, 'pr_sol').forEach(c => pushTo(totals.solide, c.label, c.value));
+
+    const { specBonuses, subSpecBonuses } = getSpecBonuses(data, gameRules);
+    pushTo(totals.resistance_magique, 'Spécialisation', specBonuses['RM'] || 0);
+    pushTo(totals.resistance_magique, 'Sous-spécialisation', subSpecBonuses['RM'] || 0);
+    pushTo(totals.resistance_magique, 'Palier de corruption', getCorruptionRm(data, gameRules));
+
+    // Movement base from the origin, scaled by the PR solide worn
+    const originObj = gameRules?.origines.find((o: Origine) =>
+        o.name_m === data.identity.origine || o.name_f === data.identity.origine
+    );
+    if (originObj) {
+        const speed = originObj.vitesse;
+        const prSolide = totals.solide.value + toInt(data.defenses.solide.temp);
+
+        // Determine Multipliers based on PR Solide
+        let marcheMult = 8;
+        if (prSolide >= 2 && prSolide < 3) marcheMult = 6;
+        else if (prSolide >= 3 && prSolide <= 5) marcheMult = 4;
+        else if (prSolide === 6) marcheMult = 3;
+        else if (prSolide === 7) marcheMult = 2;
+        else if (prSolide > 7) marcheMult = 1;
+
+        let courseMult = 12;
+        if (prSolide >= 2 && prSolide < 3) courseMult = 10;
+        else if (prSolide >= 3 && prSolide <= 4) courseMult = 8;
+        else if (prSolide === 5) courseMult = 6;
+        else if (prSolide === 6) courseMult = 4;
+        else if (prSolide === 7) courseMult = 3;
+        else if (prSolide > 7) courseMult = 2;
+
+        pushTo(totals.marche, `Base origine: ${speed / 100} * (PR Sol ${prSolide} => x${marcheMult})`, Math.ceil(speed * marcheMult / 100));
+        pushTo(totals.course, `Base origine: ${speed / 100} * (PR Sol ${prSolide} => x${courseMult})`, Math.ceil(speed * courseMult / 100));
+
+        pushTo(totals.marche, 'Spécialisation', specBonuses['MVTm'] || 0);
+        pushTo(totals.marche, 'Sous-spécialisation', subSpecBonuses['MVTm'] || 0);
+        pushTo(totals.course, 'Spécialisation', specBonuses['MVTc'] || 0);
+        pushTo(totals.course, 'Sous-spécialisation', subSpecBonuses['MVTc'] || 0);
+    }
+
+    (['discretion', 'magie_physique', 'magie_psychique', 'resistance_magique', 'protection_pluie', 'protection_froid', 'protection_chaleur', 'marche', 'course'] as const)
+        .forEach(key => getAppliedEffects(data, key).forEach(c => pushTo(totals[key], c.label, c.value)));
+
+    return totals;
+};